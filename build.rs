@@ -10,6 +10,10 @@ fn main() {
         .unwrap_or_else(|| std::env::var("CARGO_PKG_VERSION").unwrap());
 
     println!("cargo:rustc-env=CS_VERSION={}", version);
+
+    // Record the target triple for updater diagnostics
+    let target = std::env::var("TARGET").unwrap_or_else(|_| "unknown".to_string());
+    println!("cargo:rustc-env=CS_TARGET={}", target);
 }
 
 fn get_git_tag_version() -> Option<String> {
@@ -0,0 +1,545 @@
+//! User configuration (`~/.cs/config.toml`)
+//!
+//! cs keeps its dependency footprint minimal, so this is a small parser for the
+//! subset of TOML the config needs: `key = value` pairs, `[section]` headers,
+//! `#` comments, and string/boolean/integer/string-array values.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+/// Default delimiter between folder and branch in session names
+pub const DEFAULT_SEPARATOR: &str = "+";
+
+/// Default number of database backups to keep
+pub const DEFAULT_BACKUP_RETENTION: usize = 10;
+
+/// Default stderr text identifying Claude's failure to resume a missing conversation
+pub const DEFAULT_MISSING_SESSION_STDERR: &str = "No conversation found";
+
+/// Default age (days since last use) after which `--gc` prunes a session
+pub const DEFAULT_PRUNE_AFTER_DAYS: u64 = 90;
+
+/// A single config value
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfigValue {
+    String(String),
+    Bool(bool),
+    Integer(i64),
+    Array(Vec<String>),
+}
+
+impl ConfigValue {
+    /// Name of the value's kind, as used in validation messages
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ConfigValue::String(_) => "string",
+            ConfigValue::Bool(_) => "boolean",
+            ConfigValue::Integer(_) => "integer",
+            ConfigValue::Array(_) => "array",
+        }
+    }
+}
+
+/// Keys cs reads and the kind of value each expects
+const KNOWN_KEYS: &[(&str, &str)] = &[
+    ("allow_positional", "boolean"),
+    ("backup_retention", "integer"),
+    ("base_ref", "string"),
+    ("case_insensitive", "boolean"),
+    ("check_paths", "boolean"),
+    ("claude_launcher", "array"),
+    ("confirm_new", "boolean"),
+    ("default_args", "array"),
+    ("group_by", "string"),
+    ("loose_args", "boolean"),
+    ("managed_paths", "array"),
+    ("min_claude_version", "string"),
+    ("missing_session_exit_code", "integer"),
+    ("missing_session_stderr", "string"),
+    ("prune_after_days", "integer"),
+    ("recover_missing_sessions", "boolean"),
+    ("rename_branch_aware", "boolean"),
+    ("require_git", "boolean"),
+    ("separator", "string"),
+    ("session_lock", "boolean"),
+    ("unmanaged_paths", "array"),
+];
+
+/// Kind of value cs expects for `key` (`[prompts]` entries are strings),
+/// or None for a key cs doesn't read
+pub fn expected_kind(key: &str) -> Option<&'static str> {
+    if key.starts_with("prompts.") {
+        return Some("string");
+    }
+    KNOWN_KEYS.iter().find(|(known, _)| *known == key).map(|(_, kind)| *kind)
+}
+
+/// Parsed configuration, keyed by `section.key` (top-level keys have no prefix)
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Config {
+    pub values: BTreeMap<String, ConfigValue>,
+}
+
+impl Config {
+    /// Get a string value
+    pub fn get_str(&self, key: &str) -> Option<&str> {
+        match self.values.get(key) {
+            Some(ConfigValue::String(s)) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// Get a boolean value
+    pub fn get_bool(&self, key: &str) -> Option<bool> {
+        match self.values.get(key) {
+            Some(ConfigValue::Bool(b)) => Some(*b),
+            _ => None,
+        }
+    }
+
+    /// Get an integer value
+    pub fn get_int(&self, key: &str) -> Option<i64> {
+        match self.values.get(key) {
+            Some(ConfigValue::Integer(n)) => Some(*n),
+            _ => None,
+        }
+    }
+
+    /// Get a string array value (empty if unset or not an array)
+    pub fn get_array(&self, key: &str) -> &[String] {
+        match self.values.get(key) {
+            Some(ConfigValue::Array(items)) => items,
+            _ => &[],
+        }
+    }
+
+    /// Directories where cs manages sessions (`managed_paths`; empty = everywhere)
+    pub fn managed_paths(&self) -> &[String] {
+        self.get_array("managed_paths")
+    }
+
+    /// Directories where cs forwards straight to claude (`unmanaged_paths`)
+    pub fn unmanaged_paths(&self) -> &[String] {
+        self.get_array("unmanaged_paths")
+    }
+
+    /// Claude arguments added to every launch (`default_args`)
+    pub fn default_args(&self) -> &[String] {
+        self.get_array("default_args")
+    }
+
+    /// Program and leading arguments that run claude, e.g. `["npx", "@anthropic-ai/claude-code"]`
+    /// (`claude_launcher`; empty = the `claude` binary)
+    pub fn claude_launcher(&self) -> &[String] {
+        self.get_array("claude_launcher")
+    }
+
+    /// Trunk whose merge-base with HEAD becomes part of session names (`base_ref`)
+    pub fn base_ref(&self) -> Option<&str> {
+        self.get_str("base_ref").filter(|v| !v.is_empty())
+    }
+
+    /// How `--list` clusters sessions (`group_by`: folder, branch, or tag)
+    pub fn group_by(&self) -> Option<&str> {
+        self.get_str("group_by").filter(|v| !v.is_empty())
+    }
+
+    /// Whether to ask before creating a new session (`confirm_new`)
+    pub fn confirm_new(&self) -> bool {
+        self.get_bool("confirm_new").unwrap_or(false)
+    }
+
+    /// Whether folder names are lowercased and normalized before hashing (`case_insensitive`)
+    pub fn case_insensitive(&self) -> bool {
+        self.get_bool("case_insensitive").unwrap_or(false)
+    }
+
+    /// Whether to refuse folder-only sessions outside git repositories (`require_git`)
+    pub fn require_git(&self) -> bool {
+        self.get_bool("require_git").unwrap_or(false)
+    }
+
+    /// Prompt template `name` from the `[prompts]` section
+    pub fn prompt(&self, name: &str) -> Option<&str> {
+        self.get_str(&format!("prompts.{}", name))
+    }
+
+    /// Names of the templates in the `[prompts]` section, sorted
+    pub fn prompt_names(&self) -> Vec<&str> {
+        self.values.keys().filter_map(|key| key.strip_prefix("prompts.")).collect()
+    }
+
+    /// Whether bare arguments are forwarded to claude as a prompt (`allow_positional`)
+    pub fn allow_positional(&self) -> bool {
+        self.get_bool("allow_positional").unwrap_or(true)
+    }
+
+    /// Whether unknown `--key=value` arguments are forwarded to claude instead of rejected (`loose_args`)
+    pub fn loose_args(&self) -> bool {
+        self.get_bool("loose_args").unwrap_or(false)
+    }
+
+    /// Whether to refuse launching a session already open elsewhere (`session_lock`)
+    pub fn session_lock(&self) -> bool {
+        self.get_bool("session_lock").unwrap_or(false)
+    }
+
+    /// Whether to offer another branch's session after a likely rename (`rename_branch_aware`)
+    pub fn rename_branch_aware(&self) -> bool {
+        self.get_bool("rename_branch_aware").unwrap_or(false)
+    }
+
+    /// Whether to verify path-valued claude flags before launching (`check_paths`)
+    pub fn check_paths(&self) -> bool {
+        self.get_bool("check_paths").unwrap_or(false)
+    }
+
+    /// Number of database backups to keep (`backup_retention`), at least one so the
+    /// backup just taken is never pruned
+    pub fn backup_retention(&self) -> usize {
+        self.get_int("backup_retention")
+            .and_then(|n| usize::try_from(n).ok())
+            .unwrap_or(DEFAULT_BACKUP_RETENTION)
+            .max(1)
+    }
+
+    /// Days since last use after which `--gc` prunes a session (`prune_after_days`)
+    pub fn prune_after_days(&self) -> u64 {
+        self.get_int("prune_after_days")
+            .and_then(|n| u64::try_from(n).ok())
+            .unwrap_or(DEFAULT_PRUNE_AFTER_DAYS)
+    }
+
+    /// Stderr text marking a failed resume of a missing conversation (`missing_session_stderr`; "" disables)
+    pub fn missing_session_stderr(&self) -> &str {
+        self.get_str("missing_session_stderr").unwrap_or(DEFAULT_MISSING_SESSION_STDERR)
+    }
+
+    /// Exit code marking a failed resume of a missing conversation (`missing_session_exit_code`)
+    pub fn missing_session_exit_code(&self) -> Option<i32> {
+        self.get_int("missing_session_exit_code").and_then(|n| i32::try_from(n).ok())
+    }
+
+    /// Whether to start a session afresh when Claude's store lacks its conversation (`recover_missing_sessions`)
+    pub fn recover_missing_sessions(&self) -> bool {
+        self.get_bool("recover_missing_sessions").unwrap_or(false)
+    }
+
+    /// Oldest Claude CLI version cs will launch (`min_claude_version`)
+    pub fn min_claude_version(&self) -> Option<&str> {
+        self.get_str("min_claude_version").filter(|v| !v.is_empty())
+    }
+
+    /// Expand environment variables in every string and array value
+    /// (of a config parsed with escapes kept for expansion)
+    fn expand_env(mut self, lookup: &dyn Fn(&str) -> Option<String>, strict: bool) -> Result<Config, String> {
+        for (key, value) in self.values.iter_mut() {
+            let expand = |s: &str| expand_env_vars(s, lookup, strict).map_err(|e| format!("{}: {}", key, e));
+            match value {
+                ConfigValue::String(s) => *s = expand(s)?,
+                ConfigValue::Array(items) => {
+                    for item in items.iter_mut() {
+                        *item = expand(item)?;
+                    }
+                }
+                ConfigValue::Bool(_) | ConfigValue::Integer(_) => {}
+            }
+        }
+        Ok(self)
+    }
+
+    /// Separator placed between folder and branch in session names
+    pub fn separator(&self) -> &str {
+        self.get_str("separator").unwrap_or(DEFAULT_SEPARATOR)
+    }
+}
+
+/// Parse a quoted string value, returning the unescaped content. With
+/// `expandable`, backslashes and `$` escapes are kept escaped (`\\`, `\$`) for
+/// [`expand_env_vars`] to resolve.
+fn parse_string(raw: &str, line_no: usize, expandable: bool) -> Result<String, String> {
+    let quote = raw.chars().next().unwrap_or('"');
+    let inner = raw
+        .strip_prefix(quote)
+        .and_then(|s| s.strip_suffix(quote))
+        .ok_or_else(|| format!("line {}: unterminated string", line_no))?;
+
+    // Literal strings ('...') have no escapes
+    if quote == '\'' {
+        return Ok(if expandable { inner.replace('\\', "\\\\") } else { inner.to_string() });
+    }
+
+    let mut out = String::new();
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('t') => out.push('\t'),
+            Some('"') => out.push('"'),
+            // Kept escaped so environment expansion treats them as literals
+            Some('\\') if expandable => out.push_str("\\\\"),
+            Some('$') if expandable => out.push_str("\\$"),
+            Some(escaped @ ('\\' | '$')) => out.push(escaped),
+            Some(other) => {
+                return Err(format!("line {}: unknown escape '\\{}'", line_no, other));
+            }
+            None => return Err(format!("line {}: trailing backslash in string", line_no)),
+        }
+    }
+    Ok(out)
+}
+
+/// Expand `$VAR` and `${VAR}` references in a config string using `lookup`.
+/// `\$` is a literal `$` and `\\` a literal backslash. Unknown variables are
+/// left as written, or are an error when `strict` is set.
+pub fn expand_env_vars(
+    value: &str,
+    lookup: &dyn Fn(&str) -> Option<String>,
+    strict: bool,
+) -> Result<String, String> {
+    let is_name_char = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    let mut out = String::new();
+    let mut rest = value;
+
+    while let Some(pos) = rest.find(['\\', '$']) {
+        out.push_str(&rest[..pos]);
+        let tail = &rest[pos..];
+        if let Some(after) = tail.strip_prefix("\\$") {
+            out.push('$');
+            rest = after;
+            continue;
+        }
+        if let Some(after) = tail.strip_prefix("\\\\") {
+            out.push('\\');
+            rest = after;
+            continue;
+        }
+        if let Some(after) = tail.strip_prefix('\\') {
+            out.push('\\');
+            rest = after;
+            continue;
+        }
+
+        // `${NAME}` or `$NAME`; anything else is a plain `$`
+        let (name, written, after) = match tail[1..].strip_prefix('{') {
+            Some(braced) => match braced.find('}') {
+                Some(end) => (&braced[..end], &tail[..end + 3], &braced[end + 1..]),
+                None => ("", "$", &tail[1..]),
+            },
+            None => {
+                let end = tail[1..].find(|c: char| !is_name_char(c)).map_or(tail.len(), |i| i + 1);
+                (&tail[1..end], &tail[..end], &tail[end..])
+            }
+        };
+        let valid = name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') && name.chars().all(is_name_char);
+        if !valid {
+            out.push('$');
+            rest = &tail[1..];
+            continue;
+        }
+        match lookup(name) {
+            Some(expanded) => out.push_str(&expanded),
+            None if strict => return Err(format!("unknown environment variable '{}'", name)),
+            None => out.push_str(written),
+        }
+        rest = after;
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// Split the inside of an array literal on commas that are outside quotes
+fn split_array_items(inner: &str) -> Vec<String> {
+    let mut items = Vec::new();
+    let mut current = String::new();
+    let mut quote: Option<char> = None;
+    let mut escaped = false;
+
+    for c in inner.chars() {
+        match quote {
+            Some(q) => {
+                current.push(c);
+                if escaped {
+                    escaped = false;
+                } else if c == '\\' && q == '"' {
+                    escaped = true;
+                } else if c == q {
+                    quote = None;
+                }
+            }
+            None if c == '"' || c == '\'' => {
+                quote = Some(c);
+                current.push(c);
+            }
+            None if c == ',' => {
+                items.push(current.trim().to_string());
+                current.clear();
+            }
+            None => current.push(c),
+        }
+    }
+    if !current.trim().is_empty() {
+        items.push(current.trim().to_string());
+    }
+    items
+}
+
+/// Parse the right-hand side of a `key = value` line
+fn parse_value(raw: &str, line_no: usize, expandable: bool) -> Result<ConfigValue, String> {
+    if raw.starts_with('"') || raw.starts_with('\'') {
+        return parse_string(raw, line_no, expandable).map(ConfigValue::String);
+    }
+    if let Some(inner) = raw.strip_prefix('[').and_then(|s| s.strip_suffix(']')) {
+        let items = split_array_items(inner)
+            .iter()
+            .map(|item| parse_string(item, line_no, expandable))
+            .collect::<Result<Vec<_>, _>>()?;
+        return Ok(ConfigValue::Array(items));
+    }
+    match raw {
+        "true" => return Ok(ConfigValue::Bool(true)),
+        "false" => return Ok(ConfigValue::Bool(false)),
+        _ => {}
+    }
+    raw.parse::<i64>()
+        .map(ConfigValue::Integer)
+        .map_err(|_| format!("line {}: invalid value '{}'", line_no, raw))
+}
+
+/// Remove a trailing `# comment` that is outside of any quotes
+fn strip_comment(line: &str) -> &str {
+    let mut quote: Option<char> = None;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match quote {
+            Some(q) => {
+                if escaped {
+                    escaped = false;
+                } else if c == '\\' && q == '"' {
+                    escaped = true;
+                } else if c == q {
+                    quote = None;
+                }
+            }
+            None if c == '"' || c == '\'' => quote = Some(c),
+            None if c == '#' => return &line[..i],
+            None => {}
+        }
+    }
+    line
+}
+
+/// Parse config file content, taking string values literally (no environment expansion)
+pub fn parse_config(content: &str) -> Result<Config, String> {
+    parse_config_content(content, false)
+}
+
+/// Parse config file content and expand environment variables in string values
+pub fn parse_config_with_env(
+    content: &str,
+    lookup: &dyn Fn(&str) -> Option<String>,
+    strict: bool,
+) -> Result<Config, String> {
+    parse_config_content(content, true)?.expand_env(lookup, strict)
+}
+
+/// Parse config file content; see [`parse_string`] for `expandable`
+fn parse_config_content(content: &str, expandable: bool) -> Result<Config, String> {
+    let mut config = Config::default();
+    let mut section = String::new();
+
+    for (i, raw_line) in content.lines().enumerate() {
+        let line_no = i + 1;
+        let line = strip_comment(raw_line).trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        if let Some(name) = line.strip_prefix('[').and_then(|s| s.strip_suffix(']')) {
+            let name = name.trim();
+            if name.is_empty() {
+                return Err(format!("line {}: empty section name", line_no));
+            }
+            section = name.to_string();
+            continue;
+        }
+
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| format!("line {}: expected 'key = value'", line_no))?;
+        let key = key.trim();
+        if key.is_empty() {
+            return Err(format!("line {}: missing key", line_no));
+        }
+        let value = parse_value(value.trim(), line_no, expandable)?;
+        let full_key = if section.is_empty() {
+            key.to_string()
+        } else {
+            format!("{}.{}", section, key)
+        };
+        config.values.insert(full_key, value);
+    }
+
+    Ok(config)
+}
+
+/// Format a value as a TOML basic string
+pub fn format_string(value: &str) -> String {
+    let mut out = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Set a top-level string-array key in config file content, replacing an
+/// existing single-line definition or adding one before the first section
+pub fn set_top_level_array(content: &str, key: &str, items: &[String]) -> String {
+    let items: Vec<String> = items.iter().map(|item| format_string(item)).collect();
+    let assignment = format!("{} = [{}]", key, items.join(", "));
+
+    let mut lines: Vec<String> = content.lines().map(str::to_string).collect();
+    let first_section = lines
+        .iter()
+        .position(|line| line.trim_start().starts_with('['))
+        .unwrap_or(lines.len());
+    let existing = lines[..first_section].iter().position(|line| {
+        strip_comment(line).split_once('=').is_some_and(|(k, _)| k.trim() == key)
+    });
+    match existing {
+        Some(index) => lines[index] = assignment,
+        None => lines.insert(first_section, assignment),
+    }
+
+    let mut out = lines.join("\n");
+    out.push('\n');
+    out
+}
+
+/// Load config from a file, expanding environment variables in string values.
+/// A missing file yields the defaults; a file that can't be read or parsed is
+/// an error, as is an unknown variable when `strict` is set, or an empty
+/// separator (folder and branch would run together into ambiguous names).
+pub fn load_config(path: &Path, strict: bool) -> Result<Config, String> {
+    let config = match fs::read_to_string(path) {
+        Ok(content) => parse_config_with_env(&content, &|name| std::env::var(name).ok(), strict)?,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Config::default()),
+        Err(e) => return Err(e.to_string()),
+    };
+    if config.get_str("separator") == Some("") {
+        return Err("separator: must not be empty".to_string());
+    }
+    Ok(config)
+}
@@ -3,8 +3,15 @@ use std::collections::HashSet;
 use std::env;
 use std::fs::{self, File, OpenOptions};
 use std::io::{BufRead, BufReader, Write};
-use std::path::PathBuf;
-use std::process::{Command, exit};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitStatus, exit};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+mod config;
+
+use config::{load_config, parse_config, parse_config_with_env, set_top_level_array, Config, DEFAULT_SEPARATOR};
 
 // Unix-specific import for exec()
 #[cfg(unix)]
@@ -55,11 +62,190 @@ const CLAUDE_VALUE_FLAGS: &[&str] = &[
     "--tools",
 ];
 
+/// Claude CLI value flags that take a file path (checked by `--check-paths`)
+const CLAUDE_PATH_FLAGS: &[&str] = &["--file", "--json-schema", "--mcp-config", "--settings"];
+
+/// Claude CLI value flags that may be repeated; `--add-dir a,b` expands to
+/// `--add-dir a --add-dir b` before being passed through
+const CLAUDE_REPEATABLE_FLAGS: &[&str] = &["--add-dir", "--plugin-dir"];
+
+/// Common Claude model names (offered as `--model` completions)
+const CLAUDE_MODEL_VALUES: &[&str] = &["opus", "sonnet", "haiku"];
+
+/// Claude CLI output formats (offered as `--output-format` completions)
+const CLAUDE_OUTPUT_FORMAT_VALUES: &[&str] = &["text", "json", "stream-json"];
+
+/// Claude CLI value flags with a known set of values (for shell completions)
+const CLAUDE_FLAG_VALUES: &[(&str, &[&str])] = &[
+    ("--fallback-model", CLAUDE_MODEL_VALUES),
+    ("--model", CLAUDE_MODEL_VALUES),
+    ("--output-format", CLAUDE_OUTPUT_FORMAT_VALUES),
+];
+
 /// Claude CLI subcommands (bypass session logic entirely)
 const CLAUDE_SUBCOMMANDS: &[&str] = &[
     "doctor", "install", "mcp", "plugin", "setup-token", "update",
 ];
 
+/// cs-specific flags (offered by shell completions)
+const CS_FLAGS: &[&str] = &[
+    "--adopt",
+    "--age",
+    "--append-arg",
+    "--ascii",
+    "--assert-session",
+    "--backup",
+    "--batch",
+    "--check-paths",
+    "--claude-args-file",
+    "--clear",
+    "--clipboard",
+    "--clone",
+    "--color",
+    "--completions",
+    "--confirm-new",
+    "--copy-cmd",
+    "--dedupe-names",
+    "--describe",
+    "--doctor-claude",
+    "-n", "--dry-run",
+    "--env",
+    "--explain-uuid",
+    "--fail-if-new",
+    "-f", "--force",
+    "--gc",
+    "--full",
+    "--git-info",
+    "-h", "--help",
+    "--import-legacy",
+    "--interactive",
+    "--json",
+    "--json-schema-out",
+    "--last-exit",
+    "--launch-dir",
+    "--namespace", "--namespace-name",
+    "--new-suffix",
+    "-l", "--list",
+    "--loose",
+    "--mru",
+    "--no-git",
+    "--no-orphans",
+    "--no-positional",
+    "--no-prune",
+    "--no-repair",
+    "--open-claude-config",
+    "--open-db",
+    "--print-argv",
+    "--print-name",
+    "--profile",
+    "--prompt-template",
+    "--recent",
+    "--remote-list",
+    "--rename-branch-aware",
+    "--repair",
+    "--repo",
+    "--reveal",
+    "--require-git",
+    "--reset",
+    "-R", "--resume",
+    "--resume-all",
+    "--resume-uuid",
+    "--rm",
+    "--self-test",
+    "--session-name",
+    "--short",
+    "--since-commit",
+    "--skip-version-check",
+    "--status",
+    "--stdin-json",
+    "--tag",
+    "--timeout",
+    "--touch-all",
+    "-U",
+    "--uuid-out",
+    "-v", "--version",
+    "--watch",
+    "--which-session",
+    "-y", "--yes",
+];
+
+/// cs short boolean flags that can be combined into one argument (`-fn`)
+const CS_SHORT_BOOL_FLAGS: &[char] = &['f', 'h', 'l', 'n', 'R', 'v', 'y'];
+
+/// Whether `arg` looks like several short flags combined (`-fn`)
+fn is_short_cluster(arg: &str) -> bool {
+    arg.strip_prefix('-')
+        .is_some_and(|rest| rest.len() > 1 && rest.chars().all(|c| c.is_ascii_alphabetic()))
+}
+
+/// Split a cluster of short flags (`-fn` -> `-f`, `-n`). Only cs's short
+/// boolean flags can be combined; value-taking flags are rejected.
+fn expand_short_cluster(arg: &str) -> Result<Vec<String>, String> {
+    arg.chars()
+        .skip(1)
+        .map(|c| {
+            let flag = format!("-{}", c);
+            if CS_SHORT_BOOL_FLAGS.contains(&c) {
+                Ok(flag)
+            } else if CLAUDE_VALUE_FLAGS.contains(&flag.as_str()) {
+                Err(format!("'{}' in '{}' takes a value and can't be combined with other flags", flag, arg))
+            } else {
+                Err(format!("'{}' in '{}' is not a cs short flag that can be combined", flag, arg))
+            }
+        })
+        .collect()
+}
+
+/// cs-specific subcommands
+const CS_SUBCOMMANDS: &[&str] = &["config", "init", "stats", "trust", "upgrade"];
+
+/// Shells supported by `cs init` and `cs --completions`
+const SUPPORTED_SHELLS: &[&str] = &["bash", "zsh", "fish"];
+
+/// Environment variables honored by cs (name, description)
+const CS_ENV_VARS: &[(&str, &str)] = &[
+    ("CS_NAMESPACE", "Custom UUID v5 namespace (default: DNS namespace)"),
+    ("CS_HOME", "cs's data directory, used instead of ~/.cs (HOME is left alone)"),
+    ("CS_PROFILE", "Named profile: database and config under ~/.cs/profiles/<name>"),
+    ("CS_DB_PATH", "Session database location (default: ~/.cs/sessions)"),
+    ("CS_CONFIG_PATH", "Config file location (default: $XDG_CONFIG_HOME/cs/config.toml if present, else ~/.cs/config.toml)"),
+    ("CS_READONLY", "Set to 1 to never modify the session database"),
+    ("CS_ASCII", "Set to 1 to draw the info box with ASCII (automatic when TERM=dumb)"),
+    ("CS_CLAUDE_ARGS", "Default claude args for every launch, e.g. \"--model opus\" (command-line flags win)"),
+    ("CS_CLAUDE_LAUNCHER", "Command that runs claude, e.g. \"npx @anthropic-ai/claude-code\" (overrides claude_launcher)"),
+    ("CS_SESSION_SUFFIX", "Suffix for a parallel session in the same directory (name becomes folder+branch#suffix)"),
+    ("CS_STRICT_CONFIG", "Set to 1 to make an unreadable or invalid config file a fatal error"),
+    ("CS_UPDATE_RETRIES", "Download attempts for cs upgrade on network errors (default: 3)"),
+    ("CLAUDE_CONFIG_DIR", "Claude's config directory, used to find its session store (default: ~/.claude)"),
+];
+
+/// Exit code: general error
+const EXIT_ERROR: i32 = 1;
+/// Exit code: `--dry-run --fail-if-new` found no existing session
+const EXIT_NEW_SESSION: i32 = 2;
+/// Exit code: Claude CLI is older than `min_claude_version`
+const EXIT_CLAUDE_TOO_OLD: i32 = 3;
+/// Exit code: `--git-info` found no git repository (folder-only session)
+const EXIT_NOT_GIT: i32 = 5;
+/// Exit code: usage error (bad config with `CS_STRICT_CONFIG=1`, missing file with `--check-paths`)
+const EXIT_USAGE: i32 = 64;
+/// Exit code: an output file couldn't be written (`--uuid-out`; sysexits EX_CANTCREAT)
+const EXIT_CANT_CREATE: i32 = 73;
+/// Exit code: Claude exceeded `--timeout` and was killed (matches GNU `timeout`)
+const EXIT_TIMEOUT: i32 = 124;
+/// Exit code: Claude CLI not found in PATH
+const EXIT_CLAUDE_NOT_FOUND: i32 = 127;
+
+/// Whether cs's own verbose logging is enabled (set by `--verbose`)
+static VERBOSE: AtomicBool = AtomicBool::new(false);
+
+/// Print a cs diagnostic line to stderr when `--verbose` is active
+fn verbose_log(message: &str) {
+    if VERBOSE.load(Ordering::Relaxed) {
+        eprintln!("cs: {}", message);
+    }
+}
+
 /// Default UUID v5 namespace (DNS namespace from RFC 4122)
 const DEFAULT_NAMESPACE: [u8; 16] = [
     0x6b, 0xa7, 0xb8, 0x10,
@@ -68,6 +254,37 @@ const DEFAULT_NAMESPACE: [u8; 16] = [
     0x4f, 0xd4, 0x30, 0xc8,
 ];
 
+/// RFC 4122 well-known namespaces, accepted by name for `--namespace` and CS_NAMESPACE
+const WELL_KNOWN_NAMESPACES: &[(&str, [u8; 16])] = &[
+    ("dns", DEFAULT_NAMESPACE),
+    ("url", [
+        0x6b, 0xa7, 0xb8, 0x11,
+        0x9d, 0xad, 0x11, 0xd1,
+        0x80, 0xb4, 0x00, 0xc0,
+        0x4f, 0xd4, 0x30, 0xc8,
+    ]),
+    ("oid", [
+        0x6b, 0xa7, 0xb8, 0x12,
+        0x9d, 0xad, 0x11, 0xd1,
+        0x80, 0xb4, 0x00, 0xc0,
+        0x4f, 0xd4, 0x30, 0xc8,
+    ]),
+    ("x500", [
+        0x6b, 0xa7, 0xb8, 0x14,
+        0x9d, 0xad, 0x11, 0xd1,
+        0x80, 0xb4, 0x00, 0xc0,
+        0x4f, 0xd4, 0x30, 0xc8,
+    ]),
+];
+
+/// Look up a well-known namespace by name (`dns`, `url`, `oid`, `x500`; any case)
+fn well_known_namespace(name: &str) -> Option<[u8; 16]> {
+    WELL_KNOWN_NAMESPACES
+        .iter()
+        .find(|(known, _)| known.eq_ignore_ascii_case(name))
+        .map(|(_, bytes)| *bytes)
+}
+
 /// Parse a UUID string (e.g., "6ba7b810-9dad-11d1-80b4-00c04fd430c8") into bytes
 fn parse_uuid(uuid_str: &str) -> Option<[u8; 16]> {
     let hex: String = uuid_str.chars().filter(|c| c.is_ascii_hexdigit()).collect();
@@ -83,11 +300,87 @@ fn parse_uuid(uuid_str: &str) -> Option<[u8; 16]> {
     Some(bytes)
 }
 
-/// Get namespace from CS_NAMESPACE env var or use default
+/// Parse a UUID given explicitly by the user. Unlike `parse_uuid`, only the
+/// canonical 8-4-4-4-12 hyphenated form or exactly 32 undashed hex digits are accepted.
+fn parse_uuid_strict(uuid_str: &str) -> Option<[u8; 16]> {
+    let well_formed = if uuid_str.len() == 32 {
+        uuid_str.chars().all(|c| c.is_ascii_hexdigit())
+    } else {
+        let groups: Vec<&str> = uuid_str.split('-').collect();
+        groups.len() == 5
+            && groups.iter().zip([8, 4, 4, 4, 12]).all(|(group, len)| {
+                group.len() == len && group.chars().all(|c| c.is_ascii_hexdigit())
+            })
+    };
+    if well_formed { parse_uuid(uuid_str) } else { None }
+}
+
+/// Meta-namespace for `--namespace-name`
+/// (UUID v5 of "namespace.cs.claude-code-resumer" in the DNS namespace)
+const NAMESPACE_META: [u8; 16] = [
+    0xb8, 0x35, 0xa6, 0xba,
+    0xaa, 0x0e, 0x5d, 0xe4,
+    0x9a, 0x78, 0xe6, 0x50,
+    0x6e, 0x42, 0x7d, 0x0d,
+];
+
+/// Namespace chosen for this invocation by `--namespace`/`--namespace-name`
+static NAMESPACE_OVERRIDE: OnceLock<[u8; 16]> = OnceLock::new();
+
+/// Derive a namespace from a human-readable string (`--namespace-name`)
+fn derive_namespace(name: &str) -> [u8; 16] {
+    uuid5_bytes(&NAMESPACE_META, name)
+}
+
+/// The arguments (after the program name) that may be cs flags, for the
+/// scans that run before parsing: everything before a `--`, without the
+/// values of claude value flags, so `cs --system-prompt --profile` or
+/// `cs -p -- --namespace` leave cs's settings alone
+fn cs_args(args: &[String]) -> Vec<&str> {
+    let mut found = Vec::new();
+    let mut iter = args.iter().skip(1).map(String::as_str);
+    while let Some(arg) = iter.next() {
+        if arg == "--" {
+            break;
+        }
+        if CLAUDE_VALUE_FLAGS.contains(&arg) {
+            iter.next();
+            continue;
+        }
+        found.push(arg);
+    }
+    found
+}
+
+/// Find `--namespace <uuid|dns|url|oid|x500>` / `--namespace-name <str>` in the
+/// arguments. An explicit namespace wins over a derived one.
+fn namespace_from_args(args: &[String]) -> Result<Option<[u8; 16]>, String> {
+    let args = cs_args(args);
+    let value_of = |flag: &str| -> Result<Option<&str>, String> {
+        match args.iter().position(|a| *a == flag) {
+            Some(i) => args.get(i + 1).copied().map(Some).ok_or_else(|| format!("'{}' requires a value", flag)),
+            None => Ok(None),
+        }
+    };
+    if let Some(uuid) = value_of("--namespace")? {
+        return well_known_namespace(uuid)
+            .or_else(|| parse_uuid_strict(uuid))
+            .map(Some)
+            .ok_or_else(|| {
+                format!("'--namespace' requires a UUID or one of dns, url, oid, x500, got '{}'", uuid)
+            });
+    }
+    Ok(value_of("--namespace-name")?.map(derive_namespace))
+}
+
+/// Get namespace from --namespace/--namespace-name, the CS_NAMESPACE env var, or use default
 fn get_namespace() -> [u8; 16] {
+    if let Some(namespace) = NAMESPACE_OVERRIDE.get() {
+        return *namespace;
+    }
     env::var("CS_NAMESPACE")
         .ok()
-        .and_then(|s| parse_uuid(&s))
+        .and_then(|s| well_known_namespace(&s).or_else(|| parse_uuid(&s)))
         .unwrap_or(DEFAULT_NAMESPACE)
 }
 
@@ -108,573 +401,5888 @@ fn get_home_dir() -> Option<PathBuf> {
     None
 }
 
-/// Get the path to the sessions database file (~/.cs/sessions)
-/// Can be overridden with CS_DB_PATH environment variable (useful for testing)
-fn get_db_path() -> PathBuf {
-    if let Ok(custom_path) = env::var("CS_DB_PATH") {
-        return PathBuf::from(custom_path);
+/// Get cs's own directory: CS_HOME if set, otherwise ~/.cs
+fn get_cs_dir() -> PathBuf {
+    if let Ok(cs_home) = env::var("CS_HOME") {
+        return PathBuf::from(cs_home);
     }
     let home = get_home_dir().unwrap_or_else(|| PathBuf::from("."));
-    home.join(".cs").join("sessions")
+    home.join(".cs")
 }
 
-/// Load existing session UUIDs from database
-fn load_sessions() -> HashSet<String> {
-    let db_path = get_db_path();
-    let mut sessions = HashSet::new();
+/// Profile chosen for this invocation by `--profile`
+static PROFILE_OVERRIDE: OnceLock<String> = OnceLock::new();
 
-    if let Ok(file) = File::open(&db_path) {
-        let reader = BufReader::new(file);
-        for line in reader.lines().map_while(Result::ok) {
-            let uuid = line.trim().to_string();
-            if !uuid.is_empty() {
-                sessions.insert(uuid);
-            }
-        }
+/// Check a profile name: letters, digits, `-`, `_` and `.` (not starting with `.`)
+fn validate_profile(name: &str) -> Result<(), String> {
+    let valid = !name.is_empty()
+        && !name.starts_with('.')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    if valid {
+        Ok(())
+    } else {
+        Err(format!("invalid profile name '{}' (use letters, digits, '-', '_' or '.')", name))
     }
-
-    sessions
 }
 
-/// Save a new session UUID to the database
-fn save_session(uuid: &str) {
-    let db_path = get_db_path();
-
-    // Create directory if it doesn't exist
-    if let Some(parent) = db_path.parent() {
-        let _ = fs::create_dir_all(parent);
+/// Find `--profile <name>` in the arguments
+fn profile_from_args(args: &[String]) -> Result<Option<String>, String> {
+    let args = cs_args(args);
+    match args.iter().position(|a| *a == "--profile") {
+        Some(i) => {
+            let name = args.get(i + 1).ok_or("'--profile' requires a name")?;
+            validate_profile(name)?;
+            Ok(Some(name.to_string()))
+        }
+        None => Ok(None),
     }
+}
 
-    // Append UUID to file
-    if let Ok(mut file) = OpenOptions::new()
-        .create(true)
-        .append(true)
-        .open(&db_path)
-    {
-        let _ = writeln!(file, "{}", uuid);
+/// Check CS_PROFILE, so a bad name is reported instead of quietly falling
+/// back to the default database ("default" and empty mean no profile)
+fn validate_profile_env() -> Result<(), String> {
+    match env::var("CS_PROFILE") {
+        Ok(name) if !name.is_empty() && name != "default" => {
+            validate_profile(&name).map_err(|e| format!("CS_PROFILE: {}", e))
+        }
+        _ => Ok(()),
     }
 }
 
-/// Remove a session UUID from the database
-fn remove_session(uuid: &str) {
-    let db_path = get_db_path();
-    if let Ok(content) = fs::read_to_string(&db_path) {
-        let filtered: Vec<&str> = content
-            .lines()
-            .filter(|line| line.trim() != uuid)
-            .collect();
-        // Write back with newline at end if there are entries
-        let new_content = if filtered.is_empty() {
-            String::new()
-        } else {
-            filtered.join("\n") + "\n"
-        };
-        let _ = fs::write(&db_path, new_content);
-    }
+/// Get the active profile from --profile or CS_PROFILE.
+/// None (or "default") means the top-level database and config.
+fn get_profile() -> Option<String> {
+    PROFILE_OVERRIDE
+        .get()
+        .cloned()
+        .or_else(|| env::var("CS_PROFILE").ok())
+        .filter(|name| !name.is_empty() && name != "default" && validate_profile(name).is_ok())
 }
 
-/// List all sessions in database
-fn list_sessions() {
-    let sessions = load_sessions();
-    if sessions.is_empty() {
-        println!("No sessions in database.");
-    } else {
-        println!("Sessions ({}):", sessions.len());
-        for uuid in &sessions {
-            println!("  {}", uuid);
-        }
+/// Get the directory holding a profile's database and config (~/.cs/profiles/<name>)
+fn get_profile_dir(profile: &str) -> PathBuf {
+    get_cs_dir().join("profiles").join(profile)
+}
+
+/// Get the path to the sessions database file (~/.cs/sessions, or
+/// ~/.cs/profiles/<name>/sessions for a profile).
+/// Can be overridden with CS_DB_PATH environment variable (useful for testing)
+fn get_db_path() -> PathBuf {
+    if let Ok(custom_path) = env::var("CS_DB_PATH") {
+        return PathBuf::from(custom_path);
+    }
+    match get_profile() {
+        Some(profile) => get_profile_dir(&profile).join("sessions"),
+        None => get_cs_dir().join("sessions"),
     }
 }
 
-/// Clear entire session database
-fn clear_sessions() {
-    let db_path = get_db_path();
-    match fs::remove_file(&db_path) {
-        Ok(_) => println!("Session database cleared."),
-        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
-            println!("Session database already empty.");
+/// Get the path to the config file: CS_CONFIG_PATH, then the active profile's
+/// config.toml if it exists, then $CS_HOME/config.toml, then
+/// $XDG_CONFIG_HOME/cs/config.toml if it exists, then ~/.cs/config.toml
+fn get_config_path() -> PathBuf {
+    if let Ok(custom_path) = env::var("CS_CONFIG_PATH") {
+        return PathBuf::from(custom_path);
+    }
+    if let Some(profile) = get_profile() {
+        let path = get_profile_dir(&profile).join("config.toml");
+        if path.is_file() {
+            return path;
         }
-        Err(e) => {
-            eprintln!("Error clearing database: {}", e);
+    }
+    if env::var_os("CS_HOME").is_some() {
+        return get_cs_dir().join("config.toml");
+    }
+    if let Ok(xdg) = env::var("XDG_CONFIG_HOME") {
+        let path = PathBuf::from(xdg).join("cs").join("config.toml");
+        if path.is_file() {
+            return path;
         }
     }
+    get_cs_dir().join("config.toml")
 }
 
-/// Generate a deterministic UUID v5 from a name using the configured namespace
-fn generate_uuid5(name: &str) -> String {
-    let namespace = get_namespace();
-    let mut hasher = Sha1::new();
-    hasher.update(namespace);
-    hasher.update(name.as_bytes());
-    let hash = hasher.finalize();
+/// Load the user config. A bad config file is reported once on stderr and
+/// the defaults are used, unless CS_STRICT_CONFIG=1 makes it an error
+/// (including references to unset environment variables).
+fn load_user_config(path: &Path) -> Result<Config, String> {
+    let strict = is_strict_config();
+    config_or_defaults(path, load_config(path, strict), strict)
+}
 
-    let mut bytes = [0u8; 16];
-    bytes.copy_from_slice(&hash[..16]);
-    bytes[6] = (bytes[6] & 0x0f) | 0x50; // Version 5
-    bytes[8] = (bytes[8] & 0x3f) | 0x80; // Variant 10xx
+/// Whether CS_STRICT_CONFIG=1 makes a bad config file an error
+fn is_strict_config() -> bool {
+    env::var("CS_STRICT_CONFIG").map(|v| v == "1").unwrap_or(false)
+}
 
-    format!(
-        "{:08x}-{:04x}-{:04x}-{:04x}-{:012x}",
-        u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
-        u16::from_be_bytes([bytes[4], bytes[5]]),
-        u16::from_be_bytes([bytes[6], bytes[7]]),
-        u16::from_be_bytes([bytes[8], bytes[9]]),
-        u64::from_be_bytes([0, 0, bytes[10], bytes[11], bytes[12], bytes[13], bytes[14], bytes[15]])
-    )
+/// Report a config file that failed to load: an error when `strict`,
+/// otherwise a warning and the defaults
+fn config_or_defaults(path: &Path, loaded: Result<Config, String>, strict: bool) -> Result<Config, String> {
+    loaded.or_else(|e| {
+        let message = format!("config file {}: {}", path.display(), e);
+        if strict {
+            return Err(message);
+        }
+        eprintln!("Warning: ignoring {}; using defaults", message);
+        Ok(Config::default())
+    })
 }
 
-/// Get current git branch name
-fn get_git_branch() -> Result<String, String> {
-    let output = Command::new("git")
-        .args(["rev-parse", "--abbrev-ref", "HEAD"])
-        .output()
-        .map_err(|_| "Failed to execute git command")?;
+/// Get the path to the file recording which separator the database was built with
+fn get_separator_marker_path() -> PathBuf {
+    get_db_path().with_file_name("separator")
+}
 
-    if !output.status.success() {
-        return Err("Not a git repository or no branch found".to_string());
+/// Warn once when the configured separator differs from the one previously used,
+/// since every computed session name (and UUID) changes with it
+fn check_separator_change(separator: &str) {
+    let marker = get_separator_marker_path();
+    let previous = fs::read_to_string(&marker)
+        .map(|s| s.trim_end_matches('\n').to_string())
+        .unwrap_or_else(|_| DEFAULT_SEPARATOR.to_string());
+
+    if previous == separator || is_dry_run() || is_db_readonly() {
+        return;
     }
 
-    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    if !load_sessions().is_empty() {
+        eprintln!(
+            "Warning: session separator changed from '{}' to '{}'",
+            previous, separator
+        );
+        eprintln!("Existing sessions were named with '{}' and won't match new session names.", previous);
+    }
+
+    if let Some(parent) = marker.parent() {
+        let _ = fs::create_dir_all(parent);
+    }
+    let _ = fs::write(&marker, format!("{}\n", separator));
 }
 
-/// Get current folder name
-fn get_folder_name() -> Result<String, String> {
-    env::current_dir()
-        .map_err(|_| "Failed to get current directory")?
-        .file_name()
-        .and_then(|n| n.to_str())
-        .map(|s| s.to_string())
-        .ok_or_else(|| "Failed to get folder name".to_string())
+/// Get the path to the file recording whether the database was built with `case_insensitive`
+fn get_case_marker_path() -> PathBuf {
+    get_db_path().with_file_name("case-insensitive")
 }
 
-/// Get the binary name for current platform
-fn get_binary_name() -> Option<&'static str> {
-    #[cfg(all(target_os = "windows", target_arch = "x86_64"))]
-    return Some("cs-windows-x64.exe");
-    #[cfg(all(target_os = "windows", target_arch = "aarch64"))]
-    return Some("cs-windows-arm64.exe");
+/// Warn once when `case_insensitive` is switched, since the session name (and
+/// UUID) of every folder with capitals or decomposed accents changes with it
+fn check_case_insensitive_change(enabled: bool) {
+    let marker = get_case_marker_path();
+    let previous = fs::read_to_string(&marker).is_ok_and(|s| s.trim() == "true");
 
-    #[cfg(all(target_os = "macos", target_arch = "aarch64"))]
-    return Some("cs-macos-arm64");
-    #[cfg(all(target_os = "macos", target_arch = "x86_64"))]
-    return Some("cs-macos-intel");
+    if previous == enabled || is_dry_run() || is_db_readonly() {
+        return;
+    }
 
-    #[cfg(all(target_os = "linux", target_arch = "x86_64", target_env = "gnu"))]
-    return Some("cs-linux-x64");
-    #[cfg(all(target_os = "linux", target_arch = "aarch64", target_env = "gnu"))]
-    return Some("cs-linux-arm64");
-    #[cfg(all(target_os = "linux", target_arch = "x86_64", target_env = "musl"))]
-    return Some("cs-linux-x64-musl");
-    #[cfg(all(target_os = "linux", target_arch = "x86", target_env = "musl"))]
-    return Some("cs-linux-i686-musl");
+    if !load_sessions().is_empty() {
+        eprintln!("Warning: case_insensitive changed from {} to {}", previous, enabled);
+        eprintln!("Folders with capital letters now get different session names and won't match existing sessions.");
+    }
 
-    #[cfg(all(target_os = "freebsd", target_arch = "x86_64"))]
-    return Some("cs-freebsd-x64");
+    if let Some(parent) = marker.parent() {
+        let _ = fs::create_dir_all(parent);
+    }
+    let _ = fs::write(&marker, format!("{}\n", enabled));
+}
 
-    #[cfg(all(target_os = "android", target_arch = "aarch64"))]
-    return Some("cs-android-arm64");
-    #[cfg(all(target_os = "android", target_arch = "arm"))]
-    return Some("cs-android-arm32");
-    #[cfg(all(target_os = "android", target_arch = "x86_64"))]
-    return Some("cs-android-x64");
+/// Build the session name from folder and (optional) branch
+fn build_session_name(folder: &str, branch: Option<&str>, separator: &str) -> String {
+    match branch {
+        Some(branch) => format!("{}{}{}", folder, separator, branch),
+        None => folder.to_string(),
+    }
+}
 
-    #[allow(unreachable_code)]
-    None
+/// Whether the read-only note has already been printed
+static READONLY_NOTED: AtomicBool = AtomicBool::new(false);
+
+/// Check whether the database can't be written (CS_READONLY=1, an existing
+/// database file that can't be opened for writing, or a database directory
+/// that doesn't accept new files, e.g. on a read-only mount)
+fn is_db_readonly() -> bool {
+    if env::var("CS_READONLY").map(|v| v == "1").unwrap_or(false) {
+        return true;
+    }
+    let db_path = get_db_path();
+    if db_path.exists() && OpenOptions::new().append(true).open(&db_path).is_err() {
+        return true;
+    }
+    // Writes replace the database through a temp file beside it
+    db_path.parent().is_some_and(|dir| !dir_accepts_files(dir))
 }
 
-/// Get the path to the current executable
-fn get_current_exe_path() -> Result<PathBuf, String> {
-    env::current_exe().map_err(|e| format!("Failed to get current executable path: {}", e))
+/// Whether new files can be created in `dir`, or in its nearest existing
+/// ancestor if it doesn't exist yet. Probed with a scratch file, since
+/// permission bits miss read-only mounts.
+fn dir_accepts_files(dir: &Path) -> bool {
+    let Some(existing) = dir.ancestors().find(|d| d.exists()) else {
+        return true;
+    };
+    let probe = existing.join(format!(".cs-write-probe.{}", std::process::id()));
+    match OpenOptions::new().write(true).create_new(true).open(&probe) {
+        Ok(_) => fs::remove_file(&probe).is_ok(),
+        Err(e) => e.kind() == std::io::ErrorKind::AlreadyExists,
+    }
 }
 
-/// Perform self-update by downloading latest release from GitHub
-fn self_update() -> Result<(), String> {
-    let binary_name = get_binary_name()
-        .ok_or_else(|| "Unsupported platform for auto-update".to_string())?;
+/// Set by `--dry-run`: every database mutation becomes a no-op, whatever
+/// order `main` reaches it in
+static DRY_RUN: AtomicBool = AtomicBool::new(false);
 
-    let download_url = format!(
-        "https://github.com/bikramtuladhar/claude-code-resumer/releases/latest/download/{}",
-        binary_name
-    );
+/// Find `--dry-run`/`-n` (alone or in a cluster such as `-fn`) anywhere in the
+/// arguments, so it applies before any other flag is acted on
+fn dry_run_from_args(args: &[String]) -> bool {
+    cs_args(args).into_iter().any(|a| {
+        a == "--dry-run" || a == "-n" || (is_short_cluster(a) && expand_short_cluster(a).is_ok_and(|f| f.iter().any(|f| f == "-n")))
+    })
+}
 
-    let current_exe = get_current_exe_path()?;
-    let current_version = env!("CS_VERSION");
+/// Whether this run is a `--dry-run`
+fn is_dry_run() -> bool {
+    DRY_RUN.load(Ordering::Relaxed)
+}
 
-    println!("cs self-update");
-    println!("──────────────────────────────────────────────");
-    println!("Current version: {}", current_version);
-    println!("Binary: {}", binary_name);
-    println!("Downloading from: {}", download_url);
-    println!();
+/// Check the database may be modified: never in a dry run, and not when it's
+/// read-only (printing a one-time note). Every database mutation goes through this.
+fn db_writable() -> bool {
+    if is_dry_run() {
+        verbose_log("dry run: leaving the session database unchanged");
+        return false;
+    }
+    if !is_db_readonly() {
+        return true;
+    }
+    if !READONLY_NOTED.swap(true, Ordering::Relaxed) {
+        eprintln!("Note: session database is read-only; changes will not be saved");
+    }
+    false
+}
 
-    // Create temp file path
-    let temp_path = current_exe.with_extension("new");
+/// Whether a command that changes the database has to stop because the
+/// database is read-only. A dry run goes ahead (writing nothing) to report
+/// what it would do.
+fn db_blocked() -> bool {
+    !is_dry_run() && !db_writable()
+}
 
-    // Download using platform-appropriate method
-    #[cfg(windows)]
-    let download_result = download_windows(&download_url, &temp_path);
-    #[cfg(not(windows))]
-    let download_result = download_unix(&download_url, &temp_path);
+/// A session database entry.
+///
+/// Each line of the database is the session UUID, optionally followed by
+/// tab-separated `key=value` fields (`name=...`, `tags=a,b`, and Unix
+/// timestamps `created=...`/`used=...`). Legacy lines holding only a UUID
+/// remain valid.
+#[derive(Debug, Clone, Default, PartialEq)]
+struct SessionRecord {
+    uuid: String,
+    name: Option<String>,
+    tags: Vec<String>,
+    created: Option<u64>,
+    last_used: Option<u64>,
+}
 
-    download_result?;
+impl SessionRecord {
+    fn new(uuid: &str, name: Option<&str>) -> Self {
+        SessionRecord {
+            uuid: uuid.to_string(),
+            name: name.map(str::to_string),
+            ..Default::default()
+        }
+    }
 
-    // Verify the download succeeded and file exists
-    if !temp_path.exists() {
-        return Err("Download failed: file not created".to_string());
+    /// Set the created and last-used timestamps to `now`
+    fn stamped(mut self, now: u64) -> Self {
+        self.created = Some(now);
+        self.last_used = Some(now);
+        self
     }
 
-    // Make executable on Unix
-    #[cfg(unix)]
-    {
-        use std::os::unix::fs::PermissionsExt;
-        let mut perms = fs::metadata(&temp_path)
-            .map_err(|e| format!("Failed to get file metadata: {}", e))?
-            .permissions();
-        perms.set_mode(0o755);
-        fs::set_permissions(&temp_path, perms)
-            .map_err(|e| format!("Failed to set permissions: {}", e))?;
+    /// Parse a database line, returning None for blank lines
+    fn parse(line: &str) -> Option<Self> {
+        let mut fields = line.trim().split('\t');
+        let uuid = fields.next()?.trim();
+        if uuid.is_empty() {
+            return None;
+        }
+        let mut record = SessionRecord::new(uuid, None);
+        for field in fields {
+            match field.split_once('=') {
+                Some(("name", name)) if !name.is_empty() => record.name = Some(name.to_string()),
+                Some(("tags", tags)) => record.add_tags(&parse_tags(tags)),
+                Some(("created", secs)) => record.created = secs.parse().ok(),
+                Some(("used", secs)) => record.last_used = secs.parse().ok(),
+                _ => {}
+            }
+        }
+        Some(record)
     }
 
-    // Replace the current binary
-    // On Windows, we can't replace a running executable directly
-    #[cfg(windows)]
-    {
-        let backup_path = current_exe.with_extension("old");
-        // Remove old backup if exists
-        let _ = fs::remove_file(&backup_path);
-        // Rename current to backup
-        fs::rename(&current_exe, &backup_path)
-            .map_err(|e| format!("Failed to backup current binary: {}", e))?;
-        // Rename new to current
-        fs::rename(&temp_path, &current_exe)
-            .map_err(|e| format!("Failed to install new binary: {}", e))?;
-        // Remove backup
-        let _ = fs::remove_file(&backup_path);
+    /// Format as a database line
+    fn to_line(&self) -> String {
+        let mut line = self.uuid.clone();
+        if let Some(name) = &self.name {
+            line.push_str(&format!("\tname={}", name.replace(['\t', '\n', '\r'], " ")));
+        }
+        if !self.tags.is_empty() {
+            line.push_str(&format!("\ttags={}", self.tags.join(",")));
+        }
+        if let Some(created) = self.created {
+            line.push_str(&format!("\tcreated={}", created));
+        }
+        if let Some(used) = self.last_used {
+            line.push_str(&format!("\tused={}", used));
+        }
+        line
     }
 
-    #[cfg(not(windows))]
-    {
-        fs::rename(&temp_path, &current_exe)
-            .map_err(|e| format!("Failed to replace binary: {}", e))?;
+    /// Add tags not already present, preserving order
+    fn add_tags(&mut self, tags: &[String]) {
+        for tag in tags {
+            if !self.tags.contains(tag) {
+                self.tags.push(tag.clone());
+            }
+        }
     }
 
-    println!("✓ Successfully updated!");
-    println!();
-    println!("Run 'cs --version' to verify the new version.");
+    /// Check whether the record carries every one of `tags`
+    fn has_tags(&self, tags: &[String]) -> bool {
+        tags.iter().all(|tag| self.tags.contains(tag))
+    }
+}
 
-    Ok(())
+/// Current time as Unix seconds
+fn unix_now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
 }
 
-/// Download file using curl or wget (Unix)
-#[cfg(not(windows))]
-fn download_unix(url: &str, dest: &std::path::Path) -> Result<(), String> {
-    // Try curl first
-    let curl_result = Command::new("curl")
-        .args(["-fsSL", "-o"])
-        .arg(dest)
-        .arg(url)
-        .output();
+/// Split a comma-separated tag list, dropping empty entries
+fn parse_tags(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|tag| !tag.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Validate tags given on the command line (no whitespace, `=`, or commas)
+fn validate_tags(value: &str) -> Result<Vec<String>, String> {
+    let tags = parse_tags(value);
+    if tags.is_empty() {
+        return Err("'--tag' requires at least one label".to_string());
+    }
+    if let Some(bad) = tags.iter().find(|t| t.contains(|c: char| c.is_whitespace() || c == '=')) {
+        return Err(format!("invalid tag '{}': tags cannot contain whitespace or '='", bad));
+    }
+    Ok(tags)
+}
+
+/// Load all session records from the database, in file order
+fn load_records() -> Vec<SessionRecord> {
+    let db_path = get_db_path();
+    let mut records = Vec::new();
+
+    if let Ok(file) = File::open(&db_path) {
+        let reader = BufReader::new(file);
+        records.extend(reader.lines().map_while(Result::ok).filter_map(|l| SessionRecord::parse(&l)));
+    }
+
+    records
+}
+
+/// Load existing session UUIDs from database
+fn load_sessions() -> HashSet<String> {
+    load_records().into_iter().map(|record| record.uuid).collect()
+}
+
+/// Save a new session UUID to the database
+fn save_session(uuid: &str) {
+    save_record(&SessionRecord::new(uuid, None));
+}
+
+/// Find a different name already stored for the record's UUID
+fn conflicting_name<'a>(records: &'a [SessionRecord], record: &SessionRecord) -> Option<&'a str> {
+    let name = record.name.as_deref()?;
+    records
+        .iter()
+        .filter(|existing| existing.uuid == record.uuid)
+        .filter_map(|existing| existing.name.as_deref())
+        .find(|existing| *existing != name)
+}
 
-    if let Ok(output) = curl_result {
-        if output.status.success() {
-            return Ok(());
+/// Append a session record to the database, warning if its UUID is already
+/// stored under a different name (the two sessions would be conflated).
+/// A UUID that is already stored is not appended again; instead the stored
+/// record gets the name and timestamps it is missing (e.g. a legacy entry).
+fn save_record(record: &SessionRecord) {
+    if !db_writable() {
+        return;
+    }
+    let _lock = lock_db();
+    let mut records = load_records();
+    if let Some(other) = conflicting_name(&records, record) {
+        eprintln!(
+            "Warning: '{}' and '{}' both map to session {}; they will share one conversation",
+            record.name.as_deref().unwrap_or_default(),
+            other,
+            record.uuid
+        );
+    }
+    if let Some(existing) = records.iter_mut().find(|existing| existing.uuid == record.uuid) {
+        let before = existing.clone();
+        if existing.name.is_none() {
+            existing.name = record.name.clone();
+        }
+        existing.created = existing.created.or(record.created);
+        existing.last_used = existing.last_used.or(record.last_used);
+        if *existing != before {
+            let lines: Vec<String> = records.iter().map(SessionRecord::to_line).collect();
+            let _ = write_db_atomic(&lines);
         }
+        return;
     }
+    let db_path = get_db_path();
 
-    // Fall back to wget
-    let wget_result = Command::new("wget")
-        .args(["-q", "-O"])
-        .arg(dest)
-        .arg(url)
-        .output();
+    // Create directory if it doesn't exist
+    if let Some(parent) = db_path.parent() {
+        let _ = fs::create_dir_all(parent);
+    }
 
-    match wget_result {
-        Ok(output) if output.status.success() => Ok(()),
-        Ok(output) => Err(format!(
-            "Download failed: {}",
-            String::from_utf8_lossy(&output.stderr)
-        )),
-        Err(_) => Err("Neither curl nor wget available for download".to_string()),
+    // Append record to file
+    if let Ok(mut file) = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&db_path)
+    {
+        let _ = writeln!(file, "{}", record.to_line());
     }
 }
 
-/// Download file using PowerShell (Windows)
-#[cfg(windows)]
-fn download_windows(url: &str, dest: &std::path::Path) -> Result<(), String> {
-    let dest_str = dest.to_string_lossy();
-    let ps_command = format!(
-        "Invoke-WebRequest -Uri '{}' -OutFile '{}' -UseBasicParsing",
-        url, dest_str
-    );
+/// Follow symlinks from `path` to the file they point at (which may not exist yet),
+/// so writes replace the target instead of the link itself
+fn resolve_symlinks(path: &Path) -> PathBuf {
+    let mut path = path.to_path_buf();
+    // Bounded, in case of a link cycle
+    for _ in 0..40 {
+        let Ok(target) = fs::read_link(&path) else {
+            break;
+        };
+        path = match path.parent() {
+            Some(parent) => parent.join(target),
+            None => target,
+        };
+    }
+    path
+}
 
-    let result = Command::new("powershell")
-        .args(["-NoProfile", "-Command", &ps_command])
-        .output()
-        .map_err(|e| format!("Failed to run PowerShell: {}", e))?;
+/// Write the database atomically (temp file + rename) so readers never see a partial file.
+/// A symlinked database (e.g. into a synced folder) is written through the link.
+fn write_db_atomic(lines: &[String]) -> std::io::Result<()> {
+    if is_dry_run() {
+        return Ok(());
+    }
+    let db_path = resolve_symlinks(&get_db_path());
+    if let Some(parent) = db_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
 
-    if result.status.success() {
-        Ok(())
-    } else {
-        Err(format!(
-            "Download failed: {}",
-            String::from_utf8_lossy(&result.stderr)
-        ))
+    let temp_path = db_path.with_extension(format!("tmp.{}", std::process::id()));
+    let mut content = lines.join("\n");
+    if !content.is_empty() {
+        content.push('\n');
     }
+    fs::write(&temp_path, content)?;
+    fs::rename(&temp_path, &db_path).inspect_err(|_| {
+        let _ = fs::remove_file(&temp_path);
+    })
 }
 
-fn print_help() {
-    eprintln!("cs - Claude Code Session Manager");
-    eprintln!();
-    eprintln!("USAGE:");
-    eprintln!("    cs              Start/resume session (folder+branch or folder-only)");
-    eprintln!("    cs --force      Force create new session (ignore database)");
-    eprintln!("    cs --reset      Remove session from database and create new");
-    eprintln!("    cs --resume     Resume using Claude's picker (fallback if not found)");
-    eprintln!("    cs --list       List all sessions in database");
-    eprintln!("    cs --clear      Clear entire session database");
-    eprintln!("    cs --dry-run    Show session info without launching Claude");
-    eprintln!("    cs upgrade      Update cs to the latest version");
-    eprintln!("    cs --help       Show this help message");
-    eprintln!("    cs --version    Show version");
-    eprintln!();
-    eprintln!("SHORT FLAGS:");
-    eprintln!("    -f              Same as --force");
-    eprintln!("    -R              Same as --resume");
-    eprintln!("    -l              Same as --list");
-    eprintln!("    -n              Same as --dry-run");
-    eprintln!("    -h              Same as --help");
-    eprintln!("    -v              Same as --version");
-    eprintln!("    -U              Same as upgrade");
-    eprintln!();
-    eprintln!("CLAUDE CODE OPTIONS:");
-    eprintln!("    All Claude Code CLI options are passed through:");
-    eprintln!("    --chrome, --model <m>, --verbose, -c, -p, etc.");
-    eprintln!();
-    eprintln!("EXAMPLES:");
-    eprintln!("    cs --chrome              # Enable Chrome integration");
-    eprintln!("    cs --model opus          # Use opus model");
-    eprintln!("    cs -f --verbose          # Force new session + verbose mode");
-    eprintln!("    cs doctor                # Run claude doctor (bypass session)");
-    eprintln!();
-    eprintln!("SESSION FORMAT:");
-    eprintln!("    Git repo:    <folder>+<branch> -> deterministic UUID v5");
-    eprintln!("    Non-git:     <folder> -> deterministic UUID v5 (folder-only)");
-    eprintln!("    Example: my-project+feature/auth -> 4b513bfa-8c71-512b-...");
-    eprintln!("    Example: my-folder -> a1b2c3d4-e5f6-5789-...");
-    eprintln!();
-    eprintln!("TROUBLESHOOTING:");
-    eprintln!("    If you see \"No conversation found\" error:");
-    eprintln!("        cs --resume  # Use Claude's picker to find/select session");
-    eprintln!("        cs --reset   # Clears stale entry and creates fresh session");
-    eprintln!();
-    eprintln!("ENVIRONMENT VARIABLES:");
-    eprintln!("    CS_NAMESPACE    Custom UUID v5 namespace (default: DNS namespace)");
-    eprintln!("                    Example: export CS_NAMESPACE=\"your-custom-uuid-here\"");
-    eprintln!();
-    eprintln!("FILES:");
-    eprintln!("    ~/.cs/sessions  Session database (one UUID per line)");
-    eprintln!("                    (Windows: %USERPROFILE%\\.cs\\sessions)");
+/// Number of `DbLock` guards this process holds (nested updates share one lock)
+static DB_LOCK_DEPTH: AtomicUsize = AtomicUsize::new(0);
+
+/// Held while the database is read, changed, and rewritten, so concurrent cs
+/// processes don't drop each other's updates. Released when dropped.
+struct DbLock {
+    _file: Option<File>,
 }
 
-fn main() {
-    let args: Vec<String> = env::args().collect();
+impl Drop for DbLock {
+    fn drop(&mut self) {
+        DB_LOCK_DEPTH.fetch_sub(1, Ordering::SeqCst);
+    }
+}
 
-    // Track mode flags
-    let mut dry_run = false;
-    let mut force_create = false;
-    let mut reset_mode = false;
-    let mut resume_mode = false;
-    let mut passthrough_args: Vec<String> = Vec::new();
+/// Take the database lock: an exclusive OS lock on `db.lock` under the lock
+/// directory, waiting while another cs process holds it. The OS drops the lock
+/// if its process dies, so there is nothing stale to reclaim. Best effort: where
+/// the lock file can't be created or locked, the update goes ahead unlocked.
+fn lock_db() -> DbLock {
+    if DB_LOCK_DEPTH.fetch_add(1, Ordering::SeqCst) > 0 || is_dry_run() {
+        return DbLock { _file: None };
+    }
+    let lock_dir = get_lock_dir();
+    let file = fs::create_dir_all(&lock_dir)
+        .and_then(|_| OpenOptions::new().create(true).truncate(false).write(true).open(lock_dir.join("db.lock")))
+        .and_then(|file| file.lock().map(|_| file));
+    if let Err(e) = &file {
+        verbose_log(&format!("database lock unavailable ({}); updating unlocked", e));
+    }
+    DbLock { _file: file.ok() }
+}
 
-    // Check for Claude subcommands first - pass entire command through (bypass session logic)
-    if args.len() > 1 && CLAUDE_SUBCOMMANDS.contains(&args[1].as_str()) {
-        let claude_args: Vec<String> = args[1..].to_vec();
-        launch_claude_owned(claude_args);
+/// Remove a session UUID from the database.
+/// Returns whether an entry was actually removed.
+fn remove_session(uuid: &str) -> bool {
+    if !db_writable() {
+        return false;
     }
+    let _lock = lock_db();
+    let db_path = get_db_path();
+    let Ok(content) = fs::read_to_string(&db_path) else {
+        return false;
+    };
+    let filtered: Vec<String> = content
+        .lines()
+        .filter(|line| SessionRecord::parse(line).is_none_or(|r| r.uuid != uuid))
+        .map(str::to_string)
+        .collect();
+    filtered.len() < content.lines().count() && write_db_atomic(&filtered).is_ok()
+}
 
-    // Parse arguments with index-based loop to handle value flags
-    let mut i = 1;
-    while i < args.len() {
-        let arg = &args[i];
+/// Resolve a `--rm` target to a UUID: a UUID as given, else the stored
+/// name index, else the UUID the name would generate
+fn resolve_session_ref(target: &str, records: &[SessionRecord]) -> String {
+    if let Some(bytes) = parse_uuid_strict(target) {
+        return format_uuid(&bytes);
+    }
+    records
+        .iter()
+        .find(|r| r.name.as_deref() == Some(target))
+        .map(|r| r.uuid.clone())
+        .unwrap_or_else(|| generate_uuid5(target))
+}
 
-        match arg.as_str() {
-            // cs-specific flags (early exit)
-            "--help" | "-h" => {
-                print_help();
-                return;
-            }
-            "--version" | "-v" => {
-                println!("cs {}", env!("CS_VERSION"));
-                return;
+/// Remove each named or UUID-identified session (`--rm`), reporting what happened.
+/// Returns false if any target was not found.
+fn remove_sessions(targets: &[String]) -> bool {
+    let records = load_records();
+    let mut all_removed = true;
+    for target in targets {
+        let uuid = resolve_session_ref(target, &records);
+        if is_dry_run() && records.iter().any(|r| r.uuid == uuid) {
+            println!("Would remove {} ({})", target, uuid);
+        } else if remove_session(&uuid) {
+            println!("Removed {} ({})", target, uuid);
+        } else {
+            eprintln!("Not found: {} ({})", target, uuid);
+            all_removed = false;
+        }
+    }
+    all_removed
+}
+
+/// Run `--rm`: back up the database, then remove the targets.
+/// Exits with an error if the backup fails or any target was not found.
+fn run_remove(targets: &[String], retention: usize) {
+    if !is_dry_run() && db_writable() {
+        match backup_db(retention) {
+            Ok(Some(path)) => eprintln!("Backup saved to {}", path.display()),
+            Ok(None) => {}
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                eprintln!("Nothing removed.");
+                exit(EXIT_ERROR);
             }
-            "--list" | "-l" => {
-                list_sessions();
-                return;
+        }
+    }
+    if !remove_sessions(targets) {
+        exit(EXIT_ERROR);
+    }
+}
+
+/// Error for tagging a session that has no database entry yet. Tagging must not create
+/// one: a plain `cs` would then resume a conversation Claude never started.
+fn untracked_tag_error(name: &str) -> String {
+    format!("session {} is not tracked yet; start it with cs before tagging it", name)
+}
+
+/// Attach tags to a tracked session.
+/// Returns the session's full tag list afterwards.
+fn tag_session(uuid: &str, name: &str, tags: &[String]) -> Result<Vec<String>, String> {
+    let _lock = lock_db();
+    let mut records = load_records();
+    let Some(record) = records.iter_mut().find(|r| r.uuid == uuid) else {
+        return Err(untracked_tag_error(name));
+    };
+    if db_blocked() {
+        return Err("session database is read-only; tags not saved".to_string());
+    }
+    record.name.get_or_insert_with(|| name.to_string());
+    record.add_tags(tags);
+    let updated = record.tags.clone();
+
+    let lines: Vec<String> = records.iter().map(SessionRecord::to_line).collect();
+    write_db_atomic(&lines).map_err(|e| format!("Failed to write database: {}", e))?;
+    Ok(updated)
+}
+
+/// Record that a session was just used (updates its `used=` timestamp)
+fn touch_session(uuid: &str) {
+    if !db_writable() {
+        return;
+    }
+    let _lock = lock_db();
+    let mut records = load_records();
+    let Some(record) = records.iter_mut().find(|r| r.uuid == uuid) else {
+        return;
+    };
+    record.last_used = Some(unix_now());
+    let lines: Vec<String> = records.iter().map(SessionRecord::to_line).collect();
+    let _ = write_db_atomic(&lines);
+}
+
+/// Sort records most recently used first; entries without a timestamp go last.
+/// Ties (e.g. sessions used in the same second) are broken by UUID, ascending.
+fn sort_by_recency(records: &mut [SessionRecord]) {
+    records.sort_by(|a, b| b.last_used.cmp(&a.last_used).then_with(|| a.uuid.cmp(&b.uuid)));
+}
+
+/// Get the `n`th most recently used session (1 = most recent)
+fn nth_recent_session(n: usize) -> Result<SessionRecord, String> {
+    let mut records = load_records();
+    sort_by_recency(&mut records);
+    let total = records.len();
+    n.checked_sub(1)
+        .and_then(|index| records.into_iter().nth(index))
+        .ok_or_else(|| format!("no session #{}; the database has {} session(s)", n, total))
+}
+
+/// Shortest UUID prefix `--resume-uuid` accepts
+const MIN_UUID_PREFIX: usize = 4;
+
+/// Find the one session whose UUID starts with `prefix` (`--resume-uuid`)
+fn find_session_by_prefix(prefix: &str, records: &[SessionRecord]) -> Result<SessionRecord, String> {
+    let prefix = prefix.to_ascii_lowercase();
+    if prefix.len() < MIN_UUID_PREFIX {
+        return Err(format!("UUID prefix '{}' is too short (at least {} characters)", prefix, MIN_UUID_PREFIX));
+    }
+    let mut seen = HashSet::new();
+    let matches: Vec<&SessionRecord> = records
+        .iter()
+        .filter(|r| r.uuid.starts_with(&prefix) && seen.insert(r.uuid.as_str()))
+        .collect();
+    match matches.as_slice() {
+        [] => Err(format!("no tracked session has a UUID starting with '{}'", prefix)),
+        [record] => Ok((*record).clone()),
+        candidates => {
+            let list: Vec<String> = candidates
+                .iter()
+                .map(|r| format!("  {}  {}", r.uuid, r.name.as_deref().unwrap_or("(unnamed)")))
+                .collect();
+            Err(format!("UUID prefix '{}' is ambiguous; candidates:\n{}", prefix, list.join("\n")))
+        }
+    }
+}
+
+/// What `--repair` changed in the database
+#[derive(Debug, Default, PartialEq)]
+struct RepairReport {
+    kept: usize,
+    duplicates: usize,
+    normalized: usize,
+    blank: usize,
+}
+
+/// Normalize a database record: lowercase well-formed UUIDs
+fn normalize_record(mut record: SessionRecord) -> SessionRecord {
+    if record.uuid.len() == 36 && parse_uuid(&record.uuid).is_some() {
+        record.uuid = record.uuid.to_lowercase();
+    }
+    record
+}
+
+/// Deduplicate, normalize, and sort the database, rewriting it atomically.
+/// Duplicate entries for the same UUID are merged (first name wins, tags are
+/// combined, and the earliest creation and latest use are kept).
+fn repair_db() -> Result<RepairReport, String> {
+    let _lock = lock_db();
+    let db_path = get_db_path();
+    let content = match fs::read_to_string(&db_path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(RepairReport::default()),
+        Err(e) => return Err(format!("Failed to read database: {}", e)),
+    };
+
+    let mut report = RepairReport::default();
+    let mut entries: Vec<SessionRecord> = Vec::new();
+    for line in content.lines() {
+        let Some(record) = SessionRecord::parse(line).map(normalize_record) else {
+            report.blank += 1;
+            continue;
+        };
+        if record.to_line() != line {
+            report.normalized += 1;
+        }
+        match entries.iter_mut().find(|e| e.uuid == record.uuid) {
+            Some(existing) => {
+                report.duplicates += 1;
+                if existing.name.is_none() {
+                    existing.name = record.name;
+                }
+                existing.add_tags(&record.tags);
+                existing.created = existing.created.into_iter().chain(record.created).min();
+                existing.last_used = existing.last_used.max(record.last_used);
             }
-            "--clear" => {
-                clear_sessions();
-                return;
+            None => entries.push(record),
+        }
+    }
+    entries.sort_by(|a, b| a.uuid.cmp(&b.uuid));
+    report.kept = entries.len();
+
+    let lines: Vec<String> = entries.iter().map(SessionRecord::to_line).collect();
+    write_db_atomic(&lines).map_err(|e| format!("Failed to write database: {}", e))?;
+    Ok(report)
+}
+
+/// Run `--repair` and print a summary
+fn run_repair() {
+    if db_blocked() {
+        eprintln!("Session database not repaired.");
+        return;
+    }
+    match repair_db() {
+        Ok(report) => {
+            let verb = if is_dry_run() { "Would repair" } else { "Repaired" };
+            eprintln!("{} {}", verb, get_db_path().display());
+            eprintln!("  Entries kept:       {}", report.kept);
+            eprintln!("  Duplicates removed: {}", report.duplicates);
+            eprintln!("  Lines normalized:   {}", report.normalized);
+            eprintln!("  Blank lines dropped: {}", report.blank);
+        }
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            exit(EXIT_ERROR);
+        }
+    }
+}
+
+/// Parse a legacy `name=uuid` session file (from cs's shell-script predecessor).
+/// Blank lines and `#` comments are skipped; any other line that isn't
+/// `name=uuid` means the file isn't in the legacy format.
+fn parse_legacy_sessions(content: &str) -> Result<Vec<SessionRecord>, String> {
+    let mut records = Vec::new();
+    for (index, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        // Names may contain '=', UUIDs never do
+        let entry = line.rsplit_once('=').and_then(|(name, uuid)| {
+            let bytes = parse_uuid_strict(uuid.trim())?;
+            Some((name.trim(), format_uuid(&bytes))).filter(|(name, _)| !name.is_empty())
+        });
+        let Some((name, uuid)) = entry else {
+            return Err(format!("line {}: expected name=uuid, found '{}'", index + 1, line));
+        };
+        records.push(SessionRecord::new(&uuid, Some(name)));
+    }
+    if records.is_empty() {
+        return Err("no name=uuid entries found".to_string());
+    }
+    Ok(records)
+}
+
+/// Merge a legacy `name=uuid` file into the database, keeping existing lines as they are.
+/// Returns (imported, already tracked) counts.
+fn import_legacy(path: &Path) -> Result<(usize, usize), String> {
+    let content = fs::read_to_string(path).map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+    let legacy = parse_legacy_sessions(&content).map_err(|e| format!("{} is not a legacy session file: {}", path.display(), e))?;
+    let _lock = lock_db();
+
+    let mut lines: Vec<String> = match fs::read_to_string(get_db_path()) {
+        Ok(db) => db.lines().map(str::to_string).collect(),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+        Err(e) => return Err(format!("Failed to read database: {}", e)),
+    };
+    let mut known: HashSet<String> = lines.iter().filter_map(|l| SessionRecord::parse(l)).map(|r| r.uuid.to_lowercase()).collect();
+
+    let mut imported = 0;
+    for record in &legacy {
+        if known.insert(record.uuid.clone()) {
+            lines.push(record.to_line());
+            imported += 1;
+        }
+    }
+    if imported > 0 {
+        write_db_atomic(&lines).map_err(|e| format!("Failed to write database: {}", e))?;
+    }
+    Ok((imported, legacy.len() - imported))
+}
+
+/// Run `--import-legacy` and print a summary
+fn run_import_legacy(path: &Path) {
+    if db_blocked() {
+        eprintln!("Nothing imported.");
+        return;
+    }
+    match import_legacy(path) {
+        Ok((imported, existing)) => {
+            let verb = if is_dry_run() { "Would import" } else { "Imported" };
+            eprintln!("{} {} session(s) from {}", verb, imported, path.display());
+            if existing > 0 {
+                eprintln!("  Already tracked: {}", existing);
             }
-            "upgrade" | "-U" => {
-                match self_update() {
-                    Ok(_) => return,
-                    Err(e) => {
-                        eprintln!("Error: {}", e);
-                        exit(1);
-                    }
-                }
+        }
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            exit(EXIT_ERROR);
+        }
+    }
+}
+
+/// The part of a session name that identifies the project, without a trailing
+/// `@<commit>` base qualifier. Git allows `@` in branch names, so any other
+/// `@` is part of the name.
+fn dedupe_key(name: &str) -> &str {
+    match name.rsplit_once(BASE_MARKER) {
+        Some((key, commit))
+            if commit.len() == BASE_COMMIT_LEN && commit.chars().all(|c| c.is_ascii_hexdigit()) =>
+        {
+            key
+        }
+        _ => name,
+    }
+}
+
+/// A proposed `--dedupe-names` consolidation
+#[derive(Debug, PartialEq)]
+struct NameMerge {
+    /// Most recently used record, with the dropped records' tags added
+    keep: SessionRecord,
+    drop: Vec<SessionRecord>,
+}
+
+/// Group named records that likely refer to the same project (same name
+/// without an `@<commit>` qualifier) and keep the most recently used of each group
+fn find_name_merges(records: &[SessionRecord]) -> Vec<NameMerge> {
+    let mut groups: Vec<(&str, Vec<&SessionRecord>)> = Vec::new();
+    for record in records {
+        let Some(name) = record.name.as_deref() else {
+            continue;
+        };
+        let key = dedupe_key(name);
+        match groups.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, members)) if members.iter().any(|m| m.uuid == record.uuid) => {}
+            Some((_, members)) => members.push(record),
+            None => groups.push((key, vec![record])),
+        }
+    }
+
+    groups
+        .into_iter()
+        .filter(|(_, members)| members.len() > 1)
+        .map(|(_, members)| {
+            let newest = members
+                .iter()
+                .enumerate()
+                .max_by_key(|(index, r)| (r.last_used, r.created, std::cmp::Reverse(*index)))
+                .map(|(index, _)| index)
+                .unwrap_or_default();
+            let mut keep = members[newest].clone();
+            let drop: Vec<SessionRecord> = members
+                .iter()
+                .enumerate()
+                .filter(|(index, _)| *index != newest)
+                .map(|(_, r)| (*r).clone())
+                .collect();
+            for record in &drop {
+                keep.add_tags(&record.tags);
             }
+            NameMerge { keep, drop }
+        })
+        .collect()
+}
+
+/// Apply `--dedupe-names` merges: drop the merged-away entries and update
+/// the kept ones in place. Returns the number of entries removed.
+fn apply_name_merges(merges: &[NameMerge]) -> Result<usize, String> {
+    let _lock = lock_db();
+    let content = fs::read_to_string(get_db_path()).map_err(|e| format!("Failed to read database: {}", e))?;
+    let dropped: HashSet<&str> = merges.iter().flat_map(|m| &m.drop).map(|r| r.uuid.as_str()).collect();
+
+    let mut removed = 0;
+    let mut lines = Vec::new();
+    for line in content.lines() {
+        let Some(record) = SessionRecord::parse(line) else {
+            lines.push(line.to_string());
+            continue;
+        };
+        if dropped.contains(record.uuid.as_str()) {
+            removed += 1;
+        } else if let Some(merge) = merges.iter().find(|m| m.keep.uuid == record.uuid) {
+            let mut kept = record;
+            kept.add_tags(&merge.keep.tags);
+            lines.push(kept.to_line());
+        } else {
+            lines.push(line.to_string());
+        }
+    }
+    write_db_atomic(&lines).map_err(|e| format!("Failed to write database: {}", e))?;
+    Ok(removed)
+}
+
+/// Ask before applying `--dedupe-names`. Without a terminal on stdin
+/// nothing is changed unless `-y` was given.
+fn confirm_dedupe() -> bool {
+    use std::io::IsTerminal;
+    if !std::io::stdin().is_terminal() {
+        eprintln!("No terminal to confirm on; re-run with -y to apply.");
+        return false;
+    }
+    eprint!("Apply these merges? [y/N] ");
+    let _ = std::io::stderr().flush();
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input).is_ok() && is_yes(&input)
+}
+
+/// Run `--dedupe-names`: show proposed merges, then apply them once confirmed
+fn run_dedupe_names(assume_yes: bool) {
+    let merges = find_name_merges(&load_records());
+    if merges.is_empty() {
+        eprintln!("No duplicate session names found.");
+        return;
+    }
+
+    let describe = |record: &SessionRecord| {
+        let used = record.last_used.map(format_utc).unwrap_or_else(|| "never".to_string());
+        format!("{}  {}  (used {})", record.uuid, record.name.as_deref().unwrap_or_default(), used)
+    };
+    for merge in &merges {
+        eprintln!("{}:", dedupe_key(merge.keep.name.as_deref().unwrap_or_default()));
+        eprintln!("  keep  {}", describe(&merge.keep));
+        for record in &merge.drop {
+            eprintln!("  drop  {}", describe(record));
+        }
+    }
+
+    if is_dry_run() {
+        let count: usize = merges.iter().map(|m| m.drop.len()).sum();
+        eprintln!("Would remove {} duplicate entries.", count);
+        return;
+    }
+    if !db_writable() || !(assume_yes || confirm_dedupe()) {
+        eprintln!("No changes made.");
+        return;
+    }
+    match apply_name_merges(&merges) {
+        Ok(removed) => eprintln!("Duplicate entries removed: {}", removed),
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            exit(EXIT_ERROR);
+        }
+    }
+}
+
+/// Whether a stored session name belongs to the project in `folder`: the
+/// folder alone, or followed by the separator, a `#` suffix, or an `@` base
+fn name_in_project(name: &str, folder: &str, separator: &str) -> bool {
+    name.strip_prefix(folder).is_some_and(|rest| {
+        rest.is_empty() || rest.starts_with(separator) || rest.starts_with([SUFFIX_MARKER, BASE_MARKER])
+    })
+}
+
+/// Whether a record's stored name belongs to one of the project `folders`
+/// (see `repo_folders`)
+fn record_in_project(record: &SessionRecord, folders: &[String], separator: &str) -> bool {
+    let name = record.name.as_deref().unwrap_or_default();
+    folders.iter().any(|folder| name_in_project(name, folder, separator))
+}
+
+/// Format a record as a JSON object for `--list --json`
+fn record_json(record: &SessionRecord) -> String {
+    let optional = |value: Option<String>| value.map_or("null".to_string(), |v| json_string(&v));
+    let tags: Vec<String> = record.tags.iter().map(|tag| json_string(tag)).collect();
+    format!(
+        "{{\"uuid\":{},\"name\":{},\"tags\":[{}],\"created\":{},\"used\":{}}}",
+        json_string(&record.uuid),
+        optional(record.name.clone()),
+        tags.join(","),
+        optional(record.created.map(format_utc)),
+        optional(record.last_used.map(format_utc))
+    )
+}
+
+/// Folder names whose sessions belong to the project at `cwd` (`--repo`,
+/// `--touch-all`): this folder, plus the repository root's when run from a subdirectory
+fn repo_folders(cwd: &Path) -> Vec<String> {
+    let mut folders: Vec<String> = get_folder_name_in(cwd).into_iter().collect();
+    if let Ok(root) = get_folder_name_in(&get_project_root(cwd)) {
+        if !folders.contains(&root) {
+            folders.push(root);
+        }
+    }
+    folders
+}
+
+/// Set the last-used time of every record named after one of `folders` to `now`.
+/// Returns how many records were touched.
+fn touch_project_records(records: &mut [SessionRecord], folders: &[String], separator: &str, now: u64) -> usize {
+    let mut touched = 0;
+    for record in records.iter_mut() {
+        if record_in_project(record, folders, separator) {
+            record.last_used = Some(now);
+            touched += 1;
+        }
+    }
+    touched
+}
+
+/// Run `--touch-all`: refresh the current project's sessions so `--gc` won't prune them
+fn run_touch_all(separator: &str) {
+    let folders = repo_folders(&env::current_dir().unwrap_or_default());
+    let _lock = lock_db();
+    let mut records = load_records();
+    let touched = touch_project_records(&mut records, &folders, separator, unix_now());
+    let project = folders.join(" or ");
+    if is_dry_run() {
+        eprintln!("Would touch {} session(s) for {}", touched, project);
+        return;
+    }
+    if touched == 0 {
+        eprintln!("No sessions for {}.", project);
+        return;
+    }
+    if !db_writable() {
+        eprintln!("Error: nothing touched");
+        exit(EXIT_ERROR);
+    }
+    let lines: Vec<String> = records.iter().map(SessionRecord::to_line).collect();
+    if let Err(e) = write_db_atomic(&lines) {
+        eprintln!("Error: Failed to write database: {}", e);
+        exit(EXIT_ERROR);
+    }
+    eprintln!("Touched {} session(s) for {}", touched, project);
+}
+
+/// How `--list` clusters sessions (`group_by`)
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum GroupBy {
+    /// The folder part of the stored name (the default, matching `folder+branch`)
+    Folder,
+    /// The branch part of the stored name
+    Branch,
+    /// Each of the session's tags
+    Tag,
+}
+
+impl GroupBy {
+    /// Parse a `group_by` value
+    fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "folder" => Ok(GroupBy::Folder),
+            "branch" => Ok(GroupBy::Branch),
+            "tag" => Ok(GroupBy::Tag),
+            other => Err(format!("must be folder, branch, or tag, got '{}'", other)),
+        }
+    }
+
+    /// The groups a record falls into, read from its stored `folder+branch` name
+    /// (without any `@base`/`#suffix` qualifier) or its tags
+    fn keys(self, record: &SessionRecord, separator: &str) -> Vec<String> {
+        // Tags don't depend on the name, so unnamed records are grouped by them too
+        if self == GroupBy::Tag {
+            return if record.tags.is_empty() { vec!["(untagged)".to_string()] } else { record.tags.clone() };
+        }
+        let Some(name) = record.name.as_deref() else {
+            return vec!["(unnamed)".to_string()];
+        };
+        let unqualified = name.split([BASE_MARKER, SUFFIX_MARKER]).next().unwrap_or(name);
+        let (folder, branch) = match unqualified.split_once(separator) {
+            Some((folder, branch)) => (folder, Some(branch)),
+            None => (unqualified, None),
+        };
+        if self == GroupBy::Folder {
+            vec![folder.to_string()]
+        } else {
+            vec![branch.unwrap_or("(no branch)").to_string()]
+        }
+    }
+}
+
+/// Cluster records by `group_by`, groups in order of first appearance.
+/// With `GroupBy::Tag`, a record carrying several tags appears in each group.
+fn group_records<'a>(
+    records: &'a [SessionRecord],
+    group_by: GroupBy,
+    separator: &str,
+) -> Vec<(String, Vec<&'a SessionRecord>)> {
+    let mut groups: Vec<(String, Vec<&SessionRecord>)> = Vec::new();
+    for record in records {
+        for key in group_by.keys(record, separator) {
+            match groups.iter_mut().find(|(k, _)| *k == key) {
+                Some((_, members)) => members.push(record),
+                None => groups.push((key, vec![record])),
+            }
+        }
+    }
+    groups
+}
+
+/// List sessions in the database, grouped by `group_by`, optionally only those
+/// carrying all `tags` and, with `--repo`, only those named after one of `project_folders`
+fn list_sessions(tags: &[String], project_folders: Option<&[String]>, separator: &str, group_by: GroupBy, json: bool) {
+    let records: Vec<SessionRecord> = load_records()
+        .into_iter()
+        .filter(|record| record.has_tags(tags))
+        .filter(|record| project_folders.is_none_or(|folders| record_in_project(record, folders, separator)))
+        .collect();
+    if json {
+        let items: Vec<String> = records.iter().map(record_json).collect();
+        println!("[{}]", items.join(","));
+    } else if records.is_empty() {
+        if let Some(folders) = project_folders {
+            println!("No sessions for {}.", folders.join(" or "));
+        } else if tags.is_empty() {
+            println!("No sessions in database.");
+        } else {
+            println!("No sessions tagged {}.", tags.join(", "));
+        }
+    } else {
+        println!("Sessions ({}):", records.len());
+        for (group, members) in group_records(&records, group_by, separator) {
+            println!("  {} ({})", group, members.len());
+            for record in members {
+                let mut line = format!("    {}", record.uuid);
+                if let Some(name) = &record.name {
+                    line.push_str(&format!("  {}", name));
+                }
+                if !record.tags.is_empty() {
+                    line.push_str(&format!("  [{}]", record.tags.join(", ")));
+                }
+                println!("{}", line);
+            }
+        }
+    }
+}
+
+/// Register `dst_name`'s session in the database alongside `src_name`'s.
+/// Claude keys conversations by UUID, so this records the mapping only;
+/// the conversation content itself is not copied.
+fn clone_session(src_name: &str, dst_name: &str) {
+    let src_uuid = generate_uuid5(src_name);
+    let dst_uuid = generate_uuid5(dst_name);
+    let sessions = load_sessions();
+
+    eprintln!("Source:      {} -> {}", src_name, src_uuid);
+    eprintln!("Destination: {} -> {}", dst_name, dst_uuid);
+
+    if !sessions.contains(&src_uuid) {
+        eprintln!("Warning: source session '{}' is not in the database", src_name);
+    }
+
+    if sessions.contains(&dst_uuid) {
+        eprintln!("Destination is already tracked; nothing to do.");
+    } else {
+        save_record(&SessionRecord::new(&dst_uuid, Some(dst_name)));
+        if is_dry_run() {
+            eprintln!("Would register the destination in the database.");
+        } else {
+            eprintln!("Destination registered in the database.");
+        }
+    }
+
+    eprintln!();
+    eprintln!("Note: conversation history is not duplicated. To continue the source");
+    eprintln!("conversation in a new session, run:");
+    eprintln!("    claude --resume {} --fork-session", src_uuid);
+}
+
+/// Get Claude's own config directory (CLAUDE_CONFIG_DIR or ~/.claude)
+fn get_claude_dir() -> Option<PathBuf> {
+    if let Ok(dir) = env::var("CLAUDE_CONFIG_DIR") {
+        return Some(PathBuf::from(dir));
+    }
+    get_home_dir().map(|home| home.join(".claude"))
+}
+
+/// Known locations in Claude's config directory, best effort by convention:
+/// (label, path) for the directory, its settings, session store, and global config
+fn claude_config_locations() -> Vec<(&'static str, PathBuf)> {
+    let mut locations = Vec::new();
+    if let Some(dir) = get_claude_dir() {
+        locations.push(("Config dir", dir.clone()));
+        locations.push(("Settings", dir.join("settings.json")));
+        locations.push(("Sessions", dir.join("projects")));
+    }
+    if let Some(home) = get_home_dir() {
+        locations.push(("Global", home.join(".claude.json")));
+    }
+    locations
+}
+
+/// Print Claude's config locations, opening its config directory if `open` is set
+/// (`--open-claude-config [--reveal]`)
+fn reveal_claude_config(open: bool) {
+    let locations = claude_config_locations();
+    let Some((_, dir)) = locations.first() else {
+        eprintln!("Error: could not determine Claude's config directory (set CLAUDE_CONFIG_DIR)");
+        exit(EXIT_ERROR);
+    };
+    for (label, path) in &locations[1..] {
+        let state = if path.exists() { "" } else { " (missing)" };
+        eprintln!("{:<11} {}{}", format!("{}:", label), path.display(), state);
+    }
+    if open {
+        reveal_path(dir);
+    } else {
+        println!("{}", dir.display());
+    }
+}
+
+/// A conversation found in Claude's session store
+struct ClaudeSession {
+    uuid: String,
+    project: String,
+}
+
+/// List conversations in Claude's session store (`<claude dir>/projects/<project>/<uuid>.jsonl`)
+fn list_claude_sessions() -> Result<Vec<ClaudeSession>, String> {
+    let projects_dir = get_claude_dir()
+        .ok_or_else(|| "Could not determine home directory".to_string())?
+        .join("projects");
+    let projects = fs::read_dir(&projects_dir).map_err(|_| {
+        format!("Claude session store not found at {}", projects_dir.display())
+    })?;
+
+    let mut found = Vec::new();
+    for project in projects.map_while(Result::ok) {
+        let Ok(files) = fs::read_dir(project.path()) else {
+            continue;
+        };
+        let project_name = project.file_name().to_string_lossy().to_string();
+        for file in files.map_while(Result::ok) {
+            let path = file.path();
+            if path.extension().and_then(|e| e.to_str()) != Some("jsonl") {
+                continue;
+            }
+            let Some(stem) = path.file_stem().and_then(|s| s.to_str()) else {
+                continue;
+            };
+            if stem.len() == 36 && parse_uuid(stem).is_some() {
+                found.push(ClaudeSession {
+                    uuid: stem.to_lowercase(),
+                    project: project_name.clone(),
+                });
+            }
+        }
+    }
+
+    found.sort_by(|a, b| (&a.project, &a.uuid).cmp(&(&b.project, &b.uuid)));
+    Ok(found)
+}
+
+/// List Claude's conversations, marking which are not tracked by cs (optionally adopting them)
+fn remote_list_sessions(adopt: bool) {
+    let claude_sessions = match list_claude_sessions() {
+        Ok(sessions) => sessions,
+        Err(e) => {
+            eprintln!("{}", e);
+            eprintln!("Set CLAUDE_CONFIG_DIR if Claude stores its data elsewhere.");
+            return;
+        }
+    };
+
+    let tracked = load_sessions();
+    let untracked: Vec<&ClaudeSession> = claude_sessions
+        .iter()
+        .filter(|s| !tracked.contains(&s.uuid))
+        .collect();
+
+    if claude_sessions.is_empty() {
+        println!("No sessions in Claude's store.");
+        return;
+    }
+
+    println!(
+        "Claude sessions ({}, {} untracked):",
+        claude_sessions.len(),
+        untracked.len()
+    );
+    for session in &claude_sessions {
+        let marker = if tracked.contains(&session.uuid) { "tracked" } else { "untracked" };
+        println!("  {}  {:<9}  {}", session.uuid, marker, session.project);
+    }
+
+    if adopt && !untracked.is_empty() {
+        for session in &untracked {
+            save_session(&session.uuid);
+        }
+        eprintln!();
+        eprintln!("Adopted {} session(s) into the cs database.", untracked.len());
+    }
+}
+
+/// Get the OS file manager launcher, if a GUI is likely available
+fn file_manager_command() -> Option<&'static str> {
+    if cfg!(target_os = "macos") {
+        return Some("open");
+    }
+    if cfg!(windows) {
+        return Some("explorer");
+    }
+    let has_display = env::var_os("DISPLAY").is_some() || env::var_os("WAYLAND_DISPLAY").is_some();
+    if has_display {
+        Some("xdg-open")
+    } else {
+        None
+    }
+}
+
+/// Print a path and, when a GUI is available, reveal its folder in the file manager
+fn reveal_path(path: &std::path::Path) {
+    println!("{}", path.display());
+
+    let Some(opener) = file_manager_command() else {
+        return;
+    };
+    let folder = if path.is_dir() {
+        path
+    } else {
+        path.parent().unwrap_or(path)
+    };
+    if !folder.exists() {
+        eprintln!("(folder does not exist yet: {})", folder.display());
+        return;
+    }
+    if let Err(e) = Command::new(opener).arg(folder).spawn() {
+        eprintln!("Could not open file manager ({}): {}", opener, e);
+    }
+}
+
+/// Get the clipboard copy command (program and args), if one is likely available
+fn clipboard_command() -> Option<(&'static str, &'static [&'static str])> {
+    if cfg!(target_os = "macos") {
+        return Some(("pbcopy", &[]));
+    }
+    if cfg!(windows) {
+        return Some(("clip", &[]));
+    }
+    if env::var_os("WAYLAND_DISPLAY").is_some() {
+        return Some(("wl-copy", &[]));
+    }
+    if env::var_os("DISPLAY").is_some() {
+        return Some(("xclip", &["-selection", "clipboard"]));
+    }
+    None
+}
+
+/// Copy text to the clipboard, returning whether it worked
+fn copy_to_clipboard(text: &str) -> bool {
+    let Some((program, args)) = clipboard_command() else {
+        return false;
+    };
+    let Ok(mut child) = Command::new(program)
+        .args(args)
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .spawn()
+    else {
+        return false;
+    };
+    let written = child.stdin.take().is_some_and(|mut stdin| stdin.write_all(text.as_bytes()).is_ok());
+    child.wait().is_ok_and(|status| status.success()) && written
+}
+
+/// Quote an argument for a POSIX shell (left as-is when no quoting is needed)
+fn shell_quote(arg: &str) -> String {
+    let safe = |c: char| c.is_ascii_alphanumeric() || "_@%+=:,./-".contains(c);
+    if !arg.is_empty() && arg.chars().all(safe) {
+        return arg.to_string();
+    }
+    format!("'{}'", arg.replace('\'', "'\\''"))
+}
+
+/// Format a shell-safe `claude ...` command line (through the `claude_launcher`)
+fn claude_command_line(args: &[String]) -> String {
+    launcher_argv(&claude_launcher(), args)
+        .iter()
+        .map(|arg| shell_quote(arg))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Characters used to draw the session info box
+struct BoxCharset {
+    top: &'static str,
+    side: &'static str,
+    bottom: &'static str,
+    horizontal: &'static str,
+    ellipsis: &'static str,
+}
+
+/// Unicode box-drawing characters (default)
+const UNICODE_BOX: BoxCharset = BoxCharset {
+    top: "┌",
+    side: "│",
+    bottom: "└",
+    horizontal: "─",
+    ellipsis: "…",
+};
+
+/// Plain ASCII characters for terminals/logs without Unicode support
+const ASCII_BOX: BoxCharset = BoxCharset {
+    top: "+",
+    side: "|",
+    bottom: "+",
+    horizontal: "-",
+    ellipsis: "...",
+};
+
+/// Width of the box's horizontal rules
+const BOX_WIDTH: usize = 45;
+
+/// Pick the box charset: `--ascii`, CS_ASCII=1, or a dumb terminal select ASCII
+fn box_charset(ascii_flag: bool) -> &'static BoxCharset {
+    let env_ascii = env::var("CS_ASCII").map(|v| v == "1").unwrap_or(false);
+    let dumb_term = env::var("TERM").map(|t| t == "dumb").unwrap_or(false);
+    if ascii_flag || env_ascii || dumb_term {
+        &ASCII_BOX
+    } else {
+        &UNICODE_BOX
+    }
+}
+
+/// Width of a row's label column ("Session: ")
+const BOX_LABEL_WIDTH: usize = 9;
+
+/// Detect the terminal width (COLUMNS, or `stty size` when stderr is a TTY)
+fn terminal_width() -> Option<usize> {
+    if let Some(cols) = env::var("COLUMNS").ok().and_then(|c| c.trim().parse().ok()) {
+        return Some(cols);
+    }
+
+    #[cfg(unix)]
+    {
+        use std::io::IsTerminal;
+        if !std::io::stderr().is_terminal() {
+            return None;
+        }
+        let tty = File::open("/dev/tty").ok()?;
+        let output = Command::new("stty").arg("size").stdin(tty).output().ok()?;
+        let size = String::from_utf8_lossy(&output.stdout);
+        size.split_whitespace().nth(1)?.parse().ok()
+    }
+
+    #[cfg(not(unix))]
+    None
+}
+
+/// Shorten `value` to at most `max` characters, ending with the ellipsis if cut
+fn truncate_value(value: &str, max: usize, ellipsis: &str) -> String {
+    if value.chars().count() <= max {
+        return value.to_string();
+    }
+    let ellipsis_len = ellipsis.chars().count();
+    if max <= ellipsis_len {
+        return value.chars().take(max).collect();
+    }
+    let kept: String = value.chars().take(max - ellipsis_len).collect();
+    format!("{}{}", kept, ellipsis)
+}
+
+/// Render the session info box as lines of `label: value` rows.
+/// With a known terminal `width`, long values are ellipsized to fit and the
+/// rules grow to fit long rows; otherwise the box uses the fixed BOX_WIDTH.
+fn render_info_box(rows: &[(&str, &str)], charset: &BoxCharset, width: Option<usize>) -> String {
+    // Content after the left border: " " + label column + value
+    let max_value = width.map(|w| w.saturating_sub(2 + BOX_LABEL_WIDTH).max(1));
+    let values: Vec<String> = rows
+        .iter()
+        .map(|(_, value)| match max_value {
+            Some(max) => truncate_value(value, max, charset.ellipsis),
+            None => value.to_string(),
+        })
+        .collect();
+
+    let rule_len = match width {
+        Some(w) => {
+            let longest = values
+                .iter()
+                .map(|v| 1 + BOX_LABEL_WIDTH + v.chars().count())
+                .max()
+                .unwrap_or(0);
+            longest.max(BOX_WIDTH).min(w.saturating_sub(1).max(1))
+        }
+        None => BOX_WIDTH,
+    };
+
+    let rule = charset.horizontal.repeat(rule_len);
+    let mut out = format!("{}{}\n", charset.top, rule);
+    for ((label, _), value) in rows.iter().zip(&values) {
+        let label = format!("{}:", label);
+        out.push_str(&format!("{} {:<width$}{}\n", charset.side, label, value, width = BOX_LABEL_WIDTH));
+    }
+    out.push_str(&format!("{}{}\n", charset.bottom, rule));
+    out
+}
+
+/// Convert a Unix timestamp (seconds) to UTC (year, month, day, hour, minute, second)
+fn unix_to_utc(secs: u64) -> (i64, u32, u32, u32, u32, u32) {
+    let days = (secs / 86_400) as i64;
+    let rem = secs % 86_400;
+
+    // Civil-from-days (Howard Hinnant's algorithm)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    (
+        year,
+        month,
+        day,
+        (rem / 3_600) as u32,
+        (rem % 3_600 / 60) as u32,
+        (rem % 60) as u32,
+    )
+}
+
+/// Format a Unix timestamp as an ISO 8601 UTC string (`2024-01-31T09:05:00Z`)
+fn format_utc(secs: u64) -> String {
+    let (year, month, day, hour, minute, second) = unix_to_utc(secs);
+    format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z", year, month, day, hour, minute, second)
+}
+
+/// Describe how long ago `then` was, relative to `now` (`3 days ago`)
+fn format_age(then: u64, now: u64) -> String {
+    let secs = now.saturating_sub(then);
+    let (count, unit) = match secs {
+        0..=59 => return "just now".to_string(),
+        60..=3_599 => (secs / 60, "minute"),
+        3_600..=86_399 => (secs / 3_600, "hour"),
+        _ => (secs / 86_400, "day"),
+    };
+    format!("{} {}{} ago", count, unit, if count == 1 { "" } else { "s" })
+}
+
+/// Get the directory holding database backups (next to the database file)
+fn get_backup_dir() -> PathBuf {
+    get_db_path().with_file_name("backups")
+}
+
+/// Get the directory holding per-session launch locks (next to the database file)
+fn get_lock_dir() -> PathBuf {
+    get_db_path().with_file_name("locks")
+}
+
+/// Whether a process with this PID is still running
+#[cfg(unix)]
+fn process_alive(pid: u32) -> bool {
+    Command::new("kill")
+        .args(["-0", &pid.to_string()])
+        .stderr(std::process::Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+/// Whether a process with this PID is still running
+#[cfg(windows)]
+fn process_alive(pid: u32) -> bool {
+    Command::new("tasklist")
+        .args(["/FI", &format!("PID eq {}", pid), "/NH"])
+        .output()
+        .is_ok_and(|output| String::from_utf8_lossy(&output.stdout).split_whitespace().any(|w| w == pid.to_string()))
+}
+
+/// Take the launch lock for `uuid` (`session_lock`): `<uuid>.lock` under the lock
+/// directory, holding this process's PID. On Unix the PID survives exec(), so the
+/// lock stays held for as long as claude runs; a lock whose process has exited is
+/// stale and gets reclaimed, which is how it is released on every platform.
+fn acquire_session_lock(uuid: &str) -> Result<(), String> {
+    let lock_dir = get_lock_dir();
+    fs::create_dir_all(&lock_dir).map_err(|e| format!("Failed to create lock directory: {}", e))?;
+    let lock_path = lock_dir.join(format!("{}.lock", uuid));
+
+    // Bounded: one retry after reclaiming a stale lock
+    for _ in 0..2 {
+        match OpenOptions::new().write(true).create_new(true).open(&lock_path) {
+            Ok(mut file) => {
+                return write!(file, "{}", std::process::id()).map_err(|e| format!("Failed to write lock file: {}", e));
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                let holder = fs::read_to_string(&lock_path).ok().and_then(|pid| pid.trim().parse::<u32>().ok());
+                if let Some(pid) = holder.filter(|pid| process_alive(*pid)) {
+                    return Err(format!("session already active in another window (pid {})", pid));
+                }
+                verbose_log(&format!("reclaiming stale lock {}", lock_path.display()));
+                let _ = fs::remove_file(&lock_path);
+            }
+            Err(e) => return Err(format!("Failed to create lock file: {}", e)),
+        }
+    }
+    Err(format!("could not take the session lock at {}", lock_path.display()))
+}
+
+/// Release a launch lock this process holds (for sessions cs waited on, since
+/// cs keeps running after claude exits)
+fn release_session_lock(uuid: &str) {
+    let lock_path = get_lock_dir().join(format!("{}.lock", uuid));
+    let ours = fs::read_to_string(&lock_path).is_ok_and(|pid| pid.trim() == std::process::id().to_string());
+    if ours {
+        let _ = fs::remove_file(&lock_path);
+    }
+}
+
+/// Take the session lock when `session_lock` is enabled, exiting if it is held
+fn lock_session_or_exit(config: &Config, uuid: &str) {
+    if !config.session_lock() {
+        return;
+    }
+    if let Err(e) = acquire_session_lock(uuid) {
+        eprintln!("Error: {}", e);
+        exit(EXIT_ERROR);
+    }
+}
+
+/// Copy the database to a timestamped file under the backup directory.
+/// Returns the backup path, or None if there is no database to back up.
+fn backup_db(retention: usize) -> Result<Option<PathBuf>, String> {
+    let db_path = get_db_path();
+    if !db_path.exists() {
+        return Ok(None);
+    }
+
+    let backup_dir = get_backup_dir();
+    fs::create_dir_all(&backup_dir)
+        .map_err(|e| format!("Failed to create backup directory: {}", e))?;
+
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default();
+    let (year, month, day, hour, minute, second) = unix_to_utc(now.as_secs());
+    let stamp = format!(
+        "{:04}{:02}{:02}-{:02}{:02}{:02}",
+        year, month, day, hour, minute, second
+    );
+
+    // Fixed-width nanoseconds, bumped past the newest backup of the same second,
+    // keep names unique and sorting chronologically
+    let prefix = format!("sessions-{}.", stamp);
+    let mut nanos = now.subsec_nanos();
+    let newest_nanos = list_backups().last().and_then(|path| {
+        let name = path.file_name()?.to_str()?;
+        name.strip_prefix(&prefix)?.strip_suffix(".bak")?.parse::<u32>().ok()
+    });
+    if let Some(newest_nanos) = newest_nanos {
+        nanos = nanos.max(newest_nanos + 1);
+    }
+    let backup_path = backup_dir.join(format!("{}{:09}.bak", prefix, nanos));
+
+    fs::copy(&db_path, &backup_path)
+        .map_err(|e| format!("Failed to back up database: {}", e))?;
+
+    prune_backups(retention);
+    Ok(Some(backup_path))
+}
+
+/// List existing database backups, oldest first
+fn list_backups() -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(get_backup_dir()) else {
+        return Vec::new();
+    };
+
+    let mut backups: Vec<PathBuf> = entries
+        .map_while(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.file_name()
+                .and_then(|n| n.to_str())
+                .map(|n| n.starts_with("sessions-") && n.ends_with(".bak"))
+                .unwrap_or(false)
+        })
+        .collect();
+
+    // Timestamped names sort chronologically
+    backups.sort();
+    backups
+}
+
+/// Remove the oldest backups so that at most `retention` remain.
+/// Returns the number of backups removed.
+fn prune_backups(retention: usize) -> usize {
+    let backups = list_backups();
+    if backups.len() <= retention {
+        return 0;
+    }
+
+    let excess = backups.len() - retention;
+    backups
+        .iter()
+        .take(excess)
+        .filter(|path| fs::remove_file(path).is_ok())
+        .count()
+}
+
+/// Back up the database and report the result
+fn run_backup(retention: usize) {
+    if is_dry_run() {
+        let db_path = get_db_path();
+        if db_path.exists() {
+            eprintln!("Would back up {} to {}", db_path.display(), get_backup_dir().display());
+        } else {
+            eprintln!("No session database to back up.");
+        }
+        return;
+    }
+    match backup_db(retention) {
+        Ok(Some(path)) => eprintln!("Backup saved to {}", path.display()),
+        Ok(None) => eprintln!("No session database to back up."),
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            exit(EXIT_ERROR);
+        }
+    }
+}
+
+/// Steps `--gc` runs (each can be skipped with `--no-repair`, `--no-prune`, `--no-orphans`)
+struct GcOptions {
+    repair: bool,
+    prune: bool,
+    orphans: bool,
+}
+
+/// How long after its last use a session missing from Claude's store is kept,
+/// since claude may not have written the conversation yet
+const ORPHAN_GRACE_SECS: u64 = 24 * 60 * 60;
+
+/// Split records into those kept and the counts pruned (last used before
+/// `prune_before`) and orphaned (not in `claude_uuids`, and not used since
+/// `orphan_before`). Entries without a timestamp are never pruned.
+fn gc_records(
+    records: Vec<SessionRecord>,
+    prune_before: Option<u64>,
+    claude_uuids: Option<&HashSet<String>>,
+    orphan_before: u64,
+) -> (Vec<SessionRecord>, usize, usize) {
+    let mut kept = Vec::new();
+    let (mut pruned, mut orphaned) = (0, 0);
+    for record in records {
+        let recent = record.last_used.max(record.created).is_some_and(|t| t >= orphan_before);
+        if prune_before.is_some_and(|cutoff| record.last_used.is_some_and(|used| used < cutoff)) {
+            pruned += 1;
+        } else if !recent && claude_uuids.is_some_and(|uuids| !uuids.contains(&record.uuid)) {
+            orphaned += 1;
+        } else {
+            kept.push(record);
+        }
+    }
+    (kept, pruned, orphaned)
+}
+
+/// Whether dropping `orphaned` of `total` sessions is drastic enough to ask
+/// first (more than half), e.g. because cs is looking at the wrong store
+fn orphans_need_confirmation(orphaned: usize, total: usize) -> bool {
+    orphaned * 2 > total
+}
+
+/// Ask before `--gc` drops most of the database as orphans. Without a terminal
+/// on stdin there is nobody to ask, so the orphans are kept.
+fn confirm_orphan_removal(orphaned: usize, total: usize) -> bool {
+    use std::io::IsTerminal;
+    if !std::io::stdin().is_terminal() {
+        eprintln!("Warning: {} of {} sessions are missing from Claude's store; keeping them (pass -y to remove)", orphaned, total);
+        return false;
+    }
+    eprint!("Remove {} of {} sessions as missing from Claude's store? [y/N] ", orphaned, total);
+    let _ = std::io::stderr().flush();
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input).is_ok() && is_yes(&input)
+}
+
+/// Clean up the database: back up, repair, prune stale sessions, and drop
+/// sessions missing from Claude's store. Dropping more than half the sessions
+/// as orphans needs confirmation unless `assume_yes`.
+fn run_gc(config: &Config, options: &GcOptions, assume_yes: bool) {
+    if db_blocked() {
+        eprintln!("Session database not cleaned.");
+        return;
+    }
+    let backup = if is_dry_run() {
+        Ok(get_db_path().exists().then(get_backup_dir))
+    } else {
+        backup_db(config.backup_retention())
+    };
+    let removed = if is_dry_run() { "would be removed" } else { "removed" };
+    match backup {
+        Ok(Some(path)) if is_dry_run() => eprintln!("Would back up to {}", path.display()),
+        Ok(Some(path)) => eprintln!("Backup saved to {}", path.display()),
+        Ok(None) => {
+            eprintln!("No session database to clean.");
+            return;
+        }
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            eprintln!("Database not cleaned.");
+            exit(EXIT_ERROR);
+        }
+    }
+
+    if options.repair {
+        match repair_db() {
+            Ok(report) => eprintln!(
+                "Repair:  {} duplicate(s) merged, {} line(s) normalized, {} blank line(s) dropped",
+                report.duplicates, report.normalized, report.blank
+            ),
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                exit(EXIT_ERROR);
+            }
+        }
+    }
+
+    let days = config.prune_after_days();
+    let prune_before = options.prune.then(|| unix_now().saturating_sub(days * 24 * 60 * 60));
+    let mut claude_uuids: Option<HashSet<String>> = if options.orphans {
+        match list_claude_sessions() {
+            // An empty store more likely means the wrong CLAUDE_CONFIG_DIR than no conversations
+            Ok(sessions) if sessions.is_empty() => {
+                eprintln!("Orphans: skipped (Claude's session store has no sessions)");
+                None
+            }
+            Ok(sessions) => Some(sessions.into_iter().map(|s| s.uuid).collect()),
+            Err(e) => {
+                eprintln!("Orphans: skipped ({})", e);
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    let orphan_before = unix_now().saturating_sub(ORPHAN_GRACE_SECS);
+    let records = load_records();
+    let (_, _, orphaned) = gc_records(records.clone(), prune_before, claude_uuids.as_ref(), orphan_before);
+    if orphans_need_confirmation(orphaned, records.len())
+        && !assume_yes
+        && !is_dry_run()
+        && !confirm_orphan_removal(orphaned, records.len())
+    {
+        claude_uuids = None;
+        eprintln!("Orphans: skipped");
+    }
+    // Re-read under the lock (not held across the prompt) so sessions other cs
+    // processes saved meanwhile are kept
+    let _lock = lock_db();
+    let records = load_records();
+    let before = records.len();
+    let (kept, pruned, orphaned) = gc_records(records, prune_before, claude_uuids.as_ref(), orphan_before);
+    if kept.len() != before {
+        let lines: Vec<String> = kept.iter().map(SessionRecord::to_line).collect();
+        if let Err(e) = write_db_atomic(&lines) {
+            eprintln!("Error: Failed to write database: {}", e);
+            exit(EXIT_ERROR);
+        }
+    }
+    if options.prune {
+        eprintln!("Prune:   {} session(s) unused for over {} days {}", pruned, days, removed);
+    }
+    if claude_uuids.is_some() {
+        eprintln!("Orphans: {} session(s) missing from Claude's store {}", orphaned, removed);
+    }
+    eprintln!("Sessions: {} -> {}", before, kept.len());
+}
+
+/// Describe which database `--clear` acts on: the profile and its path
+fn clear_target(profile: Option<&str>, db_path: &Path) -> String {
+    match profile {
+        Some(profile) => format!("profile '{}' ({})", profile, db_path.display()),
+        None => format!("the default profile ({})", db_path.display()),
+    }
+}
+
+/// Ask before clearing the database of `target`. Without a terminal on stdin
+/// there is nobody to ask, so clearing proceeds.
+fn confirm_clear(target: &str, count: usize) -> bool {
+    use std::io::IsTerminal;
+    if !std::io::stdin().is_terminal() {
+        return true;
+    }
+    eprint!("Delete all {} session(s) in {}? [y/N] ", count, target);
+    let _ = std::io::stderr().flush();
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input).is_ok() && is_yes(&input)
+}
+
+/// Clear entire session database (after taking a backup), first naming the
+/// profile and path it applies to and, on a terminal, asking unless `assume_yes`.
+/// With `dry_run`, only report what would be removed.
+fn clear_sessions(retention: usize, dry_run: bool, assume_yes: bool) {
+    let db_path = get_db_path();
+    let target = clear_target(get_profile().as_deref(), &db_path);
+    eprintln!("Target: {}", target);
+    if dry_run {
+        let count = load_sessions().len();
+        if db_path.exists() {
+            eprintln!("Would remove {} session(s) by deleting {}", count, db_path.display());
+        } else {
+            eprintln!("Session database already empty ({} does not exist).", db_path.display());
+        }
+        return;
+    }
+    if !db_writable() {
+        eprintln!("Session database not cleared.");
+        return;
+    }
+    if db_path.exists() && !assume_yes && !confirm_clear(&target, load_sessions().len()) {
+        eprintln!("Session database not cleared.");
+        return;
+    }
+
+    match backup_db(retention) {
+        Ok(Some(path)) => eprintln!("Backup saved to {}", path.display()),
+        Ok(None) => {}
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            eprintln!("Database not cleared.");
+            return;
+        }
+    }
+    // Empty a symlinked database in place rather than deleting the link
+    let cleared = if fs::symlink_metadata(&db_path).is_ok_and(|m| m.file_type().is_symlink()) {
+        write_db_atomic(&[])
+    } else {
+        fs::remove_file(&db_path)
+    };
+    match cleared {
+        Ok(_) => eprintln!("Session database cleared."),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            eprintln!("Session database already empty.");
+        }
+        Err(e) => {
+            eprintln!("Error clearing database: {}", e);
+        }
+    }
+}
+
+/// Format bytes as lowercase hex
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Format 16 bytes in canonical 8-4-4-4-12 UUID form
+fn format_uuid(bytes: &[u8; 16]) -> String {
+    format!(
+        "{:08x}-{:04x}-{:04x}-{:04x}-{:012x}",
+        u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+        u16::from_be_bytes([bytes[4], bytes[5]]),
+        u16::from_be_bytes([bytes[6], bytes[7]]),
+        u16::from_be_bytes([bytes[8], bytes[9]]),
+        u64::from_be_bytes([0, 0, bytes[10], bytes[11], bytes[12], bytes[13], bytes[14], bytes[15]])
+    )
+}
+
+/// SHA-1 digest of namespace + name (the UUID v5 input, before version/variant bits)
+fn uuid5_digest(namespace: &[u8; 16], name: &str) -> [u8; 20] {
+    let mut hasher = Sha1::new();
+    hasher.update(namespace);
+    hasher.update(name.as_bytes());
+    hasher.finalize().into()
+}
+
+/// Compute the bytes of a UUID v5 for a name in a namespace
+fn uuid5_bytes(namespace: &[u8; 16], name: &str) -> [u8; 16] {
+    let hash = uuid5_digest(namespace, name);
+
+    let mut bytes = [0u8; 16];
+    bytes.copy_from_slice(&hash[..16]);
+    bytes[6] = (bytes[6] & 0x0f) | 0x50; // Version 5
+    bytes[8] = (bytes[8] & 0x3f) | 0x80; // Variant 10xx
+    bytes
+}
+
+/// Generate a deterministic UUID v5 from a name in an explicit namespace
+fn generate_uuid5_with(name: &str, namespace: &[u8; 16]) -> String {
+    format_uuid(&uuid5_bytes(namespace, name))
+}
+
+/// Generate a deterministic UUID v5 from a name using the configured namespace
+fn generate_uuid5(name: &str) -> String {
+    generate_uuid5_with(name, &get_namespace())
+}
+
+/// Build passthrough tokens for a value flag, expanding comma-separated
+/// lists into repeated flags for the flags in CLAUDE_REPEATABLE_FLAGS.
+/// A list with nothing but commas is an error rather than a dropped flag.
+fn expand_value_flag(flag: &str, value: &str) -> Result<Vec<String>, String> {
+    if !CLAUDE_REPEATABLE_FLAGS.contains(&flag) || !value.contains(',') {
+        return Ok(vec![flag.to_string(), value.to_string()]);
+    }
+    let expanded: Vec<String> = value
+        .split(',')
+        .filter(|item| !item.is_empty())
+        .flat_map(|item| [flag.to_string(), item.to_string()])
+        .collect();
+    if expanded.is_empty() {
+        return Err(format!("'{}' requires at least one value, got '{}'", flag, value));
+    }
+    Ok(expanded)
+}
+
+/// Claude arguments for a `--key=value` argument: repeatable flags are split
+/// (see `expand_value_flag`), other known flags pass as written, and unknown
+/// keys are rejected (None) unless `loose` (`--loose`/`loose_args`), when they
+/// are forwarded as-is in case claude knows a flag cs doesn't yet
+fn key_value_args(arg: &str, loose: bool) -> Option<Result<Vec<String>, String>> {
+    let (key, value) = arg.split_once('=')?;
+    if CLAUDE_REPEATABLE_FLAGS.contains(&key) {
+        Some(expand_value_flag(key, value))
+    } else if CLAUDE_VALUE_FLAGS.contains(&key) || CLAUDE_BOOL_FLAGS.contains(&key) {
+        Some(Ok(vec![arg.to_string()]))
+    } else if loose && key.starts_with("--") {
+        verbose_log(&format!("forwarding unknown argument {} (loose mode)", arg));
+        Some(Ok(vec![arg.to_string()]))
+    } else {
+        None
+    }
+}
+
+/// Project config file, kept (and usually committed) at the project root
+const PROJECT_CONFIG_FILE: &str = ".cs.toml";
+
+/// Group claude args into units: a value flag with its value, or a single token
+fn arg_units(args: &[String]) -> Vec<&[String]> {
+    let mut units = Vec::new();
+    let mut i = 0;
+    while i < args.len() {
+        let len = if CLAUDE_VALUE_FLAGS.contains(&args[i].as_str()) && i + 1 < args.len() { 2 } else { 1 };
+        units.push(&args[i..i + len]);
+        i += len;
+    }
+    units
+}
+
+/// Flag name of an argument unit (`--model=opus` gives `--model`); None for positional args
+fn unit_flag(unit: &[String]) -> Option<&str> {
+    let first = unit.first()?;
+    first.starts_with('-').then(|| first.split('=').next().unwrap_or(first))
+}
+
+/// Split a command-line string into words, honoring single and double quotes
+/// (and backslash escapes outside single quotes)
+fn split_args(input: &str) -> Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    let mut current = String::new();
+    let mut in_word = false;
+    let mut quote: Option<char> = None;
+    let mut chars = input.chars();
+
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some('"'), '\\') | (None, '\\') => {
+                current.push(chars.next().ok_or("trailing backslash")?);
+                in_word = true;
+            }
+            (Some(_), c) => current.push(c),
+            (None, '"') | (None, '\'') => {
+                quote = Some(c);
+                in_word = true;
+            }
+            (None, c) if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut current));
+                    in_word = false;
+                }
+            }
+            (None, c) => {
+                current.push(c);
+                in_word = true;
+            }
+        }
+    }
+    if let Some(q) = quote {
+        return Err(format!("unterminated {} quote", q));
+    }
+    if in_word {
+        words.push(current);
+    }
+    Ok(words)
+}
+
+/// File recording which project `.cs.toml` files the user has reviewed (`cs trust`):
+/// one `<sha1>\t<path>` line per file
+fn get_trust_path() -> PathBuf {
+    get_cs_dir().join("trusted")
+}
+
+/// Hex SHA-1 of a project config's content, so any edit needs a new `cs trust`
+fn content_digest(content: &[u8]) -> String {
+    to_hex(&Sha1::digest(content))
+}
+
+/// Whether `content` is the version of the project config at `path` the user trusted
+fn is_project_config_trusted(path: &Path, content: &[u8]) -> bool {
+    let record = format!("{}\t{}", content_digest(content), path.display());
+    fs::read_to_string(get_trust_path()).is_ok_and(|trusted| trusted.lines().any(|line| line == record))
+}
+
+/// Trust the current content of the project config at `path`, replacing any
+/// earlier record for the same file
+fn trust_project_config(path: &Path) -> Result<(), String> {
+    let content = fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let trust_path = get_trust_path();
+    let suffix = format!("\t{}", path.display());
+    let mut lines: Vec<String> = fs::read_to_string(&trust_path)
+        .unwrap_or_default()
+        .lines()
+        .filter(|line| !line.ends_with(&suffix))
+        .map(str::to_string)
+        .collect();
+    lines.push(format!("{}{}", content_digest(&content), suffix));
+
+    if let Some(parent) = trust_path.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+    }
+    fs::write(&trust_path, lines.join("\n") + "\n")
+        .map_err(|e| format!("Failed to write {}: {}", trust_path.display(), e))
+}
+
+/// Load the project config (`.cs.toml` at the project root of `dir`). A file the
+/// user hasn't trusted is ignored with a warning: a checked-out repository
+/// shouldn't be able to add claude flags such as `--dangerously-skip-permissions`.
+fn load_project_config(dir: &Path) -> Result<Config, String> {
+    let path = get_project_root(dir).join(PROJECT_CONFIG_FILE);
+    let content = match fs::read(&path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Config::default()),
+        Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+    };
+    if !is_project_config_trusted(&path, &content) {
+        eprintln!("Warning: ignoring untrusted {}", path.display());
+        eprintln!("Review it, then run 'cs trust' to use its default_args.");
+        return Ok(Config::default());
+    }
+    // Environment variables are only expanded in the user's own config, so a
+    // committed file can't pass e.g. $AWS_SECRET_ACCESS_KEY to claude
+    config_or_defaults(&path, parse_config(&String::from_utf8_lossy(&content)), is_strict_config())
+}
+
+/// Run `cs trust`: trust this project's `.cs.toml` as it is now
+fn run_trust() {
+    let cwd = env::current_dir().unwrap_or_else(|_| {
+        eprintln!("Error: Failed to get current directory");
+        exit(EXIT_ERROR);
+    });
+    let path = get_project_root(&cwd).join(PROJECT_CONFIG_FILE);
+    if !path.is_file() {
+        eprintln!("Error: no {} in {}", PROJECT_CONFIG_FILE, path.parent().unwrap_or(&cwd).display());
+        exit(EXIT_ERROR);
+    }
+    let config = fs::read_to_string(&path)
+        .map_err(|e| e.to_string())
+        .and_then(|content| parse_config(&content))
+        .unwrap_or_else(|e| {
+            eprintln!("Error: {}: {}", path.display(), e);
+            exit(EXIT_USAGE);
+        });
+    if let Err(e) = trust_project_config(&path) {
+        eprintln!("Error: {}", e);
+        exit(EXIT_ERROR);
+    }
+    eprintln!("Trusted {}", path.display());
+    if !config.default_args().is_empty() {
+        eprintln!("default_args: {}", config.default_args().join(" "));
+    }
+}
+
+/// Put default args ahead of the command-line args. `layers` are ordered
+/// from highest precedence (CS_CLAUDE_ARGS) to lowest (user config); a flag already given
+/// by the command line or a higher layer is skipped, except repeatable flags,
+/// which accumulate.
+fn merge_default_args(cli: Vec<String>, layers: &[&[String]]) -> Vec<String> {
+    let mut seen: HashSet<String> = arg_units(&cli).into_iter().filter_map(unit_flag).map(str::to_string).collect();
+    let mut merged = Vec::new();
+    for layer in layers {
+        let mut layer_flags = Vec::new();
+        for unit in arg_units(layer) {
+            if let Some(flag) = unit_flag(unit) {
+                if seen.contains(flag) && !CLAUDE_REPEATABLE_FLAGS.contains(&flag) {
+                    continue;
+                }
+                layer_flags.push(flag.to_string());
+            }
+            merged.extend_from_slice(unit);
+        }
+        seen.extend(layer_flags);
+    }
+    merged.extend(cli);
+    merged
+}
+
+/// Append claude args (e.g. "--mcp-config ./mcp.json") to the project's
+/// `default_args` in `.cs.toml`, returning the file written
+fn append_project_arg(dir: &Path, value: &str) -> Result<PathBuf, String> {
+    let tokens: Vec<String> = value.split_whitespace().map(str::to_string).collect();
+    if !tokens.first().is_some_and(|t| t.starts_with('-')) {
+        return Err("'--append-arg' requires claude flags, e.g. \"--mcp-config ./mcp.json\"".to_string());
+    }
+
+    let path = get_project_root(dir).join(PROJECT_CONFIG_FILE);
+    let content = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+    };
+    let existing = parse_config(&content).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let mut args = existing.default_args().to_vec();
+    args.extend(tokens);
+    // The user is writing these args themselves; keep trusting a file they already trusted
+    let trusted = content.is_empty() || is_project_config_trusted(&path, content.as_bytes());
+    if is_dry_run() {
+        return Ok(path);
+    }
+
+    fs::write(&path, set_top_level_array(&content, "default_args", &args))
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    if trusted {
+        trust_project_config(&path)?;
+    }
+    Ok(path)
+}
+
+/// Whether the claude args ask for machine-readable output
+/// (`--output-format json|stream-json`), so cs should stay quiet
+fn wants_machine_output(args: &[String]) -> bool {
+    let format = args.iter().enumerate().find_map(|(i, arg)| match arg.split_once('=') {
+        Some(("--output-format", value)) => Some(value),
+        _ if arg == "--output-format" => args.get(i + 1).map(String::as_str),
+        _ => None,
+    });
+    matches!(format, Some("json" | "stream-json"))
+}
+
+/// Resolve a `--launch-dir` value to an absolute directory
+fn resolve_launch_dir(value: &str) -> Result<PathBuf, String> {
+    let path = Path::new(value);
+    if !path.is_dir() {
+        return Err(format!("--launch-dir: not a directory: {}", value));
+    }
+    path.canonicalize()
+        .map_err(|e| format!("--launch-dir: {}: {}", value, e))
+}
+
+/// Switch to the `--launch-dir` directory so claude starts there.
+/// The session identity has already been computed from the invocation directory.
+fn enter_launch_dir(dir: Option<&Path>) {
+    if let Some(dir) = dir {
+        if let Err(e) = env::set_current_dir(dir) {
+            eprintln!("Error: failed to enter {}: {}", dir.display(), e);
+            exit(EXIT_ERROR);
+        }
+        verbose_log(&format!("launch dir: {}", dir.display()));
+    }
+}
+
+/// Verify that path-valued flags in `args` name existing files, resolving
+/// relative paths against `base` (the directory claude will start in).
+/// Inline JSON values (starting with `{`) are accepted as-is.
+fn check_path_args(args: &[String], base: &Path) -> Result<(), String> {
+    let mut i = 0;
+    while i < args.len() {
+        let (flag, value) = match args[i].split_once('=') {
+            Some((flag, value)) => (flag, Some(value)),
+            None if CLAUDE_PATH_FLAGS.contains(&args[i].as_str()) => {
+                i += 1;
+                (args[i - 1].as_str(), args.get(i).map(String::as_str))
+            }
+            None => (args[i].as_str(), None),
+        };
+        if let Some(value) = value.filter(|v| CLAUDE_PATH_FLAGS.contains(&flag) && !v.trim_start().starts_with('{')) {
+            if !base.join(value).exists() {
+                return Err(format!("file not found: {} for {}", value, flag));
+            }
+        }
+        i += 1;
+    }
+    Ok(())
+}
+
+/// Print the exact inputs and intermediate digest behind a session UUID
+fn explain_uuid(name: &str) {
+    let namespace = get_namespace();
+    let digest = uuid5_digest(&namespace, name);
+
+    eprintln!("UUID v5 derivation:");
+    eprintln!("  Namespace: {}", format_uuid(&namespace));
+    eprintln!("             bytes: {}", to_hex(&namespace));
+    eprintln!("  Name:      {:?}", name);
+    eprintln!("             bytes: {}", to_hex(name.as_bytes()));
+    eprintln!("  SHA-1:     {}", to_hex(&digest));
+    eprintln!("             (first 16 bytes used; version 5 set in byte 6, variant 10xx in byte 8)");
+    eprintln!("  UUID:      {}", generate_uuid5(name));
+}
+
+/// Get the git branch name checked out in a directory
+fn get_git_branch_in(dir: &Path) -> Result<String, String> {
+    let output = Command::new("git")
+        .args(["rev-parse", "--abbrev-ref", "HEAD"])
+        .current_dir(dir)
+        .output()
+        .map_err(|_| "Failed to execute git command")?;
+
+    if !output.status.success() {
+        return Err("Not a git repository or no branch found".to_string());
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Get the abbreviated merge-base of HEAD and `base_ref` in a directory
+fn get_merge_base_in(dir: &Path, base_ref: &str) -> Result<String, String> {
+    let output = Command::new("git")
+        .args(["merge-base", "HEAD", base_ref])
+        .current_dir(dir)
+        .output()
+        .map_err(|_| "Failed to execute git command")?;
+
+    if !output.status.success() {
+        return Err(format!("no merge-base between HEAD and '{}'", base_ref));
+    }
+
+    let commit = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    Ok(commit.chars().take(BASE_COMMIT_LEN).collect())
+}
+
+/// Check whether a local branch exists in a directory's repository
+fn local_branch_exists_in(dir: &Path, branch: &str) -> bool {
+    Command::new("git")
+        .args(["show-ref", "--verify", "--quiet", &format!("refs/heads/{}", branch)])
+        .current_dir(dir)
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+/// Get the project root for a directory (git toplevel, or the directory itself)
+fn get_project_root(dir: &Path) -> PathBuf {
+    Command::new("git")
+        .args(["rev-parse", "--show-toplevel"])
+        .current_dir(dir)
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| PathBuf::from(String::from_utf8_lossy(&output.stdout).trim()))
+        .unwrap_or_else(|| dir.to_path_buf())
+}
+
+/// Set from `case_insensitive`: fold folder names to one spelling before hashing
+static CASE_INSENSITIVE: AtomicBool = AtomicBool::new(false);
+
+/// Fold a folder name for case-insensitive filesystems (`case_insensitive`):
+/// lowercase it, then normalize it to NFC so the decomposed spellings macOS
+/// reports (e.g. `e` + U+0301) match their precomposed forms.
+fn fold_folder_name(name: &str) -> String {
+    use unicode_normalization::UnicodeNormalization;
+    name.chars().flat_map(char::to_lowercase).nfc().collect()
+}
+
+/// Get the folder name of a directory (folded with `case_insensitive`)
+fn get_folder_name_in(dir: &Path) -> Result<String, String> {
+    let name = dir
+        .file_name()
+        .and_then(|n| n.to_str())
+        .ok_or_else(|| "Failed to get folder name".to_string())?;
+    Ok(if CASE_INSENSITIVE.load(Ordering::Relaxed) {
+        fold_folder_name(name)
+    } else {
+        name.to_string()
+    })
+}
+
+/// Session identity derived from a directory's folder name and git branch
+struct SessionIdentity {
+    name: String,
+    uuid: String,
+    is_git_repo: bool,
+}
+
+/// Compute the session name and UUID as if cs were run inside `dir`
+/// (falls back to folder-only naming outside a git repo, or when `detect_git` is false)
+fn resolve_session(dir: &Path, separator: &str, detect_git: bool) -> Result<SessionIdentity, String> {
+    let folder = get_folder_name_in(dir)?;
+    verbose_log(&format!("folder: {}", folder));
+
+    let git_branch = if detect_git {
+        get_git_branch_in(dir)
+    } else {
+        Err("git detection disabled by --no-git".to_string())
+    };
+    let branch = match git_branch {
+        Ok(branch) => {
+            verbose_log(&format!("git branch: {}", branch));
+            Some(branch)
+        }
+        Err(e) => {
+            verbose_log(&format!("git: {} (folder-only mode)", e));
+            None
+        }
+    };
+
+    let name = build_session_name(&folder, branch.as_deref(), separator);
+    let uuid = generate_uuid5(&name);
+    Ok(SessionIdentity {
+        name,
+        uuid,
+        is_git_repo: branch.is_some(),
+    })
+}
+
+/// Enforce `require_git`: folder-only sessions are treated as a misconfiguration
+fn check_require_git(identity: &SessionIdentity) -> Result<(), String> {
+    if identity.is_git_repo {
+        return Ok(());
+    }
+    Err(format!(
+        "not in a git repository, refusing folder-only session '{}' (require_git is set; use --session-name to name one explicitly)",
+        identity.name
+    ))
+}
+
+/// Tie a git session to the merge-base of HEAD and `base_ref` (`base_ref`,
+/// `--since-commit`), so rebasing onto a new base starts a fresh session.
+/// The base branch itself is left alone, and failures fall back with a warning.
+fn apply_base_ref(identity: SessionIdentity, dir: &Path, base_ref: &str) -> SessionIdentity {
+    if !identity.is_git_repo {
+        return identity;
+    }
+    let on_base = get_git_branch_in(dir)
+        .is_ok_and(|branch| base_ref == branch || base_ref.ends_with(&format!("/{}", branch)));
+    if on_base {
+        return identity;
+    }
+    match get_merge_base_in(dir, base_ref) {
+        Ok(commit) => {
+            verbose_log(&format!("merge-base with {}: {}", base_ref, commit));
+            identity.with_base(&commit)
+        }
+        Err(e) => {
+            eprintln!("Warning: {}; using plain branch naming", e);
+            identity
+        }
+    }
+}
+
+impl SessionIdentity {
+    /// Derive a session tied to a base commit by appending `@<commit>` to the name
+    fn with_base(self, commit: &str) -> Self {
+        let name = format!("{}{}{}", self.name, BASE_MARKER, commit);
+        let uuid = generate_uuid5(&name);
+        SessionIdentity { name, uuid, ..self }
+    }
+
+    /// Derive a parallel session by appending `#<suffix>` to the name
+    fn with_suffix(self, suffix: &str) -> Self {
+        let name = format!("{}{}{}", self.name, SUFFIX_MARKER, suffix);
+        let uuid = generate_uuid5(&name);
+        SessionIdentity { name, uuid, ..self }
+    }
+
+    /// Recompute the UUID in an explicit namespace (a `--stdin-json` request's `namespace`)
+    fn in_namespace(self, namespace: &[u8; 16]) -> Self {
+        let uuid = generate_uuid5_with(&self.name, namespace);
+        SessionIdentity { uuid, ..self }
+    }
+}
+
+/// Separates a session name from its base commit (`app+feature@1a2b3c4d`)
+const BASE_MARKER: char = '@';
+
+/// Number of merge-base hex digits kept in a session name
+const BASE_COMMIT_LEN: usize = 8;
+
+/// Separates a session name from its parallel-session suffix (`app+main#2`)
+const SUFFIX_MARKER: char = '#';
+
+/// Find the smallest N >= 1 such that `<base_name>#N` isn't in the database
+fn next_free_suffix(base_name: &str, sessions: &HashSet<String>) -> u32 {
+    (1..)
+        .find(|n| !sessions.contains(&generate_uuid5(&format!("{}{}{}", base_name, SUFFIX_MARKER, n))))
+        .unwrap_or(1)
+}
+
+/// How claude is launched for the current session
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum LaunchMode {
+    /// `--resume <uuid>`: resume, falling back to Claude's picker
+    ResumePicker,
+    /// `--session-id <uuid>`: start a new conversation
+    Create,
+    /// `-r <uuid>`: resume the existing conversation
+    Resume,
+}
+
+impl LaunchMode {
+    /// Pick the launch mode from `--resume`, `--force`/`--reset`, and whether the session exists
+    fn decide(resume_mode: bool, force_create: bool, session_exists: bool) -> Self {
+        if resume_mode {
+            LaunchMode::ResumePicker
+        } else if force_create || !session_exists {
+            LaunchMode::Create
+        } else {
+            LaunchMode::Resume
+        }
+    }
+
+    /// The session arguments passed to claude
+    fn session_args(self, uuid: &str) -> Vec<String> {
+        let flag = match self {
+            LaunchMode::ResumePicker => "--resume",
+            LaunchMode::Create => "--session-id",
+            LaunchMode::Resume => "-r",
+        };
+        vec![flag.to_string(), uuid.to_string()]
+    }
+}
+
+/// Escape a string as a JSON string literal
+fn json_string(value: &str) -> String {
+    let mut out = String::with_capacity(value.len() + 2);
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// A parsed JSON value (numbers are kept as written; cs only needs strings)
+#[derive(Debug, Clone, PartialEq)]
+enum JsonValue {
+    Null,
+    Bool(bool),
+    Number(String),
+    String(String),
+    Array(Vec<JsonValue>),
+    Object(Vec<(String, JsonValue)>),
+}
+
+type JsonChars<'a> = std::iter::Peekable<std::str::Chars<'a>>;
+
+/// Deepest array/object nesting `parse_json` accepts, so hostile input can't overflow the stack
+const JSON_MAX_DEPTH: usize = 64;
+
+/// Parse a single JSON document (`--stdin-json`)
+fn parse_json(input: &str) -> Result<JsonValue, String> {
+    let mut chars = input.chars().peekable();
+    let value = parse_json_value(&mut chars, 0)?;
+    skip_json_whitespace(&mut chars);
+    match chars.next() {
+        None => Ok(value),
+        Some(c) => Err(format!("unexpected '{}' after the JSON value", c)),
+    }
+}
+
+fn skip_json_whitespace(chars: &mut JsonChars) {
+    while chars.next_if(|c| matches!(c, ' ' | '\t' | '\n' | '\r')).is_some() {}
+}
+
+/// Consume `literal` (`null`, `true`, `false`) and return `value`
+fn expect_json_literal(chars: &mut JsonChars, literal: &str, value: JsonValue) -> Result<JsonValue, String> {
+    for expected in literal.chars() {
+        if chars.next() != Some(expected) {
+            return Err(format!("invalid literal, expected '{}'", literal));
+        }
+    }
+    Ok(value)
+}
+
+/// Parse one JSON value, including any nested arrays and objects
+fn parse_json_value(chars: &mut JsonChars, depth: usize) -> Result<JsonValue, String> {
+    skip_json_whitespace(chars);
+    if depth == JSON_MAX_DEPTH && matches!(chars.peek(), Some('[' | '{')) {
+        return Err(format!("nested deeper than {} levels", JSON_MAX_DEPTH));
+    }
+    match chars.peek().copied() {
+        None => Err("unexpected end of input".to_string()),
+        Some('n') => expect_json_literal(chars, "null", JsonValue::Null),
+        Some('t') => expect_json_literal(chars, "true", JsonValue::Bool(true)),
+        Some('f') => expect_json_literal(chars, "false", JsonValue::Bool(false)),
+        Some('"') => parse_json_string(chars).map(JsonValue::String),
+        Some('[') => {
+            chars.next();
+            let mut items = Vec::new();
+            skip_json_whitespace(chars);
+            if chars.next_if_eq(&']').is_some() {
+                return Ok(JsonValue::Array(items));
+            }
+            loop {
+                items.push(parse_json_value(chars, depth + 1)?);
+                skip_json_whitespace(chars);
+                match chars.next() {
+                    Some(',') => {}
+                    Some(']') => return Ok(JsonValue::Array(items)),
+                    _ => return Err("expected ',' or ']' in array".to_string()),
+                }
+            }
+        }
+        Some('{') => {
+            chars.next();
+            let mut fields = Vec::new();
+            skip_json_whitespace(chars);
+            if chars.next_if_eq(&'}').is_some() {
+                return Ok(JsonValue::Object(fields));
+            }
+            loop {
+                skip_json_whitespace(chars);
+                if chars.peek() != Some(&'"') {
+                    return Err("expected a string key in object".to_string());
+                }
+                let key = parse_json_string(chars)?;
+                skip_json_whitespace(chars);
+                if chars.next() != Some(':') {
+                    return Err(format!("expected ':' after key \"{}\"", key));
+                }
+                fields.push((key, parse_json_value(chars, depth + 1)?));
+                skip_json_whitespace(chars);
+                match chars.next() {
+                    Some(',') => {}
+                    Some('}') => return Ok(JsonValue::Object(fields)),
+                    _ => return Err("expected ',' or '}' in object".to_string()),
+                }
+            }
+        }
+        Some(c) if c == '-' || c.is_ascii_digit() => {
+            let mut number = String::new();
+            while let Some(c) = chars.next_if(|c| c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E')) {
+                number.push(c);
+            }
+            Ok(JsonValue::Number(number))
+        }
+        Some(c) => Err(format!("unexpected '{}'", c)),
+    }
+}
+
+/// Read the four hex digits of a `\u` escape
+fn parse_json_hex4(chars: &mut JsonChars) -> Result<u32, String> {
+    let hex: String = chars.by_ref().take(4).collect();
+    if hex.len() != 4 {
+        return Err(format!("invalid escape \\u{}", hex));
+    }
+    u32::from_str_radix(&hex, 16).map_err(|_| format!("invalid escape \\u{}", hex))
+}
+
+/// Parse a JSON string literal, starting at its opening quote
+fn parse_json_string(chars: &mut JsonChars) -> Result<String, String> {
+    chars.next(); // opening quote
+    let mut out = String::new();
+    loop {
+        match chars.next() {
+            None => return Err("unterminated string".to_string()),
+            Some('"') => return Ok(out),
+            Some('\\') => match chars.next() {
+                Some('"') => out.push('"'),
+                Some('\\') => out.push('\\'),
+                Some('/') => out.push('/'),
+                Some('b') => out.push('\u{8}'),
+                Some('f') => out.push('\u{c}'),
+                Some('n') => out.push('\n'),
+                Some('r') => out.push('\r'),
+                Some('t') => out.push('\t'),
+                Some('u') => {
+                    let mut code = parse_json_hex4(chars)?;
+                    // Characters outside the BMP arrive as a \uD8xx\uDCxx surrogate pair
+                    if (0xD800..0xDC00).contains(&code) {
+                        let low = match (chars.next(), chars.next()) {
+                            (Some('\\'), Some('u')) => parse_json_hex4(chars)?,
+                            _ => 0,
+                        };
+                        if !(0xDC00..0xE000).contains(&low) {
+                            return Err("unpaired surrogate in string".to_string());
+                        }
+                        code = 0x10000 + ((code - 0xD800) << 10) + (low - 0xDC00);
+                    }
+                    out.push(char::from_u32(code).ok_or_else(|| "unpaired surrogate in string".to_string())?);
+                }
+                _ => return Err("invalid escape in string".to_string()),
+            },
+            Some(c) if (c as u32) < 0x20 => return Err("control character in string".to_string()),
+            Some(c) => out.push(c),
+        }
+    }
+}
+
+/// Match `text` against a glob pattern (`*` and `?` stay within one path
+/// component, `**` matches across components)
+fn glob_match(pattern: &str, text: &str) -> bool {
+    fn is_sep(c: char) -> bool {
+        c == '/' || c == '\\'
+    }
+    fn matches(p: &[char], t: &[char]) -> bool {
+        match p.first() {
+            None => t.is_empty(),
+            Some('*') if p.get(1) == Some(&'*') => (0..=t.len()).any(|i| matches(&p[2..], &t[i..])),
+            Some('*') => {
+                for i in 0..=t.len() {
+                    if matches(&p[1..], &t[i..]) {
+                        return true;
+                    }
+                    if i < t.len() && is_sep(t[i]) {
+                        break;
+                    }
+                }
+                false
+            }
+            Some('?') => t.first().is_some_and(|c| !is_sep(*c)) && matches(&p[1..], &t[1..]),
+            Some(c) => t.first() == Some(c) && matches(&p[1..], &t[1..]),
+        }
+    }
+    let p: Vec<char> = pattern.chars().collect();
+    let t: Vec<char> = text.chars().collect();
+    matches(&p, &t)
+}
+
+/// Check whether `dir` falls under a managed/unmanaged path pattern.
+/// Plain paths match the directory and everything below it; patterns with
+/// `*` or `?` are globs matched against the directory or any of its parents.
+/// A leading `~` expands to the home directory.
+fn path_pattern_matches(pattern: &str, dir: &Path) -> bool {
+    let Some(expanded) = expand_home_prefix(pattern) else {
+        return false;
+    };
+    if expanded.contains(['*', '?']) {
+        dir.ancestors().any(|d| glob_match(&expanded, &d.to_string_lossy()))
+    } else {
+        dir.starts_with(&expanded)
+    }
+}
+
+/// Expand a leading `~` in a path pattern to the home directory
+/// (None if the home directory is unknown)
+fn expand_home_prefix(pattern: &str) -> Option<String> {
+    match pattern.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with(['/', '\\']) => {
+            get_home_dir().map(|home| format!("{}{}", home.display(), rest))
+        }
+        _ => Some(pattern.to_string()),
+    }
+}
+
+/// Problems `cs config validate` finds in config file content. Relative paths
+/// in `default_args` are checked against `base` (the config file's directory).
+fn config_problems(content: &str, base: &Path, lookup: &dyn Fn(&str) -> Option<String>) -> Vec<String> {
+    let config = match parse_config(content) {
+        Ok(config) => config,
+        Err(e) => return vec![e],
+    };
+    let mut problems = Vec::new();
+    for (key, value) in &config.values {
+        match config::expected_kind(key) {
+            None => problems.push(format!("{}: unknown key", key)),
+            Some(kind) if kind != value.kind() => {
+                problems.push(format!("{}: expected {}, found {}", key, kind, value.kind()))
+            }
+            Some(_) => {}
+        }
+    }
+
+    let config = match parse_config_with_env(content, lookup, true) {
+        Ok(config) => config,
+        Err(e) => {
+            problems.push(e);
+            return problems;
+        }
+    };
+    if config.get_str("separator") == Some("") {
+        problems.push("separator: must not be empty".to_string());
+    }
+    for key in ["backup_retention", "prune_after_days"] {
+        if config.get_int(key).is_some_and(|n| n < 0) {
+            problems.push(format!("{}: must not be negative", key));
+        }
+    }
+    if let Some(Err(e)) = config.group_by().map(GroupBy::parse) {
+        problems.push(format!("group_by: {}", e));
+    }
+    if let Some(version) = config.min_claude_version().filter(|v| parse_version(v).is_none()) {
+        problems.push(format!("min_claude_version: '{}' is not a version like 1.2.3", version));
+    }
+    if let Err(e) = check_path_args(config.default_args(), base) {
+        problems.push(format!("default_args: {}", e));
+    }
+    for key in ["managed_paths", "unmanaged_paths"] {
+        for pattern in config.get_array(key).iter().filter(|p| !p.contains(['*', '?'])) {
+            if !expand_home_prefix(pattern).is_some_and(|path| Path::new(&path).exists()) {
+                problems.push(format!("{}: {} does not exist", key, pattern));
+            }
+        }
+    }
+    problems
+}
+
+/// Run `cs config validate [path]` (default: the user config), printing "OK"
+/// or each problem, and exiting nonzero if there are any
+fn run_config_validate(path: Option<&str>) {
+    let path = path.map(PathBuf::from).unwrap_or_else(get_config_path);
+    let content = fs::read_to_string(&path).unwrap_or_else(|e| {
+        eprintln!("Error: failed to read {}: {}", path.display(), e);
+        exit(EXIT_USAGE);
+    });
+    let base = path.parent().map(Path::to_path_buf).unwrap_or_default();
+    let problems = config_problems(&content, &base, &|name| env::var(name).ok());
+    if problems.is_empty() {
+        println!("OK");
+        return;
+    }
+    for problem in &problems {
+        println!("{}: {}", path.display(), problem);
+    }
+    exit(EXIT_USAGE);
+}
+
+/// Check whether cs should manage sessions in `dir` (`managed_paths` and `unmanaged_paths` config).
+/// Unmanaged paths take precedence; an empty `managed_paths` means everywhere.
+fn is_managed_dir(config: &Config, dir: &Path) -> bool {
+    if config.unmanaged_paths().iter().any(|p| path_pattern_matches(p, dir)) {
+        return false;
+    }
+    let managed = config.managed_paths();
+    managed.is_empty() || managed.iter().any(|p| path_pattern_matches(p, dir))
+}
+
+/// Session state for `--status`: "exists", "new", or "none" (no session could be determined)
+fn session_status(identity: Option<&SessionIdentity>) -> &'static str {
+    match identity {
+        Some(identity) if load_sessions().contains(&identity.uuid) => "exists",
+        Some(_) => "new",
+        None => "none",
+    }
+}
+
+/// Check `--assert-session exists|absent` against the database.
+/// Returns an explanation when the assertion fails.
+fn check_session_assertion(expect_exists: bool, identity: &SessionIdentity) -> Result<(), String> {
+    let exists = session_status(Some(identity)) == "exists";
+    match (expect_exists, exists) {
+        (true, false) => Err(format!("session {} ({}) does not exist", identity.name, identity.uuid)),
+        (false, true) => Err(format!("session {} ({}) already exists", identity.name, identity.uuid)),
+        _ => Ok(()),
+    }
+}
+
+/// Print a compact, single-line session status for shell prompts
+fn print_status(identity: Option<&SessionIdentity>, json: bool) {
+    let status = session_status(identity);
+    let short_uuid = identity.map(|i| &i.uuid[..8.min(i.uuid.len())]).unwrap_or("");
+
+    if json {
+        let (name, uuid) = match identity {
+            Some(i) => (json_string(&i.name), json_string(&i.uuid)),
+            None => ("null".to_string(), "null".to_string()),
+        };
+        println!(
+            "{{\"status\":{},\"name\":{},\"uuid\":{}}}",
+            json_string(status),
+            name,
+            uuid
+        );
+    } else if short_uuid.is_empty() {
+        println!("{}", status);
+    } else {
+        println!("{} {}", status, short_uuid);
+    }
+}
+
+/// Print the session another directory would use, without launching
+fn which_session(dir: &str, separator: &str, base_ref: Option<&str>) -> Result<(), String> {
+    let path = fs::canonicalize(dir).map_err(|e| format!("Cannot access '{}': {}", dir, e))?;
+    if !path.is_dir() {
+        return Err(format!("'{}' is not a directory", dir));
+    }
+    let mut identity = resolve_session(&path, separator, true)?;
+    if let Some(base_ref) = base_ref {
+        identity = apply_base_ref(identity, &path, base_ref);
+    }
+    println!("Session: {}", identity.name);
+    println!("UUID:    {}", identity.uuid);
+    if !identity.is_git_repo {
+        println!("Note:    Not a git repo (folder-only mode)");
+    }
+    Ok(())
+}
+
+/// Look up a `[prompts]` template for `--prompt-template`
+fn prompt_template<'a>(config: &'a Config, name: &str) -> Result<&'a str, String> {
+    if let Some(text) = config.prompt(name) {
+        return Ok(text);
+    }
+    let names = config.prompt_names();
+    if names.is_empty() {
+        Err(format!("unknown prompt template '{}' (no templates defined under [prompts])", name))
+    } else {
+        Err(format!("unknown prompt template '{}' (defined: {})", name, names.join(", ")))
+    }
+}
+
+/// Where to record the argv of the last claude launch (`--claude-args-file`)
+static CLAUDE_ARGS_FILE: OnceLock<PathBuf> = OnceLock::new();
+
+/// Write claude arguments to `path`, one per line, replacing its contents
+fn write_claude_args(path: &Path, args: &[String]) -> Result<(), String> {
+    let mut content = args.join("\n");
+    content.push('\n');
+    fs::write(path, content).map_err(|e| format!("failed to write claude args to {}: {}", path.display(), e))
+}
+
+/// Record this launch's arguments in the `--claude-args-file`, if one was given.
+/// A failed write only warns; claude still launches.
+fn record_claude_args(args: &[String]) {
+    if let Some(path) = CLAUDE_ARGS_FILE.get() {
+        if let Err(e) = write_claude_args(path, args) {
+            eprintln!("Warning: {}", e);
+        }
+    }
+}
+
+/// File recording the exit code of the last claude cs waited for (`--last-exit`):
+/// `last-exit` in the cs directory
+fn get_last_exit_path() -> PathBuf {
+    get_cs_dir().join("last-exit")
+}
+
+/// Write an exit code to `path`
+fn write_exit_code(path: &Path, code: i32) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, format!("{}\n", code))
+}
+
+/// Read the exit code recorded in `path` (None if missing or unreadable)
+fn read_exit_code(path: &Path) -> Option<i32> {
+    fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+/// Record the exit code of a claude that cs spawned and waited for.
+/// Failures only show up in verbose output; they never change cs's exit code.
+fn record_exit_code(code: i32) {
+    if let Err(e) = write_exit_code(&get_last_exit_path(), code) {
+        verbose_log(&format!("failed to record exit code: {}", e));
+    }
+}
+
+/// Forget the recorded exit code before exec-ing claude: cs can't observe
+/// that exit, so `--last-exit` reports "unknown" rather than an older run's code
+fn forget_exit_code() {
+    let _ = fs::remove_file(get_last_exit_path());
+}
+
+/// Write just the session UUID (and a newline) to `path` for other tools (`--uuid-out`)
+fn write_uuid_file(path: &Path, uuid: &str) -> Result<(), String> {
+    fs::write(path, format!("{}\n", uuid)).map_err(|e| format!("failed to write UUID to {}: {}", path.display(), e))
+}
+
+/// Describe what cs knows about `uuid` (`--describe`), one `Label: value` line each.
+/// `current` is the session computed for the current directory, if any.
+fn describe_session(uuid: &str, records: &[SessionRecord], current: Option<&SessionIdentity>) -> Vec<String> {
+    let mut lines = vec![format!("UUID:      {}", uuid)];
+    match records.iter().find(|r| r.uuid.eq_ignore_ascii_case(uuid)) {
+        Some(record) => {
+            let timestamp = |secs: Option<u64>| secs.map_or("unknown".to_string(), format_utc);
+            lines.push("Database:  tracked".to_string());
+            lines.push(format!("Name:      {}", record.name.as_deref().unwrap_or("(unnamed)")));
+            if !record.tags.is_empty() {
+                lines.push(format!("Tags:      {}", record.tags.join(", ")));
+            }
+            lines.push(format!("Created:   {}", timestamp(record.created)));
+            lines.push(format!("Last used: {}", timestamp(record.last_used)));
+        }
+        None => lines.push("Database:  not tracked".to_string()),
+    }
+    match current {
+        Some(identity) if identity.uuid == uuid => {
+            lines.push(format!("Directory: matches this directory's session ({})", identity.name))
+        }
+        Some(identity) => {
+            lines.push(format!("Directory: this directory's session is {} ({})", identity.name, identity.uuid))
+        }
+        None => {}
+    }
+    lines
+}
+
+/// Report how long ago a session (UUID or name) was last used (`--age`)
+fn session_age(target: &str, records: &[SessionRecord], now: u64) -> Result<String, String> {
+    let uuid = resolve_session_ref(target, records);
+    let record = records
+        .iter()
+        .find(|r| r.uuid.eq_ignore_ascii_case(&uuid))
+        .ok_or_else(|| format!("session '{}' ({}) is not tracked", target, uuid))?;
+    Ok(match record.last_used {
+        Some(used) => format!("{} ({})", format_age(used, now), format_utc(used)),
+        None => "unknown".to_string(),
+    })
+}
+
+/// Get the binary name for current platform
+fn get_binary_name() -> Option<&'static str> {
+    #[cfg(all(target_os = "windows", target_arch = "x86_64"))]
+    return Some("cs-windows-x64.exe");
+    #[cfg(all(target_os = "windows", target_arch = "aarch64"))]
+    return Some("cs-windows-arm64.exe");
+
+    #[cfg(all(target_os = "macos", target_arch = "aarch64"))]
+    return Some("cs-macos-arm64");
+    #[cfg(all(target_os = "macos", target_arch = "x86_64"))]
+    return Some("cs-macos-intel");
+
+    #[cfg(all(target_os = "linux", target_arch = "x86_64", target_env = "gnu"))]
+    return Some("cs-linux-x64");
+    #[cfg(all(target_os = "linux", target_arch = "aarch64", target_env = "gnu"))]
+    return Some("cs-linux-arm64");
+    #[cfg(all(target_os = "linux", target_arch = "x86_64", target_env = "musl"))]
+    return Some("cs-linux-x64-musl");
+    #[cfg(all(target_os = "linux", target_arch = "x86", target_env = "musl"))]
+    return Some("cs-linux-i686-musl");
+
+    #[cfg(all(target_os = "freebsd", target_arch = "x86_64"))]
+    return Some("cs-freebsd-x64");
+
+    #[cfg(all(target_os = "android", target_arch = "aarch64"))]
+    return Some("cs-android-arm64");
+    #[cfg(all(target_os = "android", target_arch = "arm"))]
+    return Some("cs-android-arm32");
+    #[cfg(all(target_os = "android", target_arch = "x86_64"))]
+    return Some("cs-android-x64");
+
+    #[allow(unreachable_code)]
+    None
+}
+
+/// Describe the platform cs was built for, for updater error messages
+fn unsupported_platform_error() -> String {
+    format!(
+        "Unsupported platform for auto-update: target {} (os: {}, arch: {})",
+        env!("CS_TARGET"),
+        env::consts::OS,
+        env::consts::ARCH
+    )
+}
+
+/// Get the path to the current executable
+fn get_current_exe_path() -> Result<PathBuf, String> {
+    env::current_exe().map_err(|e| format!("Failed to get current executable path: {}", e))
+}
+
+/// Removes a temporary file when dropped (no-op if the file was already moved away)
+struct TempFileGuard(PathBuf);
+
+impl Drop for TempFileGuard {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+/// Path of the temp file a self-update downloads into (unique per process)
+fn temp_download_path(exe: &Path, pid: u32) -> PathBuf {
+    exe.with_extension(format!("new.{}", pid))
+}
+
+/// Remove partial downloads left next to the executable by interrupted self-updates
+fn remove_stale_downloads(exe: &Path) -> usize {
+    let (Some(dir), Some(stem)) = (exe.parent(), exe.file_stem().and_then(|s| s.to_str())) else {
+        return 0;
+    };
+    let prefix = format!("{}.new", stem);
+    let Ok(entries) = fs::read_dir(dir) else {
+        return 0;
+    };
+    entries
+        .map_while(Result::ok)
+        .filter(|entry| {
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            name == prefix || name.starts_with(&format!("{}.", prefix))
+        })
+        .filter(|entry| fs::remove_file(entry.path()).is_ok())
+        .count()
+}
+
+/// Perform self-update by downloading latest release from GitHub
+fn self_update() -> Result<(), String> {
+    let binary_name = get_binary_name()
+        .ok_or_else(unsupported_platform_error)?;
+
+    let download_url = format!(
+        "https://github.com/bikramtuladhar/claude-code-resumer/releases/latest/download/{}",
+        binary_name
+    );
+
+    let current_exe = get_current_exe_path()?;
+    let current_version = env!("CS_VERSION");
+
+    println!("cs self-update");
+    println!("──────────────────────────────────────────────");
+    println!("Current version: {}", current_version);
+    println!("Binary: {}", binary_name);
+    println!("Downloading from: {}", download_url);
+    println!();
+
+    // Create a per-process temp file path; the guard removes it if we bail out early.
+    // This is best-effort: a hard kill (or Ctrl-C, which terminates cs immediately)
+    // skips the guard, so leftovers from earlier runs are swept here instead.
+    remove_stale_downloads(&current_exe);
+    let temp_path = temp_download_path(&current_exe, std::process::id());
+    let _temp_guard = TempFileGuard(temp_path.clone());
+
+    // Download using platform-appropriate method, retrying network errors
+    #[cfg(windows)]
+    let download = || download_windows(&download_url, &temp_path);
+    #[cfg(not(windows))]
+    let download = || download_unix(&download_url, &temp_path);
+
+    download_with_retries(&download, update_attempts(), &std::thread::sleep)?;
+
+    // Verify the download succeeded and file exists
+    if !temp_path.exists() {
+        return Err("Download failed: file not created".to_string());
+    }
+
+    // Make executable on Unix
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mut perms = fs::metadata(&temp_path)
+            .map_err(|e| format!("Failed to get file metadata: {}", e))?
+            .permissions();
+        perms.set_mode(0o755);
+        fs::set_permissions(&temp_path, perms)
+            .map_err(|e| format!("Failed to set permissions: {}", e))?;
+    }
+
+    // Replace the current binary
+    // On Windows, we can't replace a running executable directly
+    #[cfg(windows)]
+    {
+        let backup_path = current_exe.with_extension("old");
+        // Remove old backup if exists
+        let _ = fs::remove_file(&backup_path);
+        // Rename current to backup
+        fs::rename(&current_exe, &backup_path)
+            .map_err(|e| format!("Failed to backup current binary: {}", e))?;
+        // Rename new to current
+        fs::rename(&temp_path, &current_exe)
+            .map_err(|e| format!("Failed to install new binary: {}", e))?;
+        // Remove backup
+        let _ = fs::remove_file(&backup_path);
+    }
+
+    #[cfg(not(windows))]
+    {
+        fs::rename(&temp_path, &current_exe)
+            .map_err(|e| format!("Failed to replace binary: {}", e))?;
+    }
+
+    println!("✓ Successfully updated!");
+    println!();
+    println!("Run 'cs --version' to verify the new version.");
+
+    Ok(())
+}
+
+
+/// A failed download, and whether it looks like a network hiccup worth retrying
+#[derive(Debug)]
+struct DownloadError {
+    message: String,
+    transient: bool,
+}
+
+/// Default number of `cs upgrade` download attempts (`CS_UPDATE_RETRIES`)
+const DEFAULT_UPDATE_ATTEMPTS: u32 = 3;
+
+/// Wait before the first download retry; doubles after each further failure
+const UPDATE_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// Number of download attempts for `cs upgrade` (`CS_UPDATE_RETRIES`, at least 1)
+fn update_attempts() -> u32 {
+    env::var("CS_UPDATE_RETRIES")
+        .ok()
+        .and_then(|v| v.trim().parse::<u32>().ok())
+        .unwrap_or(DEFAULT_UPDATE_ATTEMPTS)
+        .max(1)
+}
+
+/// Run `download` up to `attempts` times, backing off exponentially between
+/// attempts. Only transient (network) errors are retried.
+fn download_with_retries(
+    download: &dyn Fn() -> Result<(), DownloadError>,
+    attempts: u32,
+    sleep: &dyn Fn(Duration),
+) -> Result<(), String> {
+    let mut delay = UPDATE_RETRY_DELAY;
+    let mut attempt = 1;
+    loop {
+        match download() {
+            Ok(()) => return Ok(()),
+            Err(e) if e.transient && attempt < attempts => {
+                attempt += 1;
+                println!("{}", e.message.trim_end());
+                println!("retrying ({}/{}) in {}s...", attempt, attempts, delay.as_secs());
+                sleep(delay);
+                delay *= 2;
+            }
+            Err(e) => return Err(e.message),
+        }
+    }
+}
+
+/// Whether a failed curl run is worth retrying: connection, DNS, TLS, and
+/// timeout failures, or an HTTP 5xx response (not 404 and other client errors)
+#[cfg(not(windows))]
+fn curl_error_is_transient(exit_code: Option<i32>, stderr: &str) -> bool {
+    match exit_code {
+        // Couldn't resolve/connect, partial transfer, timeout, TLS, send/receive failures
+        Some(5 | 6 | 7 | 18 | 28 | 35 | 52 | 55 | 56) => true,
+        // HTTP error status (-f): "The requested URL returned error: 503"
+        Some(22) => stderr
+            .rsplit("error:")
+            .next()
+            .is_some_and(|status| status.trim().starts_with('5')),
+        _ => false,
+    }
+}
+
+/// Download file using curl or wget (Unix)
+#[cfg(not(windows))]
+fn download_unix(url: &str, dest: &std::path::Path) -> Result<(), DownloadError> {
+    // Try curl first
+    let curl_result = Command::new("curl")
+        .args(["-fsSL", "-o"])
+        .arg(dest)
+        .arg(url)
+        .output();
+
+    let curl_error = match curl_result {
+        Ok(output) if output.status.success() => return Ok(()),
+        Ok(output) => {
+            let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+            Some(DownloadError {
+                transient: curl_error_is_transient(output.status.code(), &stderr),
+                message: format!("Download failed: {}", stderr),
+            })
+        }
+        Err(_) => None,
+    };
+
+    // Fall back to wget
+    let wget_result = Command::new("wget")
+        .args(["-q", "-O"])
+        .arg(dest)
+        .arg(url)
+        .output();
+
+    match wget_result {
+        Ok(output) if output.status.success() => Ok(()),
+        Ok(output) => Err(DownloadError {
+            message: format!("Download failed: {}", String::from_utf8_lossy(&output.stderr)),
+            // wget exit status 4: network failure
+            transient: output.status.code() == Some(4),
+        }),
+        Err(_) => Err(curl_error.unwrap_or(DownloadError {
+            message: "Neither curl nor wget available for download".to_string(),
+            transient: false,
+        })),
+    }
+}
+
+/// Download file using PowerShell (Windows)
+#[cfg(windows)]
+fn download_windows(url: &str, dest: &std::path::Path) -> Result<(), DownloadError> {
+    let dest_str = dest.to_string_lossy();
+    let ps_command = format!(
+        "Invoke-WebRequest -Uri '{}' -OutFile '{}' -UseBasicParsing",
+        url, dest_str
+    );
+
+    let result = Command::new("powershell")
+        .args(["-NoProfile", "-Command", &ps_command])
+        .output()
+        .map_err(|e| DownloadError {
+            message: format!("Failed to run PowerShell: {}", e),
+            transient: false,
+        })?;
+
+    if result.status.success() {
+        Ok(())
+    } else {
+        let stderr = String::from_utf8_lossy(&result.stderr);
+        Err(DownloadError {
+            // HTTP client errors read "(404) Not Found"; anything else may be the network
+            transient: !stderr.contains("(4"),
+            message: format!("Download failed: {}", stderr),
+        })
+    }
+}
+
+/// All flags offered by shell completions (cs flags first, then Claude passthrough flags)
+fn completion_flags() -> Vec<&'static str> {
+    CS_FLAGS
+        .iter()
+        .chain(CLAUDE_BOOL_FLAGS)
+        .chain(CLAUDE_VALUE_FLAGS)
+        .copied()
+        .collect()
+}
+
+/// All subcommands offered by shell completions
+fn completion_subcommands() -> Vec<&'static str> {
+    CS_SUBCOMMANDS
+        .iter()
+        .chain(CLAUDE_SUBCOMMANDS)
+        .copied()
+        .collect()
+}
+
+/// Known completion values for a flag (empty if the flag's values are free-form)
+fn completion_values(flag: &str) -> &'static [&'static str] {
+    CLAUDE_FLAG_VALUES
+        .iter()
+        .find(|(name, _)| *name == flag)
+        .map(|(_, values)| *values)
+        .unwrap_or(&[])
+}
+
+/// Generate the completion script for a shell (None if the shell is unsupported)
+fn completion_script(shell: &str) -> Option<String> {
+    let flags = completion_flags().join(" ");
+    let subcommands = completion_subcommands().join(" ");
+
+    match shell {
+        "bash" => {
+            let mut value_cases = String::new();
+            for (flag, values) in CLAUDE_FLAG_VALUES {
+                value_cases.push_str(&format!(
+                    "        {}) COMPREPLY=( $(compgen -W \"{}\" -- \"$cur\") ); return ;;\n",
+                    flag,
+                    values.join(" ")
+                ));
+            }
+            Some(format!(
+                "_cs() {{\n\
+                 \x20   local cur=\"${{COMP_WORDS[COMP_CWORD]}}\"\n\
+                 \x20   local prev=\"${{COMP_WORDS[COMP_CWORD-1]}}\"\n\
+                 \x20   case \"$prev\" in\n\
+                 {value_cases}\
+                 \x20   esac\n\
+                 \x20   if [ \"$COMP_CWORD\" -eq 1 ]; then\n\
+                 \x20       COMPREPLY=( $(compgen -W \"{subcommands} {flags}\" -- \"$cur\") )\n\
+                 \x20   else\n\
+                 \x20       COMPREPLY=( $(compgen -W \"{flags}\" -- \"$cur\") )\n\
+                 \x20   fi\n\
+                 }}\n\
+                 complete -o default -F _cs cs\n"
+            ))
+        }
+        "zsh" => {
+            let mut value_cases = String::new();
+            for (flag, values) in CLAUDE_FLAG_VALUES {
+                value_cases.push_str(&format!(
+                    "        {}) compadd -- {}; return ;;\n",
+                    flag,
+                    values.join(" ")
+                ));
+            }
+            Some(format!(
+                "#compdef cs\n\
+                 _cs() {{\n\
+                 \x20   local -a cs_words\n\
+                 \x20   case \"${{words[CURRENT-1]}}\" in\n\
+                 {value_cases}\
+                 \x20   esac\n\
+                 \x20   if (( CURRENT == 2 )); then\n\
+                 \x20       cs_words=({subcommands} {flags})\n\
+                 \x20   else\n\
+                 \x20       cs_words=({flags})\n\
+                 \x20   fi\n\
+                 \x20   compadd -- $cs_words\n\
+                 \x20   _files\n\
+                 }}\n\
+                 compdef _cs cs\n"
+            ))
+        }
+        "fish" => {
+            let mut script = format!(
+                "complete -c cs -n '__fish_use_subcommand' -a '{}'\n",
+                subcommands
+            );
+            for flag in completion_flags() {
+                let values = completion_values(flag);
+                let value_args = if values.is_empty() {
+                    String::new()
+                } else {
+                    format!(" -x -a '{}'", values.join(" "))
+                };
+                if let Some(long) = flag.strip_prefix("--") {
+                    script.push_str(&format!("complete -c cs -l {}{}\n", long, value_args));
+                } else if let Some(short) = flag.strip_prefix('-') {
+                    script.push_str(&format!("complete -c cs -s {}{}\n", short, value_args));
+                }
+            }
+            Some(script)
+        }
+        _ => None,
+    }
+}
+
+/// Generate the `cs init` block for a shell rc file (None if the shell is unsupported)
+fn init_script(shell: &str) -> Option<String> {
+    let (rc_file, completions, wrapper, export) = match shell {
+        "bash" => (
+            "~/.bashrc",
+            "source <(cs --completions bash)",
+            "# cs() { command cs \"$@\"; }",
+            "# export",
+        ),
+        "zsh" => (
+            "~/.zshrc",
+            "source <(cs --completions zsh)",
+            "# cs() { command cs \"$@\"; }",
+            "# export",
+        ),
+        "fish" => (
+            "~/.config/fish/config.fish",
+            "cs --completions fish | source",
+            "# function cs; command cs $argv; end",
+            "# set -gx",
+        ),
+        _ => return None,
+    };
+
+    let mut script = String::new();
+    script.push_str("# cs - Claude Code Session Manager\n");
+    script.push_str(&format!("# Add the following to {}\n", rc_file));
+    script.push('\n');
+    script.push_str("# Shell completions\n");
+    script.push_str(completions);
+    script.push('\n');
+    script.push('\n');
+    script.push_str("# Optional: wrapper function (add your default flags)\n");
+    script.push_str(wrapper);
+    script.push('\n');
+    script.push('\n');
+    script.push_str("# Environment variables honored by cs:\n");
+    for (name, description) in CS_ENV_VARS {
+        script.push_str(&format!("#   {:<16}{}\n", name, description));
+    }
+    for (name, _) in CS_ENV_VARS {
+        if shell == "fish" {
+            script.push_str(&format!("{} {} \"...\"\n", export, name));
+        } else {
+            script.push_str(&format!("{} {}=\"...\"\n", export, name));
+        }
+    }
+
+    Some(script)
+}
+
+/// Print every environment variable cs reads with its current value
+fn print_env_vars() {
+    println!("Environment variables honored by cs:");
+    for (name, description) in CS_ENV_VARS {
+        let value = env::var(name).unwrap_or_else(|_| "(unset)".to_string());
+        println!("  {:<18}{}", name, value);
+        println!("  {:<18}{}", "", description);
+    }
+}
+
+/// Print an output block for a shell, or exit with an error for unsupported shells
+fn print_shell_output(shell: Option<&str>, generate: fn(&str) -> Option<String>) {
+    let shell = shell.unwrap_or("");
+    match generate(shell) {
+        Some(output) => print!("{}", output),
+        None => {
+            if shell.is_empty() {
+                eprintln!("Error: missing shell name");
+            } else {
+                eprintln!("Error: unsupported shell '{}'", shell);
+            }
+            eprintln!("Supported shells: {}", SUPPORTED_SHELLS.join(", "));
+            exit(EXIT_ERROR);
+        }
+    }
+}
+
+/// The `--version` line: `cs <version>`, or the bare version with `--short`
+fn version_line(short: bool) -> String {
+    if short {
+        env!("CS_VERSION").to_string()
+    } else {
+        format!("cs {}", env!("CS_VERSION"))
+    }
+}
+
+/// A titled block of `--help` rows: (usage, description). An empty usage
+/// marks a prose line; `\n` in a description continues it on the next line.
+struct HelpSection {
+    title: &'static str,
+    rows: Vec<(String, String)>,
+}
+
+/// Longest usage column kept on the same line as its description
+const HELP_USAGE_WIDTH: usize = 30;
+
+/// Build one `--help` row
+fn help_row(usage: impl Into<String>, description: impl Into<String>) -> (String, String) {
+    (usage.into(), description.into())
+}
+
+/// The `--help` sections, grouped by what the commands act on
+fn help_sections() -> Vec<HelpSection> {
+    vec![
+        HelpSection {
+            title: "USAGE",
+            rows: vec![
+                help_row("cs [options] [claude options]", "Start or resume this directory's session"),
+                help_row("cs <subcommand>", "Run a cs or claude subcommand (see SUBCOMMANDS)"),
+            ],
+        },
+        HelpSection {
+            title: "SESSIONS",
+            rows: vec![
+                help_row("cs", "Start/resume session (folder+branch or folder-only)"),
+                help_row("-f, --force", "Force create new session (ignore database)"),
+                help_row("--reset", "Remove session from database and create new"),
+                help_row("-R, --resume", "Resume using Claude's picker (fallback if not found)"),
+                help_row(
+                    "--resume-all",
+                    "Resume this repository's sessions in turn (most recent first), asking\nbefore each; q or Ctrl-C at the prompt stops the sequence",
+                ),
+                help_row("--interactive", "Pick a session to resume from a numbered list (most recent first)"),
+                help_row("--recent <n>", "Resume the nth most recently used session (1 = most recent)"),
+                help_row(
+                    "--mru [n]",
+                    format!("Print the n most recently used sessions as 'N) name' (default {})", DEFAULT_MRU_COUNT),
+                ),
+                help_row(
+                    "--resume-uuid <prefix>",
+                    format!("Resume the tracked session whose UUID starts with <prefix> (min {} chars)", MIN_UUID_PREFIX),
+                ),
+                help_row(
+                    "--watch",
+                    "Relaunch the session when claude fails; stops on a normal exit (/exit),\nCtrl-C during the pause, or 5 quick failures in a row",
+                ),
+                help_row("--confirm-new", "Ask before creating a new session (-y, --yes: don't ask)"),
+                help_row("--new-suffix", "Start a fresh parallel session (folder+branch#N, smallest free N)"),
+                help_row("--session-name <name>", "Use <name> instead of folder+branch for the session"),
+                help_row("--prompt-template <name>", "Pass the [prompts] template <name> from the config as the prompt"),
+                help_row("--no-git", "Skip git detection (folder-only session name)"),
+                help_row(
+                    "--rename-branch-aware",
+                    "With no session for this branch, offer this folder's most recent\nsession whose branch no longer exists (likely renamed)",
+                ),
+                help_row(
+                    "--since-commit <ref>",
+                    "Name the session after the merge-base with <ref> too (folder+branch@commit),\nso rebasing onto a new base starts a fresh session",
+                ),
+                help_row("--require-git", format!("Refuse to run outside a git repository (exit {})", EXIT_NOT_GIT)),
+                help_row("--launch-dir <path>", "Start claude in <path>; the session is still named from\nthe current folder/branch"),
+                help_row("--profile <name>", "Use the <name> profile's database and config (overrides CS_PROFILE)"),
+                help_row("--namespace <uuid>", "Use <uuid> as the UUID namespace (overrides CS_NAMESPACE)"),
+                help_row("--namespace-name <str>", "Derive the namespace from a team/project string"),
+            ],
+        },
+        HelpSection {
+            title: "DATABASE",
+            rows: vec![
+                help_row("-l, --list", "List all sessions in database"),
+                help_row("--list --repo [--json]", "List only this repository's sessions (--json: array of objects)"),
+                help_row(
+                    "--tag <a,b>",
+                    "Tag the current session, once tracked (with --list: only show sessions\nwith the tags)",
+                ),
+                help_row("--rm <name|uuid>...", "Remove specific sessions from the database"),
+                help_row(
+                    "--clear",
+                    "Clear this profile's session database (backs up first, asks unless -y)\n(with --dry-run: show what would be removed)",
+                ),
+                help_row("--clone <src> <dst>", "Register session <dst> (e.g. app+feature-b) next to <src>"),
+                help_row("--adopt", "Add the current directory's session to the database without launching"),
+                help_row("--remote-list [--adopt]", "List Claude's sessions not tracked by cs\n(--adopt adds all untracked sessions to the database)"),
+                help_row("--gc", "Back up, repair, prune stale sessions, and drop ones Claude no longer has\n(skip steps with --no-repair, --no-prune, --no-orphans; asks before\ndropping over half the sessions unless -y)"),
+                help_row("--touch-all", "Mark this repo's sessions as just used (so --gc won't prune them)"),
+                help_row("--repair", "Deduplicate, normalize, and sort the session database"),
+                help_row(
+                    "--dedupe-names",
+                    "Merge entries whose names differ only by an @<commit> base, keeping\nthe most recently used (asks first; -y to skip)",
+                ),
+                help_row("--import-legacy <file>", "Merge a legacy name=uuid session file into the database"),
+                help_row("--backup", "Back up the session database to ~/.cs/backups/"),
+                help_row("--open-db", "Print the database path and open its folder"),
+                help_row(
+                    "--open-claude-config [--reveal]",
+                    "Print Claude's config and session store locations\n(--reveal: also open the folder)",
+                ),
+                help_row("--append-arg \"<args>\"", "Add claude args to this project's .cs.toml default_args"),
+            ],
+        },
+        HelpSection {
+            title: "INSPECTION AND SCRIPTING",
+            rows: vec![
+                help_row("-n, --dry-run", "Show session info without launching Claude"),
+                help_row("--fail-if-new", format!("With --dry-run: exit {} if the session would be created", EXIT_NEW_SESSION)),
+                help_row("--explain-uuid", "With --dry-run: show the namespace, name, and SHA-1 behind the UUID"),
+                help_row("--print-argv", "Print the arguments cs would pass to claude, one per line"),
+                help_row(
+                    "--copy-cmd [--clipboard]",
+                    "Print the claude command as one shell-quoted line\n(--clipboard: also copy it to the clipboard)",
+                ),
+                help_row("--uuid-out <path>", "Write the session UUID to <path> (then launch, unless --dry-run)"),
+                help_row("--claude-args-file <path>", "Write the claude arguments of this launch to <path>, one per line"),
+                help_row("--print-name [--json]", "Print the computed session name (after --session-name, suffix, --since-commit)"),
+                help_row("--status [--json]", "Print 'exists|new|none <short-uuid>' for shell prompts"),
+                help_row("--git-info", format!("Print 'git|folder <name>'; exit 0 in a git repo, {} otherwise", EXIT_NOT_GIT)),
+                help_row(
+                    "--assert-session <state>",
+                    format!("Exit 0 if the session 'exists' (or is 'absent'), {} otherwise", EXIT_ERROR),
+                ),
+                help_row("--which-session <dir>", "Show the session name and UUID for another directory"),
+                help_row("--batch <file|-> [--json]", "Print 'name<TAB>uuid' for each session name in a file"),
+                help_row(
+                    "--stdin-json",
+                    "Read {\"dir\", \"mode\": resume|create|dry, \"namespace\", \"extra_args\"}\nfrom stdin, print the result as JSON, then launch (no box)",
+                ),
+                help_row("--describe <uuid>", "Show the stored name, tags, and timestamps for a session UUID"),
+                help_row("--age <uuid|name>", "Print how long ago a session was last used ('3 days ago')"),
+                help_row("--json-schema-out", "Print JSON Schemas for every --json output"),
+                help_row("--loose", "Forward unknown --key=value arguments to claude instead of rejecting them"),
+                help_row(
+                    "--check-paths",
+                    format!("Check that {} values exist before launching", CLAUDE_PATH_FLAGS.join("/")),
+                ),
+                help_row(
+                    "--no-positional",
+                    format!("Reject bare arguments (prompts) instead of passing them to claude (exit {})", EXIT_USAGE),
+                ),
+                help_row("--doctor-claude", "Run 'claude doctor' and report whether this session exists in cs and Claude"),
+                help_row(
+                    "--last-exit",
+                    "Print the exit code of the last claude cs waited for (spawn mode:\nWindows, --watch, --resume-all), or 'unknown'",
+                ),
+                help_row("--self-test", "Check UUID generation, database writes, and launching claude; pass/FAIL each"),
+                help_row("--env", "List environment variables cs reads and their values"),
+            ],
+        },
+        HelpSection {
+            title: "OPTIONS",
+            rows: vec![
+                help_row("--ascii", "Draw the info box with plain ASCII characters"),
+                help_row("--color <when>", "Color output: always, auto (terminal only; the default), or never"),
+                help_row("--skip-version-check", "Launch even if claude is older than min_claude_version"),
+                help_row("--timeout <secs>", format!("Stop claude after <secs> (Windows or --watch; exit {})", EXIT_TIMEOUT)),
+                help_row("--completions <shell>", "Print shell completion script"),
+                help_row("-h, --help [--full]", "Show this help message (--full: every recognized claude flag)"),
+                help_row("-v, --version [--short]", "Show version (--short: just the number, for scripts)"),
+                help_row("-fn, -nRy, ...", "Short flags -f -h -l -n -R -v -y can be combined"),
+            ],
+        },
+        HelpSection {
+            title: "SUBCOMMANDS",
+            rows: vec![
+                help_row("stats [--json]", "Show session counts, recent use, and repository totals"),
+                help_row("config validate [path]", "Check a config file (default: the user config) and list problems"),
+                help_row("init <shell>", "Print shell integration for your rc file (bash, zsh, fish)"),
+                help_row("trust", "Use this project's .cs.toml default_args (re-run after it changes)"),
+                help_row("upgrade, -U", "Update cs to the latest version"),
+                help_row(CLAUDE_SUBCOMMANDS.join(", "), "Passed straight to claude (no session)"),
+            ],
+        },
+        HelpSection {
+            title: "CLAUDE CODE OPTIONS",
+            rows: vec![
+                help_row("", format!(
+                    "All Claude Code CLI options are passed through ({} recognized, e.g.\n--chrome, --model <m>, --verbose, -c, -p).",
+                    CLAUDE_BOOL_FLAGS.len() + CLAUDE_VALUE_FLAGS.len()
+                )),
+                help_row("", format!(
+                    "Comma-separated values expand into repeated flags for: {}\n(e.g. --add-dir a,b -> --add-dir a --add-dir b)",
+                    CLAUDE_REPEATABLE_FLAGS.join(", ")
+                )),
+                help_row("", "--verbose is also used by cs: it logs resolved paths, git detection,\nand the final claude command to stderr."),
+            ],
+        },
+        HelpSection {
+            title: "EXAMPLES",
+            rows: vec![
+                help_row("cs --chrome", "Enable Chrome integration"),
+                help_row("cs --model opus", "Use opus model"),
+                help_row("cs -f --verbose", "Force new session + verbose mode"),
+                help_row("cs doctor", "Run claude doctor (bypass session)"),
+            ],
+        },
+        HelpSection {
+            title: "SESSION FORMAT",
+            rows: vec![
+                help_row("Git repo:", "<folder><separator><branch> -> deterministic UUID v5 (default separator: +)"),
+                help_row("Non-git:", "<folder> -> deterministic UUID v5 (folder-only)"),
+                help_row("Example:", "my-project+feature/auth -> 4b513bfa-8c71-512b-..."),
+                help_row("Example:", "my-folder -> a1b2c3d4-e5f6-5789-..."),
+            ],
+        },
+        HelpSection {
+            title: "EXIT CODES",
+            rows: vec![
+                help_row("0", "Success"),
+                help_row(EXIT_ERROR.to_string(), "Error"),
+                help_row(EXIT_NEW_SESSION.to_string(), "No existing session (--dry-run --fail-if-new)"),
+                help_row(
+                    EXIT_CLAUDE_TOO_OLD.to_string(),
+                    "Claude older than min_claude_version (skip with --skip-version-check)",
+                ),
+                help_row(EXIT_NOT_GIT.to_string(), "Not a git repository (--git-info, --require-git)"),
+                help_row(
+                    EXIT_USAGE.to_string(),
+                    "Invalid config file (CS_STRICT_CONFIG=1), missing file (--check-paths),\nor bare argument (--no-positional)",
+                ),
+                help_row(EXIT_CANT_CREATE.to_string(), "Could not write the --uuid-out file"),
+                help_row(EXIT_TIMEOUT.to_string(), "Claude exceeded --timeout (Windows, --watch)"),
+                help_row(EXIT_CLAUDE_NOT_FOUND.to_string(), "Claude CLI not found"),
+            ],
+        },
+        HelpSection {
+            title: "TROUBLESHOOTING",
+            rows: vec![
+                help_row("", "If you see \"No conversation found\" error:"),
+                help_row("cs --resume", "Use Claude's picker to find/select session"),
+                help_row("cs --reset", "Clears stale entry and creates fresh session"),
+            ],
+        },
+        HelpSection {
+            title: "ENVIRONMENT VARIABLES",
+            rows: CS_ENV_VARS
+                .iter()
+                .map(|(name, description)| help_row(*name, *description))
+                .chain([help_row("", "Run 'cs --env' to see their current values")])
+                .collect(),
+        },
+        HelpSection {
+            title: "FILES",
+            rows: vec![
+                help_row("~/.cs/sessions", "Session database (one session per line)\n(Windows: %USERPROFILE%\\.cs\\sessions)"),
+                help_row("~/.cs/config.toml", "Configuration file (or CS_CONFIG_PATH)"),
+                help_row("~/.cs/backups/", "Timestamped database backups"),
+                help_row("~/.cs/last-exit", "Exit code of the last claude cs waited for (--last-exit)"),
+                help_row("~/.cs/profiles/<name>/", "A profile's database, config, and backups"),
+                help_row("<project>/.cs.toml", "Project config (default_args), at the git root"),
+                help_row("~/.cs/trusted", "Project configs allowed by cs trust (path and content hash)"),
+            ],
+        },
+        HelpSection {
+            title: "CONFIGURATION",
+            rows: vec![
+                help_row("separator = \"+\"", "Delimiter between folder and branch in session names\nChanging it changes every computed session UUID"),
+                help_row("backup_retention = 10", "Number of database backups to keep"),
+                help_row("prune_after_days = 90", "Days unused before --gc prunes a session"),
+                help_row("group_by = \"folder\"", "How --list groups sessions: folder, branch, or tag"),
+                help_row("require_git = true", "Same as always passing --require-git"),
+                help_row(
+                    "case_insensitive = true",
+                    "Lowercase and NFC-normalize folder names before hashing (MyProject = myproject)\nChanging it changes the UUID of every folder with capitals",
+                ),
+                help_row("base_ref = \"main\"", "Same as always passing --since-commit main"),
+                help_row("confirm_new = true", "Same as always passing --confirm-new"),
+                help_row(
+                    "missing_session_stderr = \"No conversation found\"",
+                    "How claude reports a missing conversation; where cs waits for claude\n(Windows, --watch, --resume-all) it then starts the session afresh",
+                ),
+                help_row(
+                    "recover_missing_sessions = true",
+                    "Start a session afresh when Claude's store no longer has it\n(instead of letting claude -r fail)",
+                ),
+                help_row("min_claude_version = \"1.2.0\"", "Refuse to launch an older claude"),
+                help_row(
+                    "claude_launcher = [\"npx\", \"@anthropic-ai/claude-code\"]",
+                    "Run claude through this command instead of the claude binary",
+                ),
+                help_row(
+                    "default_args = [\"--model\", \"opus\"]",
+                    "Claude args added to every launch\nPrecedence: command line > CS_CLAUDE_ARGS > project .cs.toml > ~/.cs/config.toml",
+                ),
+            ],
+        },
+    ]
+}
+
+/// Render help sections with aligned columns, colored when `color` is set
+fn render_help(sections: &[HelpSection], color: bool) -> String {
+    let paint = |code: &str, text: &str| {
+        if color {
+            format!("\x1b[{}m{}\x1b[0m", code, text)
+        } else {
+            text.to_string()
+        }
+    };
+
+    let mut out = format!("{}\n\n", paint("1", "cs - Claude Code Session Manager"));
+    for section in sections {
+        out.push_str(&paint("1;33", &format!("{}:", section.title)));
+        out.push('\n');
+        let width = section
+            .rows
+            .iter()
+            .map(|(usage, _)| usage.chars().count())
+            .filter(|&len| len <= HELP_USAGE_WIDTH)
+            .max()
+            .unwrap_or(0);
+        for (usage, description) in &section.rows {
+            let mut lines = description.lines();
+            let first = lines.next().unwrap_or_default();
+            let len = usage.chars().count();
+            let indent = if usage.is_empty() { 4 } else { 4 + width + 2 };
+            if usage.is_empty() {
+                out.push_str(&format!("    {}\n", first));
+            } else if len > width {
+                out.push_str(&format!("    {}\n{:indent$}{}\n", paint("36", usage), "", first));
+            } else {
+                out.push_str(&format!("    {}{:pad$}{}\n", paint("36", usage), "", first, pad = width - len + 2));
+            }
+            for line in lines {
+                out.push_str(&format!("{:indent$}{}\n", "", line));
+            }
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// When to emit ANSI color (`--color always|auto|never`)
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ColorChoice {
+    Always,
+    Auto,
+    Never,
+}
+
+impl ColorChoice {
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "always" => Some(ColorChoice::Always),
+            "auto" => Some(ColorChoice::Auto),
+            "never" => Some(ColorChoice::Never),
+            _ => None,
+        }
+    }
+}
+
+/// Color choice for this invocation from `--color` (default `auto`)
+static COLOR_CHOICE: OnceLock<ColorChoice> = OnceLock::new();
+
+/// Find `--color <when>` or `--color=<when>` in the arguments
+fn color_from_args(args: &[String]) -> Result<Option<ColorChoice>, String> {
+    let args = cs_args(args);
+    let value = args.iter().enumerate().find_map(|(i, a)| match a.strip_prefix("--color") {
+        Some("") => Some(args.get(i + 1).copied()),
+        Some(rest) => rest.strip_prefix('=').map(Some),
+        None => None,
+    });
+    match value {
+        Some(value) => value
+            .and_then(ColorChoice::parse)
+            .map(Some)
+            .ok_or_else(|| "'--color' requires always, auto, or never".to_string()),
+        None => Ok(None),
+    }
+}
+
+/// Decide whether to color output. `always` wins over everything, including
+/// NO_COLOR; `never` disables color; `auto` colors a terminal unless NO_COLOR
+/// is set or TERM is `dumb`.
+fn should_color(choice: ColorChoice, is_terminal: bool, no_color: bool, dumb_term: bool) -> bool {
+    match choice {
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+        ColorChoice::Auto => is_terminal && !no_color && !dumb_term,
+    }
+}
+
+/// Whether cs's stderr output (e.g. `--help`) should use color; every place
+/// that emits color asks this
+fn color_enabled() -> bool {
+    use std::io::IsTerminal;
+    should_color(
+        COLOR_CHOICE.get().copied().unwrap_or(ColorChoice::Auto),
+        std::io::stderr().is_terminal(),
+        env::var_os("NO_COLOR").is_some(),
+        env::var("TERM").is_ok_and(|t| t == "dumb"),
+    )
+}
+
+/// Pair short flags with the long flag that follows them ("-c", "--continue" -> "-c, --continue")
+fn pair_short_flags(flags: &[&str]) -> Vec<String> {
+    let mut paired = Vec::new();
+    let mut i = 0;
+    while i < flags.len() {
+        match flags.get(i + 1) {
+            Some(long) if !flags[i].starts_with("--") && long.starts_with("--") => {
+                paired.push(format!("{}, {}", flags[i], long));
+                i += 2;
+            }
+            _ => {
+                paired.push(flags[i].to_string());
+                i += 1;
+            }
+        }
+    }
+    paired
+}
+
+/// Describe how cs treats a Claude value flag
+fn claude_value_flag_note(flag: &str) -> String {
+    let mut notes = vec!["takes a value".to_string()];
+    if CLAUDE_PATH_FLAGS.contains(&flag) {
+        notes.push("file path (checked by --check-paths)".to_string());
+    }
+    if CLAUDE_REPEATABLE_FLAGS.contains(&flag) {
+        notes.push("repeatable; a,b expands to two flags".to_string());
+    }
+    let values = completion_values(flag);
+    if !values.is_empty() {
+        notes.push(format!("one of: {}", values.join(", ")));
+    }
+    notes.join("; ")
+}
+
+/// `--help --full`: the curated help plus every passthrough flag and
+/// subcommand cs recognizes, generated from the same lists as completions
+fn full_help_sections() -> Vec<HelpSection> {
+    let long_of = |entry: &str| entry.rsplit(", ").next().unwrap_or(entry).to_string();
+    let mut sections = help_sections();
+    sections.push(HelpSection {
+        title: "CLAUDE FLAGS",
+        rows: pair_short_flags(CLAUDE_BOOL_FLAGS)
+            .into_iter()
+            .map(|entry| help_row(entry, "switch"))
+            .chain(pair_short_flags(CLAUDE_VALUE_FLAGS).into_iter().map(|entry| {
+                let note = claude_value_flag_note(&long_of(&entry));
+                help_row(entry, note)
+            }))
+            .collect(),
+    });
+    sections.push(HelpSection {
+        title: "ALL SUBCOMMANDS",
+        rows: completion_subcommands()
+            .into_iter()
+            .map(|name| {
+                let owner = if CS_SUBCOMMANDS.contains(&name) { "cs" } else { "claude (no session)" };
+                help_row(name, owner)
+            })
+            .collect(),
+    });
+    sections
+}
+
+fn print_help(full: bool) {
+    let sections = if full { full_help_sections() } else { help_sections() };
+    eprint!("{}", render_help(&sections, color_enabled()));
+}
+
+fn main() {
+    let mut args: Vec<String> = env::args().collect();
+    match profile_from_args(&args) {
+        Ok(Some(profile)) => {
+            let _ = PROFILE_OVERRIDE.set(profile);
+        }
+        // --profile wins, so CS_PROFILE only matters without it
+        Ok(None) => {
+            if let Err(e) = validate_profile_env() {
+                eprintln!("Error: {}", e);
+                exit(EXIT_USAGE);
+            }
+        }
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            exit(EXIT_ERROR);
+        }
+    }
+    match color_from_args(&args) {
+        Ok(Some(choice)) => {
+            let _ = COLOR_CHOICE.set(choice);
+        }
+        Ok(None) => {}
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            exit(EXIT_ERROR);
+        }
+    }
+    // Read first, so no code path can write to the database during a dry run
+    let mut dry_run = dry_run_from_args(&args);
+    DRY_RUN.store(dry_run, Ordering::Relaxed);
+    // Validate before loading, so a broken config is reported rather than replaced
+    if args.get(1).map(String::as_str) == Some("config") {
+        match args.get(2).map(String::as_str) {
+            Some("validate") => run_config_validate(args.get(3).map(String::as_str)),
+            _ => {
+                eprintln!("Error: unknown config command; try 'cs config validate [path]'");
+                exit(EXIT_ERROR);
+            }
+        }
+        return;
+    }
+    let config: Config = load_user_config(&get_config_path()).unwrap_or_else(|e| {
+        eprintln!("Error: {}", e);
+        exit(EXIT_USAGE);
+    });
+    let launcher = match env::var("CS_CLAUDE_LAUNCHER") {
+        Ok(value) if !value.trim().is_empty() => split_args(&value).unwrap_or_else(|e| {
+            eprintln!("Error: CS_CLAUDE_LAUNCHER: {}", e);
+            exit(EXIT_USAGE);
+        }),
+        _ => config.claude_launcher().to_vec(),
+    };
+    let _ = CLAUDE_LAUNCHER.set(launcher);
+    CASE_INSENSITIVE.store(config.case_insensitive(), Ordering::Relaxed);
+
+    // Track mode flags
+    let mut force_create = false;
+    let mut reset_mode = false;
+    let mut resume_mode = false;
+    let mut fail_if_new = false;
+    let mut clear = false;
+    let mut remote_list = false;
+    let mut adopt = false;
+    let mut ascii = false;
+    let mut explain = false;
+    let mut timeout: Option<Duration> = None;
+    let mut status_mode = false;
+    let mut git_info = false;
+    let mut print_name = false;
+    let mut watch = false;
+    let mut assert_session: Option<bool> = None;
+    let mut json_output = false;
+    let mut no_git = false;
+    let mut print_argv = false;
+    let mut copy_cmd = false;
+    let mut clipboard = false;
+    let mut reveal = false;
+    let mut new_suffix = false;
+    let mut check_paths = config.check_paths();
+    let mut base_ref = config.base_ref().map(str::to_string);
+    let mut confirm_new = config.confirm_new();
+    let mut require_git = config.require_git();
+    let mut rename_branch_aware = config.rename_branch_aware();
+    let mut uuid_out: Option<PathBuf> = None;
+    let mut allow_positional = config.allow_positional();
+    let mut positionals: Vec<String> = Vec::new();
+    let mut skip_version_check = false;
+    let mut recent: Option<usize> = None;
+    let mut uuid_prefix: Option<String> = None;
+    let mut name_override: Option<String> = None;
+    let mut launch_dir: Option<PathBuf> = None;
+    let mut doctor = false;
+    let mut interactive = false;
+    let mut gc = false;
+    let mut gc_options = GcOptions { repair: true, prune: true, orphans: true };
+    let mut list_mode = false;
+    let mut resume_all_mode = false;
+    let mut touch_all = false;
+    let mut rm_targets: Vec<String> = Vec::new();
+    let mut repo_only = false;
+    let mut stdin_json = false;
+    let mut tags: Vec<String> = Vec::new();
+    let mut passthrough_args: Vec<String> = Vec::new();
+    let loose_args = config.loose_args() || cs_args(&args).contains(&"--loose");
+
+    // Shell integration helpers
+    if args.len() > 1 && args[1] == "init" {
+        print_shell_output(args.get(2).map(String::as_str), init_script);
+        return;
+    }
+    if args.len() > 1 && args[1] == "trust" {
+        run_trust();
+        return;
+    }
+    if args.len() > 1 && args[1] == "stats" {
+        let json = args[2..].iter().any(|a| a == "--json");
+        print_stats(&session_stats(&load_records(), unix_now(), config.separator()), json);
+        return;
+    }
+
+    // Check for Claude subcommands first - pass entire command through (bypass session logic)
+    if args.len() > 1 && CLAUDE_SUBCOMMANDS.contains(&args[1].as_str()) {
+        let claude_args: Vec<String> = args[1..].to_vec();
+        launch_claude_owned(claude_args, None);
+    }
+
+    // Outside managed directories, forward everything to claude untouched
+    // (unless a cs flag or subcommand asks for cs explicitly)
+    let uses_cs = cs_args(&args).into_iter().any(|a| {
+        let flag = a.split('=').next().unwrap_or(a);
+        CS_FLAGS.contains(&flag) || CS_SUBCOMMANDS.contains(&flag) || (is_short_cluster(a) && expand_short_cluster(a).is_ok())
+    });
+    if !uses_cs && env::current_dir().is_ok_and(|cwd| !is_managed_dir(&config, &cwd)) {
+        launch_claude_owned(args[1..].to_vec(), None);
+    }
+
+    match namespace_from_args(&args) {
+        Ok(Some(namespace)) => {
+            let _ = NAMESPACE_OVERRIDE.set(namespace);
+        }
+        Ok(None) => {}
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            exit(EXIT_ERROR);
+        }
+    }
+
+    // Parse arguments with index-based loop to handle value flags
+    let mut i = 1;
+    while i < args.len() {
+        let arg = &args[i];
+
+        match arg.as_str() {
+            // cs-specific flags (early exit)
+            "--help" | "-h" => {
+                print_help(args.iter().any(|a| a == "--full"));
+                return;
+            }
+            "--version" | "-v" => {
+                println!("{}", version_line(args.iter().any(|a| a == "--short")));
+                return;
+            }
+            "--list" | "-l" => {
+                list_mode = true;
+            }
+            "--repo" => {
+                repo_only = true;
+            }
+            "--clear" => {
+                clear = true;
+            }
+            "--env" => {
+                print_env_vars();
+                return;
+            }
+            "--batch" => {
+                let Some(source) = args.get(i + 1) else {
+                    eprintln!("Error: '--batch' requires a file of names (or '-' for stdin)");
+                    exit(EXIT_ERROR);
+                };
+                let content = if source == "-" {
+                    std::io::read_to_string(std::io::stdin())
+                } else {
+                    fs::read_to_string(source)
+                };
+                match content {
+                    Ok(content) => print_batch(&batch_uuids(&content, &get_namespace()), args.iter().any(|a| a == "--json")),
+                    Err(e) => {
+                        eprintln!("Error: failed to read {}: {}", source, e);
+                        exit(EXIT_ERROR);
+                    }
+                }
+                return;
+            }
+            "--stdin-json" => {
+                stdin_json = true;
+            }
+            "--which-session" => {
+                let Some(dir) = args.get(i + 1) else {
+                    eprintln!("Error: '--which-session' requires a directory");
+                    exit(EXIT_ERROR);
+                };
+                if let Err(e) = which_session(dir, config.separator(), config.base_ref()) {
+                    eprintln!("Error: {}", e);
+                    exit(EXIT_ERROR);
+                }
+                return;
+            }
+            "--self-test" => run_self_test(),
+            "--mru" => {
+                let count = match args.get(i + 1).filter(|v| v.chars().all(|c| c.is_ascii_digit())) {
+                    Some(value) => match value.parse::<usize>() {
+                        Ok(n) if n > 0 => n,
+                        _ => {
+                            eprintln!("Error: '--mru' takes a positive number of sessions");
+                            exit(EXIT_ERROR);
+                        }
+                    },
+                    None => DEFAULT_MRU_COUNT,
+                };
+                for line in mru_lines(load_records(), count) {
+                    println!("{}", line);
+                }
+                return;
+            }
+            "--last-exit" => {
+                match read_exit_code(&get_last_exit_path()) {
+                    Some(code) => println!("{}", code),
+                    None => println!("unknown"),
+                }
+                return;
+            }
+            "--open-db" => {
+                reveal_path(&get_db_path());
+                return;
+            }
+            "--open-claude-config" => {
+                reveal_claude_config(cs_args(&args).contains(&"--reveal"));
+                return;
+            }
+            "--clone" => {
+                match (args.get(i + 1), args.get(i + 2)) {
+                    (Some(src), Some(dst)) => clone_session(src, dst),
+                    _ => {
+                        eprintln!("Error: '--clone' requires <src-name> <dst-name>");
+                        exit(EXIT_ERROR);
+                    }
+                }
+                return;
+            }
+            "--repair" => {
+                run_repair();
+                return;
+            }
+            "--describe" => {
+                let Some(bytes) = args.get(i + 1).and_then(|uuid| parse_uuid_strict(uuid)) else {
+                    eprintln!("Error: '--describe' requires a session UUID");
+                    exit(EXIT_ERROR);
+                };
+                let current = env::current_dir().ok().and_then(|cwd| {
+                    let identity = resolve_session(&cwd, config.separator(), true).ok()?;
+                    Some(match config.base_ref() {
+                        Some(base_ref) => apply_base_ref(identity, &cwd, base_ref),
+                        None => identity,
+                    })
+                });
+                for line in describe_session(&format_uuid(&bytes), &load_records(), current.as_ref()) {
+                    println!("{}", line);
+                }
+                return;
+            }
+            "--age" => {
+                let Some(target) = args.get(i + 1) else {
+                    eprintln!("Error: '--age' requires a session UUID or name");
+                    exit(EXIT_ERROR);
+                };
+                match session_age(target, &load_records(), unix_now()) {
+                    Ok(age) => println!("{}", age),
+                    Err(e) => {
+                        eprintln!("Error: {}", e);
+                        exit(EXIT_ERROR);
+                    }
+                }
+                return;
+            }
+            "--json-schema-out" => {
+                print_json_schemas();
+                return;
+            }
+            "--dedupe-names" => {
+                run_dedupe_names(args.iter().any(|a| a == "-y" || a == "--yes"));
+                return;
+            }
+            "--import-legacy" => {
+                let Some(path) = args.get(i + 1) else {
+                    eprintln!("Error: '--import-legacy' requires a file of name=uuid lines");
+                    exit(EXIT_ERROR);
+                };
+                run_import_legacy(Path::new(path));
+                return;
+            }
+            "--append-arg" => {
+                let result = match (args.get(i + 1), env::current_dir()) {
+                    (Some(value), Ok(cwd)) => append_project_arg(&cwd, value),
+                    (None, _) => Err("'--append-arg' requires a value, e.g. \"--mcp-config ./mcp.json\"".to_string()),
+                    (_, Err(_)) => Err("Failed to get current directory".to_string()),
+                };
+                match result {
+                    Ok(path) if dry_run => println!("Would add '{}' to default_args in {}", args[i + 1], path.display()),
+                    Ok(path) => println!("Added '{}' to default_args in {}", args[i + 1], path.display()),
+                    Err(e) => {
+                        eprintln!("Error: {}", e);
+                        exit(EXIT_ERROR);
+                    }
+                }
+                return;
+            }
+            "--backup" => {
+                run_backup(config.backup_retention());
+                return;
+            }
+            "--rm" => {
+                let targets: Vec<String> = args[i + 1..].iter().take_while(|a| !a.starts_with('-')).cloned().collect();
+                if targets.is_empty() {
+                    eprintln!("Error: '--rm' requires at least one session name or UUID");
+                    exit(EXIT_ERROR);
+                }
+                i += targets.len();
+                rm_targets.extend(targets);
+            }
+            "--completions" => {
+                print_shell_output(args.get(i + 1).map(String::as_str), completion_script);
+                return;
+            }
+            "upgrade" | "-U" => {
+                match self_update() {
+                    Ok(_) => return,
+                    Err(e) => {
+                        eprintln!("Error: {}", e);
+                        exit(EXIT_ERROR);
+                    }
+                }
+            }
+
+            // cs-specific mode flags
+            // Read before parsing, by dry_run_from_args
+            "--dry-run" | "-n" => {}
+            "--force" | "-f" => {
+                force_create = true;
+            }
+            "--reset" => {
+                reset_mode = true;
+            }
+            "--resume" | "-R" => {
+                resume_mode = true;
+            }
+            "--fail-if-new" => {
+                fail_if_new = true;
+            }
+            "--remote-list" => {
+                remote_list = true;
+            }
+            "--adopt" => {
+                adopt = true;
+            }
+            "--ascii" => {
+                ascii = true;
+            }
+            "--explain-uuid" => {
+                explain = true;
+            }
+            "--status" => {
+                status_mode = true;
+            }
+            "--git-info" => {
+                git_info = true;
+            }
+            "--print-name" => {
+                print_name = true;
+            }
+            "--watch" => {
+                watch = true;
+            }
+            "--assert-session" => {
+                i += 1;
+                match args.get(i).map(String::as_str) {
+                    Some("exists") => assert_session = Some(true),
+                    Some("absent") => assert_session = Some(false),
+                    _ => {
+                        eprintln!("Error: '--assert-session' requires 'exists' or 'absent'");
+                        exit(EXIT_ERROR);
+                    }
+                }
+            }
+            // Read before parsing, by namespace_from_args, profile_from_args, and color_from_args
+            "--namespace" | "--namespace-name" | "--profile" | "--color" => {
+                i += 1;
+            }
+            // Read before parsing, by color_from_args
+            _ if arg.starts_with("--color=") => {}
+            "--gc" => {
+                gc = true;
+            }
+            "--no-repair" => {
+                gc_options.repair = false;
+            }
+            "--no-prune" => {
+                gc_options.prune = false;
+            }
+            "--no-orphans" => {
+                gc_options.orphans = false;
+            }
+            "--interactive" => {
+                interactive = true;
+            }
+            "--json" => {
+                json_output = true;
+            }
+            // Only changes --help output
+            "--full" => {}
+            // Only changes --version output
+            "--short" => {}
+            // Read before parsing, by loose_args above
+            "--loose" => {}
+            "--no-git" => {
+                no_git = true;
+            }
+            "--require-git" => {
+                require_git = true;
+            }
+            "--no-positional" => {
+                allow_positional = false;
+            }
+            "--rename-branch-aware" => {
+                rename_branch_aware = true;
+            }
+            "--resume-all" => {
+                resume_all_mode = true;
+            }
+            "--touch-all" => {
+                touch_all = true;
+            }
+            "--claude-args-file" => {
+                i += 1;
+                match args.get(i) {
+                    Some(path) => {
+                        let _ = CLAUDE_ARGS_FILE.set(PathBuf::from(path));
+                    }
+                    None => {
+                        eprintln!("Error: '--claude-args-file' requires a file path");
+                        exit(EXIT_ERROR);
+                    }
+                }
+            }
+            "--uuid-out" => {
+                i += 1;
+                match args.get(i) {
+                    Some(path) => uuid_out = Some(PathBuf::from(path)),
+                    None => {
+                        eprintln!("Error: '--uuid-out' requires a file path");
+                        exit(EXIT_ERROR);
+                    }
+                }
+            }
+            "--prompt-template" => {
+                i += 1;
+                match args.get(i).map(|name| prompt_template(&config, name)) {
+                    Some(Ok(text)) => passthrough_args.push(text.to_string()),
+                    Some(Err(e)) => {
+                        eprintln!("Error: {}", e);
+                        exit(EXIT_ERROR);
+                    }
+                    None => {
+                        eprintln!("Error: '--prompt-template' requires a template name");
+                        exit(EXIT_ERROR);
+                    }
+                }
+            }
+            "--print-argv" => {
+                print_argv = true;
+            }
+            "--copy-cmd" => {
+                copy_cmd = true;
+            }
+            "--clipboard" => {
+                clipboard = true;
+            }
+            "--reveal" => {
+                reveal = true;
+            }
+            "--doctor-claude" => {
+                doctor = true;
+            }
+            "--new-suffix" => {
+                new_suffix = true;
+            }
+            "--confirm-new" => {
+                confirm_new = true;
+            }
+            "-y" | "--yes" => {
+                confirm_new = false;
+            }
+            "--check-paths" => {
+                check_paths = true;
+            }
+            "--skip-version-check" => {
+                skip_version_check = true;
+            }
+            "--session-name" => {
+                i += 1;
+                match args.get(i).map(|v| v.trim()).filter(|v| !v.is_empty()) {
+                    Some(name) => name_override = Some(name.to_string()),
+                    None => {
+                        eprintln!("Error: '--session-name' requires a name");
+                        exit(EXIT_ERROR);
+                    }
+                }
+            }
+            "--launch-dir" => {
+                i += 1;
+                match args.get(i).map(|v| resolve_launch_dir(v)) {
+                    Some(Ok(dir)) => launch_dir = Some(dir),
+                    Some(Err(e)) => {
+                        eprintln!("Error: {}", e);
+                        exit(EXIT_ERROR);
+                    }
+                    None => {
+                        eprintln!("Error: '--launch-dir' requires a path");
+                        exit(EXIT_ERROR);
+                    }
+                }
+            }
+            "--since-commit" => {
+                i += 1;
+                match args.get(i).filter(|v| !v.is_empty()) {
+                    Some(reference) => base_ref = Some(reference.clone()),
+                    None => {
+                        eprintln!("Error: '--since-commit' requires a base ref, e.g. main");
+                        exit(EXIT_ERROR);
+                    }
+                }
+            }
+            "--resume-uuid" => {
+                i += 1;
+                match args.get(i) {
+                    Some(prefix) => uuid_prefix = Some(prefix.clone()),
+                    None => {
+                        eprintln!("Error: '--resume-uuid' requires a UUID or UUID prefix");
+                        exit(EXIT_ERROR);
+                    }
+                }
+            }
+            "--recent" => {
+                i += 1;
+                match args.get(i).and_then(|v| v.parse::<usize>().ok()) {
+                    Some(n) if n > 0 => recent = Some(n),
+                    _ => {
+                        eprintln!("Error: '--recent' requires a positive number (1 = most recent)");
+                        exit(EXIT_ERROR);
+                    }
+                }
+            }
+            "--tag" => {
+                i += 1;
+                match validate_tags(args.get(i).map(String::as_str).unwrap_or("")) {
+                    Ok(parsed) => tags.extend(parsed),
+                    Err(e) => {
+                        eprintln!("Error: {}", e);
+                        exit(EXIT_ERROR);
+                    }
+                }
+            }
+            "--timeout" => {
+                i += 1;
+                match args.get(i).and_then(|v| v.parse::<u64>().ok()) {
+                    Some(secs) if secs > 0 => timeout = Some(Duration::from_secs(secs)),
+                    _ => {
+                        eprintln!("Error: '--timeout' requires a positive number of seconds");
+                        exit(EXIT_ERROR);
+                    }
+                }
+            }
+
+            // Blocked flags (conflict with cs session management)
+            "--session-id" => {
+                eprintln!("Error: '--session-id' conflicts with cs session management");
+                eprintln!("cs automatically manages session IDs based on folder+branch");
+                exit(EXIT_ERROR);
+            }
+
+            // Forwarded to Claude, and also enables cs's own logging
+            "--verbose" => {
+                VERBOSE.store(true, Ordering::Relaxed);
+                passthrough_args.push(arg.clone());
+            }
+
+            // Everything after `--` is claude's (e.g. a prompt starting with '-')
+            "--" => {
+                positionals.extend(args[i + 1..].iter().cloned());
+                passthrough_args.extend(args[i..].iter().cloned());
+                break;
+            }
+
+            // Check for Claude boolean flags
+            _ if CLAUDE_BOOL_FLAGS.contains(&arg.as_str()) => {
+                passthrough_args.push(arg.clone());
+            }
+
+            // Check for Claude value flags
+            _ if CLAUDE_VALUE_FLAGS.contains(&arg.as_str()) => {
+                i += 1;
+                if i < args.len() {
+                    match expand_value_flag(arg, &args[i]) {
+                        Ok(expanded) => passthrough_args.extend(expanded),
+                        Err(e) => {
+                            eprintln!("Error: {}", e);
+                            exit(EXIT_ERROR);
+                        }
+                    }
+                } else {
+                    eprintln!("Error: '{}' requires a value", arg);
+                    exit(EXIT_ERROR);
+                }
+            }
+
+            // Handle --flag=value syntax
+            _ if arg.contains('=') => match key_value_args(arg, loose_args) {
+                Some(Ok(forwarded)) => passthrough_args.extend(forwarded),
+                Some(Err(e)) => {
+                    eprintln!("Error: {}", e);
+                    exit(EXIT_ERROR);
+                }
+                None => {
+                    eprintln!("Unknown argument: {}", arg);
+                    eprintln!("Run 'cs --help' for cs options");
+                    eprintln!("Run 'claude --help' for Claude options");
+                    if arg.starts_with("--") {
+                        eprintln!("(or pass --loose to forward unknown --key=value arguments to claude)");
+                    }
+                    exit(EXIT_ERROR);
+                }
+            },
+
+            // Positional argument (prompt) - pass through to Claude
+            _ if !arg.starts_with('-') => {
+                positionals.push(arg.clone());
+                passthrough_args.push(arg.clone());
+            }
+
+            // Combined short flags (-fn): replace the cluster with its flags and re-read them
+            _ if is_short_cluster(arg) => match expand_short_cluster(arg) {
+                Ok(flags) => {
+                    args.splice(i..=i, flags);
+                    continue;
+                }
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    exit(EXIT_ERROR);
+                }
+            },
+
+            // Unknown flag
+            _ => {
+                eprintln!("Unknown argument: {}", arg);
+                eprintln!("Run 'cs --help' for cs options");
+                eprintln!("Run 'claude --help' for Claude options");
+                exit(EXIT_ERROR);
+            }
+        }
+        i += 1;
+    }
+
+    if timeout.is_some() && cfg!(not(windows)) && !watch {
+        eprintln!("Warning: --timeout is only enforced on Windows and with --watch (cs execs claude otherwise)");
+    }
+
+    if let Some(stray) = positionals.first().filter(|_| !allow_positional) {
+        eprintln!("Error: unexpected argument '{}' (positional arguments are disabled)", stray);
+        exit(EXIT_USAGE);
+    }
+
+    if touch_all {
+        run_touch_all(config.separator());
+        return;
+    }
+
+    if list_mode {
+        let project_folders = repo_only.then(|| repo_folders(&env::current_dir().unwrap_or_default()));
+        let group_by = GroupBy::parse(config.group_by().unwrap_or("folder")).unwrap_or_else(|e| {
+            eprintln!("Error: group_by: {}", e);
+            exit(EXIT_USAGE);
+        });
+        list_sessions(&tags, project_folders.as_deref(), config.separator(), group_by, json_output);
+        return;
+    }
+
+    if repo_only {
+        eprintln!("Error: '--repo' requires --list");
+        exit(EXIT_ERROR);
+    }
+
+    if gc {
+        run_gc(&config, &gc_options, args.iter().any(|a| a == "-y" || a == "--yes"));
+        return;
+    }
+    if !(gc_options.repair && gc_options.prune && gc_options.orphans) {
+        eprintln!("Error: '--no-repair', '--no-prune', and '--no-orphans' require --gc");
+        exit(EXIT_ERROR);
+    }
+
+    if !rm_targets.is_empty() {
+        run_remove(&rm_targets, config.backup_retention());
+        return;
+    }
+
+    if clear {
+        clear_sessions(config.backup_retention(), dry_run, args.iter().any(|a| a == "-y" || a == "--yes"));
+        return;
+    }
+
+    if remote_list {
+        remote_list_sessions(adopt);
+        return;
+    }
+
+    // The --stdin-json request picks the directory, mode, and extra claude
+    // args; everything else runs as if cs were started there with those flags
+    let mut stdin_namespace = None;
+    if stdin_json {
+        let request = read_stdin_json();
+        match request.mode {
+            JsonRequestMode::Resume => {}
+            JsonRequestMode::Create => force_create = true,
+            JsonRequestMode::Dry => {
+                dry_run = true;
+                DRY_RUN.store(true, Ordering::Relaxed);
+            }
+        }
+        stdin_namespace = request.namespace;
+        passthrough_args.extend(request.extra_args);
+    }
+    if fail_if_new && !dry_run {
+        eprintln!("Error: '--fail-if-new' requires --dry-run");
+        exit(EXIT_ERROR);
+    }
+    if reveal {
+        eprintln!("Error: '--reveal' requires --open-claude-config");
+        exit(EXIT_ERROR);
+    }
+    if clipboard && !copy_cmd {
+        eprintln!("Error: '--clipboard' requires --copy-cmd");
+        exit(EXIT_ERROR);
+    }
+    if new_suffix && resume_mode {
+        eprintln!("Error: '--new-suffix' always creates a session and can't be combined with --resume");
+        exit(EXIT_ERROR);
+    }
+    if explain && !dry_run {
+        eprintln!("Error: '--explain-uuid' requires --dry-run");
+        exit(EXIT_ERROR);
+    }
+
+    // Default args: command line > CS_CLAUDE_ARGS > project .cs.toml > user config
+    if !status_mode && !git_info && !print_name && assert_session.is_none() {
+        let project_config = match env::current_dir() {
+            Ok(cwd) => load_project_config(&cwd).unwrap_or_else(|e| {
+                eprintln!("Error: {}", e);
+                exit(EXIT_USAGE);
+            }),
+            Err(_) => Config::default(),
+        };
+        let env_args = split_args(&env::var("CS_CLAUDE_ARGS").unwrap_or_default()).unwrap_or_else(|e| {
+            eprintln!("Error: CS_CLAUDE_ARGS: {}", e);
+            exit(EXIT_USAGE);
+        });
+        passthrough_args = merge_default_args(
+            passthrough_args,
+            &[&env_args, project_config.default_args(), config.default_args()],
+        );
+    }
+
+    if resume_all_mode {
+        if !skip_version_check {
+            check_claude_version(&config);
+        }
+        resume_all(&config, &passthrough_args);
+        return;
+    }
+
+    // Machine-readable claude output: keep cs's own decorations out of the way
+    let quiet = stdin_json || (!dry_run && wants_machine_output(&passthrough_args));
+
+    if check_paths {
+        let base = launch_dir.clone().or_else(|| env::current_dir().ok()).unwrap_or_default();
+        if let Err(e) = check_path_args(&passthrough_args, &base) {
+            eprintln!("Error: {}", e);
+            exit(EXIT_USAGE);
+        }
+    }
+
+    // Without a terminal there is nobody to pick, so launch this directory's session
+    let interactive = interactive && {
+        use std::io::IsTerminal;
+        std::io::stdin().is_terminal()
+    };
+    if interactive {
+        match pick_session() {
+            Ok(Some(n)) => recent = Some(n),
+            Ok(None) => return,
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                exit(EXIT_ERROR);
+            }
+        }
+    }
+    // Sessions picked from the database rather than from the current directory
+    let picked = match (recent, &uuid_prefix) {
+        (Some(_), Some(_)) => Some(Err("'--recent' and '--resume-uuid' cannot be combined".to_string())),
+        (Some(n), None) => Some(nth_recent_session(n).map(|record| (record, format!("resume (recent #{})", n)))),
+        (None, Some(prefix)) => Some(
+            find_session_by_prefix(prefix, &load_records())
+                .map(|record| (record, "resume (by UUID prefix)".to_string())),
+        ),
+        (None, None) => None,
+    };
+    if let Some(picked) = picked {
+        let (record, status) = picked.unwrap_or_else(|e| {
+            eprintln!("Error: {}", e);
+            exit(EXIT_ERROR);
+        });
+        if !dry_run {
+            if !skip_version_check {
+                check_claude_version(&config);
+            }
+            enter_launch_dir(launch_dir.as_deref());
+            lock_session_or_exit(&config, &record.uuid);
+        }
+        resume_record(&record, &status, &passthrough_args, dry_run, quiet, box_charset(ascii), timeout);
+        return;
+    }
+
+    verbose_log(&format!("config: {}", get_config_path().display()));
+    verbose_log(&format!("database: {}", get_db_path().display()));
+
+    let separator = config.separator();
+
+    // Get folder name and git branch (folder-only if not in a git repo),
+    // unless --session-name supplies the name directly
+    let identity = match &name_override {
+        Some(name) => Ok(SessionIdentity {
+            name: name.clone(),
+            uuid: generate_uuid5(name),
+            is_git_repo: false,
+        }),
+        None => env::current_dir()
+            .map_err(|_| "Failed to get current directory".to_string())
+            .and_then(|cwd| {
+                let identity = resolve_session(&cwd, separator, !no_git)?;
+                Ok(match &base_ref {
+                    Some(base_ref) => apply_base_ref(identity, &cwd, base_ref),
+                    None => identity,
+                })
+            }),
+    };
+
+    // Parallel sessions: --new-suffix picks a fresh one, CS_SESSION_SUFFIX names one
+    let suffix = if new_suffix {
+        identity.as_ref().ok().map(|id| next_free_suffix(&id.name, &load_sessions()).to_string())
+    } else {
+        env::var("CS_SESSION_SUFFIX").ok().filter(|s| !s.is_empty())
+    };
+    let identity = identity.map(|id| match &suffix {
+        Some(suffix) => id.with_suffix(suffix),
+        None => id,
+    });
+    let identity = identity.map(|id| match &stdin_namespace {
+        Some(namespace) => id.in_namespace(namespace),
+        None => id,
+    });
+
+    if status_mode {
+        print_status(identity.as_ref().ok(), json_output);
+        return;
+    }
+    if print_name {
+        match &identity {
+            Ok(identity) if json_output => println!("{{\"name\":{}}}", json_string(&identity.name)),
+            Ok(identity) => println!("{}", identity.name),
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                exit(EXIT_ERROR);
+            }
+        }
+        return;
+    }
+    if git_info {
+        match &identity {
+            Ok(identity) if identity.is_git_repo => println!("git {}", identity.name),
+            Ok(identity) => {
+                println!("folder {}", identity.name);
+                exit(EXIT_NOT_GIT);
+            }
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                exit(EXIT_ERROR);
+            }
+        }
+        return;
+    }
+    if require_git && name_override.is_none() {
+        if let Ok(identity) = &identity {
+            if let Err(e) = check_require_git(identity) {
+                eprintln!("Error: {}", e);
+                exit(EXIT_NOT_GIT);
+            }
+        }
+    }
+    if adopt {
+        match &identity {
+            Ok(identity) => adopt_session(identity),
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                exit(EXIT_ERROR);
+            }
+        }
+        return;
+    }
+    if let Some(expect_exists) = assert_session {
+        if let Err(e) = identity.as_ref().map_err(String::clone).and_then(|id| check_session_assertion(expect_exists, id)) {
+            eprintln!("Error: {}", e);
+            exit(EXIT_ERROR);
+        }
+        return;
+    }
+    if doctor {
+        match &identity {
+            Ok(identity) => doctor_claude(identity),
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                exit(EXIT_ERROR);
+            }
+        }
+    }
+
+    let SessionIdentity {
+        name: session_name,
+        uuid: session_uuid,
+        is_git_repo,
+    } = match identity {
+        Ok(identity) => identity,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            exit(EXIT_ERROR);
+        }
+    };
+
+    if let Some(path) = &uuid_out {
+        if let Err(e) = write_uuid_file(path, &session_uuid) {
+            eprintln!("Error: {}", e);
+            exit(EXIT_CANT_CREATE);
+        }
+    }
+
+    // Tag the current session instead of launching
+    if !tags.is_empty() {
+        if !load_sessions().contains(&session_uuid) {
+            eprintln!("Error: {}", untracked_tag_error(&session_name));
+            exit(EXIT_ERROR);
+        }
+        if dry_run {
+            eprintln!("Would tag {} ({}) with: {}", session_name, session_uuid, tags.join(", "));
+            return;
+        }
+        match tag_session(&session_uuid, &session_name, &tags) {
+            Ok(all) => eprintln!("Tagged {} ({}): {}", session_name, session_uuid, all.join(", ")),
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                exit(EXIT_ERROR);
+            }
+        }
+        return;
+    }
+
+    // Print the claude argv (or command line) without the box or any database changes
+    if print_argv || copy_cmd {
+        let session_exists = load_sessions().contains(&session_uuid);
+        let mut claude_args =
+            LaunchMode::decide(resume_mode, force_create || reset_mode, session_exists).session_args(&session_uuid);
+        claude_args.extend(passthrough_args);
+        if copy_cmd {
+            let command = claude_command_line(&claude_args);
+            println!("{}", command);
+            if clipboard {
+                if copy_to_clipboard(&command) {
+                    eprintln!("(copied to clipboard)");
+                } else {
+                    eprintln!("Warning: could not copy to the clipboard");
+                }
+            }
+        } else {
+            for arg in &claude_args {
+                println!("{}", arg);
+            }
+        }
+        return;
+    }
+
+    // Only launches record the naming settings, so queries run from a shell
+    // prompt (--status, --print-name, ...) never write or warn
+    check_separator_change(separator);
+    check_case_insensitive_change(config.case_insensitive());
+
+    // Handle reset mode: remove existing entry from database
+    if reset_mode {
+        remove_session(&session_uuid);
+    }
+
+    // Load session database (after potential reset)
+    let sessions = load_sessions();
+    let session_exists = sessions.contains(&session_uuid);
+
+    // A branch with no session may just have been renamed: offer its old session
+    let plain_launch = !dry_run && !resume_mode && !force_create && !reset_mode;
+    if rename_branch_aware && plain_launch && !session_exists && is_git_repo && name_override.is_none() && suffix.is_none() {
+        let cwd = env::current_dir().unwrap_or_default();
+        let prefix = get_folder_name_in(&cwd).map(|folder| format!("{}{}", folder, separator)).unwrap_or_default();
+        let branch_exists = |branch: &str| local_branch_exists_in(&cwd, branch);
+        if let Some(record) = renamed_branch_candidate(&load_records(), &prefix, &session_name, &branch_exists) {
+            if confirm_renamed_branch(&session_name, &record) {
+                if !skip_version_check {
+                    check_claude_version(&config);
+                }
+                enter_launch_dir(launch_dir.as_deref());
+                lock_session_or_exit(&config, &record.uuid);
+                resume_record(&record, "resume (renamed branch)", &passthrough_args, false, quiet, box_charset(ascii), timeout);
+                return;
+            }
+        }
+    }
+
+    // Determine effective status for display
+    let status_display = if resume_mode {
+        "resume-with-picker"
+    } else if force_create || reset_mode {
+        "force-create"
+    } else if session_exists {
+        "exists"
+    } else {
+        "new"
+    };
+
+    // Print info
+    let mut rows = vec![
+        ("Session", session_name.as_str()),
+        ("UUID", session_uuid.as_str()),
+        ("Status", status_display),
+    ];
+    if let Some(suffix) = &suffix {
+        rows.push(("Suffix", suffix.as_str()));
+    }
+    let launch_display = launch_dir.as_ref().map(|dir| dir.display().to_string());
+    if let Some(dir) = &launch_display {
+        rows.push(("Launch", dir.as_str()));
+    }
+    if name_override.is_some() {
+        rows.push(("Note", "Name set by --session-name (folder/branch ignored)"));
+    } else if !is_git_repo {
+        rows.push(("Note", "Not a git repo (folder-only mode)"));
+    }
+    if stdin_json {
+        let mut argv =
+            LaunchMode::decide(resume_mode, force_create || reset_mode, session_exists).session_args(&session_uuid);
+        argv.extend(passthrough_args.iter().cloned());
+        print_stdin_json(&session_name, &session_uuid, session_exists, &argv, !dry_run);
+    } else if !quiet {
+        eprintln!("{}", render_info_box(&rows, box_charset(ascii), terminal_width()));
+    }
+
+    // Check for dry-run
+    if dry_run {
+        if !passthrough_args.is_empty() {
+            eprintln!("Passthrough args: {:?}", passthrough_args);
+        }
+        if explain {
+            explain_uuid(&session_name);
+        }
+        if fail_if_new && !session_exists {
+            exit(EXIT_NEW_SESSION);
+        }
+        return;
+    }
+
+    if !skip_version_check {
+        check_claude_version(&config);
+    }
+    enter_launch_dir(launch_dir.as_deref());
+
+    // Determine which arguments to use
+    let mut mode = LaunchMode::decide(resume_mode, force_create || reset_mode, session_exists);
+    if mode == LaunchMode::Create && !session_exists && confirm_new && !confirm_new_session(&session_name) {
+        eprintln!("Not creating a session.");
+        return;
+    }
+    // A stale entry would make `claude -r` fail, so start the conversation afresh
+    let mut session_exists = session_exists;
+    if mode == LaunchMode::Resume
+        && config.recover_missing_sessions()
+        && missing_from_claude_store(&session_uuid, list_claude_sessions())
+    {
+        eprintln!("cs: Claude has no conversation {}; starting it as a new session", session_uuid);
+        remove_session(&session_uuid);
+        mode = LaunchMode::Create;
+        session_exists = false;
+    }
+    lock_session_or_exit(&config, &session_uuid);
+    if session_exists {
+        touch_session(&session_uuid);
+    }
+    if mode == LaunchMode::Create && !session_exists {
+        save_record(&SessionRecord::new(&session_uuid, Some(&session_name)).stamped(unix_now()));
+    }
+    if !quiet {
+        match mode {
+            LaunchMode::ResumePicker => eprintln!("Resuming session (with picker fallback)..."),
+            LaunchMode::Create => eprintln!("Creating session..."),
+            LaunchMode::Resume => eprintln!("Resuming session..."),
+        }
+    }
+    let mut claude_args = mode.session_args(&session_uuid);
+
+    // Append passthrough args
+    let relaunch_args = watch.then(|| {
+        let mut args = LaunchMode::Resume.session_args(&session_uuid);
+        args.extend(passthrough_args.iter().cloned());
+        args
+    });
+    claude_args.extend(passthrough_args);
+    verbose_log(&format!("exec: claude {}", claude_args.join(" ")));
+
+    let recovery = SessionRecovery::new(&session_uuid, &session_name, &config);
+
+    // --watch keeps cs around to relaunch claude, so it never execs
+    if let Some(relaunch_args) = relaunch_args {
+        watch_claude(claude_args, relaunch_args, timeout, &recovery);
+    }
+
+    // Where cs waits for claude, recover from a stale entry automatically
+    if mode == LaunchMode::Resume && cfg!(windows) {
+        exit(recovery.run(&claude_args, timeout));
+    }
+
+    // Launch claude (platform-specific)
+    launch_claude_owned(claude_args, timeout);
+}
+
+/// Aggregate numbers for `cs stats`
+#[derive(Debug, PartialEq)]
+struct SessionStats {
+    total: usize,
+    used_7d: usize,
+    used_30d: usize,
+    repos: usize,
+    oldest: Option<u64>,
+    newest: Option<u64>,
+}
+
+/// Compute stats over database records. Repos are counted by the folder part
+/// of stored names; `oldest` is the earliest creation, `newest` the latest use.
+fn session_stats(records: &[SessionRecord], now: u64, separator: &str) -> SessionStats {
+    let used_within = |days: u64| {
+        let cutoff = now.saturating_sub(days * 24 * 60 * 60);
+        records.iter().filter(|r| r.last_used.is_some_and(|used| used >= cutoff)).count()
+    };
+    let repos: HashSet<&str> = records
+        .iter()
+        .filter_map(|r| r.name.as_deref())
+        .filter_map(|name| name.split(separator).next())
+        .filter_map(|folder| folder.split(SUFFIX_MARKER).next())
+        .collect();
+
+    SessionStats {
+        total: records.len(),
+        used_7d: used_within(7),
+        used_30d: used_within(30),
+        repos: repos.len(),
+        oldest: records.iter().filter_map(|r| r.created.or(r.last_used)).min(),
+        newest: records.iter().filter_map(|r| r.last_used).max(),
+    }
+}
+
+/// Print `cs stats`, as text or a JSON object
+fn print_stats(stats: &SessionStats, json: bool) {
+    if json {
+        let timestamp = |secs: Option<u64>| secs.map_or("null".to_string(), |s| json_string(&format_utc(s)));
+        println!(
+            "{{\"total\":{},\"used_7d\":{},\"used_30d\":{},\"repos\":{},\"oldest\":{},\"newest\":{}}}",
+            stats.total,
+            stats.used_7d,
+            stats.used_30d,
+            stats.repos,
+            timestamp(stats.oldest),
+            timestamp(stats.newest)
+        );
+        return;
+    }
+    let timestamp = |secs: Option<u64>| secs.map_or("unknown".to_string(), format_utc);
+    println!("Sessions:          {}", stats.total);
+    println!("Used in 7 days:    {}", stats.used_7d);
+    println!("Used in 30 days:   {}", stats.used_30d);
+    println!("Repositories:      {}", stats.repos);
+    println!("Oldest:            {}", timestamp(stats.oldest));
+    println!("Most recent use:   {}", timestamp(stats.newest));
+}
+
+/// Default number of sessions `--mru` prints
+const DEFAULT_MRU_COUNT: usize = 10;
+
+/// Format `--mru` lines (`N) name`) for the `count` most recently used sessions.
+/// N matches `--recent N`.
+fn mru_lines(mut records: Vec<SessionRecord>, count: usize) -> Vec<String> {
+    sort_by_recency(&mut records);
+    records
+        .iter()
+        .take(count)
+        .enumerate()
+        .map(|(i, record)| format!("{}) {}", i + 1, record.name.as_deref().unwrap_or(&record.uuid)))
+        .collect()
+}
+
+/// Format the `--interactive` picker lines (`N) name  short-uuid`) for records in recency order
+fn picker_lines(records: &[SessionRecord]) -> Vec<String> {
+    let width = records.len().to_string().len();
+    records
+        .iter()
+        .enumerate()
+        .map(|(i, record)| {
+            let short_uuid = &record.uuid[..8.min(record.uuid.len())];
+            let name = record.name.as_deref().unwrap_or("(unnamed)");
+            format!("{:>width$}) {}  {}", i + 1, name, short_uuid, width = width)
+        })
+        .collect()
+}
+
+/// Parse a picker answer: Ok(None) aborts (empty or `q`), Ok(Some(n)) picks entry n
+fn parse_picker_choice(input: &str, count: usize) -> Result<Option<usize>, String> {
+    let input = input.trim();
+    if input.is_empty() || input.eq_ignore_ascii_case("q") {
+        return Ok(None);
+    }
+    match input.parse::<usize>() {
+        Ok(n) if (1..=count).contains(&n) => Ok(Some(n)),
+        _ => Err(format!("invalid choice '{}': enter a number from 1 to {}", input, count)),
+    }
+}
+
+/// Prompt for a session from the database (most recent first), returning its
+/// position for `--recent`, or None if the user aborted
+fn pick_session() -> Result<Option<usize>, String> {
+    let mut records = load_records();
+    if records.is_empty() {
+        eprintln!("No sessions in database.");
+        return Ok(None);
+    }
+    sort_by_recency(&mut records);
+    for line in picker_lines(&records) {
+        eprintln!("{}", line);
+    }
+    eprint!("Resume which session? [1-{}, q to quit] ", records.len());
+    let _ = std::io::stderr().flush();
+
+    let mut input = String::new();
+    std::io::stdin()
+        .read_line(&mut input)
+        .map_err(|e| format!("Failed to read choice: {}", e))?;
+    parse_picker_choice(&input, records.len())
+}
+
+/// Whether a `[y/N]` answer means yes (anything else, including empty, is no)
+fn is_yes(answer: &str) -> bool {
+    matches!(answer.trim().to_ascii_lowercase().as_str(), "y" | "yes")
+}
+
+/// Ask before creating a new session (`confirm_new`). Without a terminal on
+/// stdin there is nobody to ask, so creation proceeds.
+fn confirm_new_session(name: &str) -> bool {
+    use std::io::IsTerminal;
+    if !std::io::stdin().is_terminal() {
+        return true;
+    }
+    eprint!("No existing session for {}. Create new? [y/N] ", name);
+    let _ = std::io::stderr().flush();
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input).is_ok() && is_yes(&input)
+}
+
+/// Find the session a renamed branch probably left behind (`--rename-branch-aware`):
+/// the most recently used record named `prefix` (folder + separator) plus a branch
+/// other than `current` that no longer exists locally
+fn renamed_branch_candidate(
+    records: &[SessionRecord],
+    prefix: &str,
+    current: &str,
+    branch_exists: &dyn Fn(&str) -> bool,
+) -> Option<SessionRecord> {
+    let mut candidates: Vec<SessionRecord> = records
+        .iter()
+        .filter(|record| {
+            record.name.as_deref().is_some_and(|name| {
+                name != current
+                    && name
+                        .strip_prefix(prefix)
+                        .is_some_and(|branch| !branch.is_empty() && !branch_exists(dedupe_key(branch)))
+            })
+        })
+        .cloned()
+        .collect();
+    sort_by_recency(&mut candidates);
+    candidates.into_iter().next()
+}
+
+/// Ask whether to resume a renamed branch's session. Without a terminal on
+/// stdin there is nobody to ask, so a new session is created as usual.
+fn confirm_renamed_branch(name: &str, record: &SessionRecord) -> bool {
+    use std::io::IsTerminal;
+    if !std::io::stdin().is_terminal() {
+        return false;
+    }
+    let used = record.last_used.map(format_utc).unwrap_or_else(|| "unknown".to_string());
+    eprintln!("No existing session for {}.", name);
+    eprint!(
+        "Resume {} (last used {}) instead? Its branch no longer exists, so it may have been renamed. [y/N] ",
+        record.name.as_deref().unwrap_or_default(),
+        used
+    );
+    let _ = std::io::stderr().flush();
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input).is_ok() && is_yes(&input)
+}
+
+/// Resume a session picked from the database rather than the current
+/// directory (`--recent <n>`, `--resume-uuid <prefix>`)
+fn resume_record(
+    record: &SessionRecord,
+    status: &str,
+    passthrough_args: &[String],
+    dry_run: bool,
+    quiet: bool,
+    charset: &BoxCharset,
+    timeout: Option<Duration>,
+) {
+    let rows = vec![
+        ("Session", record.name.as_deref().unwrap_or("(unnamed)")),
+        ("UUID", record.uuid.as_str()),
+        ("Status", status),
+    ];
+    if !quiet {
+        eprintln!("{}", render_info_box(&rows, charset, terminal_width()));
+    }
+
+    if dry_run {
+        if !passthrough_args.is_empty() {
+            eprintln!("Passthrough args: {:?}", passthrough_args);
+        }
+        return;
+    }
+
+    touch_session(&record.uuid);
+    if !quiet {
+        eprintln!("Resuming session...");
+    }
+    let mut claude_args = LaunchMode::Resume.session_args(&record.uuid);
+    claude_args.extend_from_slice(passthrough_args);
+    verbose_log(&format!("exec: claude {}", claude_args.join(" ")));
+    launch_claude_owned(claude_args, timeout);
+}
+
+/// Register the current directory's session without launching (`--adopt`)
+fn adopt_session(identity: &SessionIdentity) {
+    if load_sessions().contains(&identity.uuid) {
+        eprintln!("Warning: {} ({}) is already tracked", identity.name, identity.uuid);
+        return;
+    }
+    if is_dry_run() {
+        eprintln!("Would adopt {} ({})", identity.name, identity.uuid);
+        return;
+    }
+    if !db_writable() {
+        eprintln!("Error: session database is read-only; nothing adopted");
+        exit(EXIT_ERROR);
+    }
+    save_record(&SessionRecord::new(&identity.uuid, Some(&identity.name)).stamped(unix_now()));
+    eprintln!("Adopted {} ({})", identity.name, identity.uuid);
+
+    if let Ok(sessions) = list_claude_sessions() {
+        if !sessions.iter().any(|s| s.uuid == identity.uuid) {
+            eprintln!("Note: Claude has no conversation with this UUID yet; 'cs' will resume it once one exists");
+        }
+    }
+}
+
+/// Compute `(name, uuid)` for each session name in `content` in `namespace`
+/// (`--batch`). Blank lines and `#` comments are skipped.
+fn batch_uuids(content: &str, namespace: &[u8; 16]) -> Vec<(String, String)> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|name| (name.to_string(), generate_uuid5_with(name, namespace)))
+        .collect()
+}
+
+/// A machine-readable output described by `--json-schema-out`
+struct JsonOutput {
+    name: &'static str,
+    command: &'static str,
+    /// Whether the output is an array of these objects
+    array: bool,
+    /// (field, JSON type); a type of `string|null` means the field may be null
+    fields: &'static [(&'static str, &'static str)],
+}
+
+const JSON_OUTPUTS: &[JsonOutput] = &[
+    JsonOutput {
+        name: "status",
+        command: "cs --status --json",
+        array: false,
+        fields: &[("status", "string"), ("name", "string|null"), ("uuid", "string|null")],
+    },
+    JsonOutput {
+        name: "list",
+        command: "cs --list --json",
+        array: true,
+        fields: &[
+            ("uuid", "string"),
+            ("name", "string|null"),
+            ("tags", "array"),
+            ("created", "string|null"),
+            ("used", "string|null"),
+        ],
+    },
+    JsonOutput {
+        name: "print-name",
+        command: "cs --print-name --json",
+        array: false,
+        fields: &[("name", "string")],
+    },
+    JsonOutput {
+        name: "stats",
+        command: "cs stats --json",
+        array: false,
+        fields: &[
+            ("total", "integer"),
+            ("used_7d", "integer"),
+            ("used_30d", "integer"),
+            ("repos", "integer"),
+            ("oldest", "string|null"),
+            ("newest", "string|null"),
+        ],
+    },
+    JsonOutput {
+        name: "batch",
+        command: "cs --batch <file> --json",
+        array: true,
+        fields: &[("name", "string"), ("uuid", "string")],
+    },
+    JsonOutput {
+        name: "stdin-json",
+        command: "cs --stdin-json",
+        array: false,
+        fields: &[
+            ("name", "string"),
+            ("uuid", "string"),
+            ("status", "string"),
+            ("dir", "string"),
+            ("launched", "boolean"),
+            ("argv", "array"),
+        ],
+    },
+    JsonOutput {
+        name: "stdin-json-error",
+        command: "cs --stdin-json (invalid request)",
+        array: false,
+        fields: &[("error", "string")],
+    },
+];
+
+/// Build the JSON Schema (draft 2020-12) document for one machine-readable output
+fn json_schema(output: &JsonOutput) -> String {
+    let properties: Vec<String> = output
+        .fields
+        .iter()
+        .map(|(name, kind)| {
+            let kind = match kind.split_once('|') {
+                Some((kind, "null")) => format!("[{},\"null\"]", json_string(kind)),
+                _ => json_string(kind),
+            };
+            format!("{}:{{\"type\":{}}}", json_string(name), kind)
+        })
+        .collect();
+    let required: Vec<String> = output.fields.iter().map(|(name, _)| json_string(name)).collect();
+    let object = format!(
+        "{{\"type\":\"object\",\"properties\":{{{}}},\"required\":[{}],\"additionalProperties\":false}}",
+        properties.join(","),
+        required.join(",")
+    );
+    let body = if output.array {
+        format!("\"type\":\"array\",\"items\":{}", object)
+    } else {
+        object[1..object.len() - 1].to_string()
+    };
+    format!(
+        "{{\"$schema\":\"https://json-schema.org/draft/2020-12/schema\",\"title\":{},\"description\":{},{}}}",
+        json_string(&format!("cs {}", output.name)),
+        json_string(&format!("Output of `{}`", output.command)),
+        body
+    )
+}
 
-            // cs-specific mode flags
-            "--dry-run" | "-n" => {
-                dry_run = true;
-            }
-            "--force" | "-f" => {
-                force_create = true;
-            }
-            "--reset" => {
-                reset_mode = true;
-            }
-            "--resume" | "-R" => {
-                resume_mode = true;
-            }
+/// Print `--json-schema-out`: one object mapping each output name to its schema
+fn print_json_schemas() {
+    let entries: Vec<String> = JSON_OUTPUTS
+        .iter()
+        .map(|output| format!("  {}: {}", json_string(output.name), json_schema(output)))
+        .collect();
+    println!("{{\n{}\n}}", entries.join(",\n"));
+}
 
-            // Blocked flags (conflict with cs session management)
-            "--session-id" => {
-                eprintln!("Error: '--session-id' conflicts with cs session management");
-                eprintln!("cs automatically manages session IDs based on folder+branch");
-                exit(1);
-            }
+/// Print `--batch` results as `name<TAB>uuid` lines or a JSON array
+fn print_batch(entries: &[(String, String)], json: bool) {
+    if json {
+        let items: Vec<String> = entries
+            .iter()
+            .map(|(name, uuid)| format!("{{\"name\":{},\"uuid\":{}}}", json_string(name), json_string(uuid)))
+            .collect();
+        println!("[{}]", items.join(","));
+        return;
+    }
+    for (name, uuid) in entries {
+        println!("{}\t{}", name, uuid);
+    }
+}
 
-            // Check for Claude boolean flags
-            _ if CLAUDE_BOOL_FLAGS.contains(&arg.as_str()) => {
-                passthrough_args.push(arg.clone());
-            }
+/// What a `--stdin-json` request asks cs to do
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum JsonRequestMode {
+    /// Resume the session, creating it if it's new (plain `cs`)
+    Resume,
+    /// Always start a new conversation (`cs --force`)
+    Create,
+    /// Report the session without launching (`cs --dry-run`)
+    Dry,
+}
 
-            // Check for Claude value flags
-            _ if CLAUDE_VALUE_FLAGS.contains(&arg.as_str()) => {
-                passthrough_args.push(arg.clone());
-                i += 1;
-                if i < args.len() {
-                    passthrough_args.push(args[i].clone());
-                } else {
-                    eprintln!("Error: '{}' requires a value", arg);
-                    exit(1);
+/// A `--stdin-json` request: `{"dir", "mode", "namespace", "extra_args"}`, all optional
+#[derive(Debug, PartialEq)]
+struct JsonRequest {
+    dir: Option<String>,
+    mode: JsonRequestMode,
+    namespace: Option<String>,
+    extra_args: Vec<String>,
+}
+
+/// Parse and validate a `--stdin-json` request; unknown fields are rejected
+fn parse_json_request(input: &str) -> Result<JsonRequest, String> {
+    let JsonValue::Object(fields) = parse_json(input).map_err(|e| format!("invalid JSON: {}", e))? else {
+        return Err("request must be a JSON object".to_string());
+    };
+    let mut request = JsonRequest {
+        dir: None,
+        mode: JsonRequestMode::Resume,
+        namespace: None,
+        extra_args: Vec::new(),
+    };
+    let string = |key: &str, value: JsonValue| match value {
+        JsonValue::String(s) => Ok(s),
+        _ => Err(format!("'{}' must be a string", key)),
+    };
+    for (key, value) in fields {
+        match key.as_str() {
+            "dir" => request.dir = Some(string(&key, value)?),
+            "namespace" => request.namespace = Some(string(&key, value)?),
+            "mode" => {
+                request.mode = match string(&key, value)?.as_str() {
+                    "resume" => JsonRequestMode::Resume,
+                    "create" => JsonRequestMode::Create,
+                    "dry" => JsonRequestMode::Dry,
+                    other => return Err(format!("'mode' must be resume, create, or dry, got '{}'", other)),
                 }
             }
-
-            // Handle --flag=value syntax
-            _ if arg.contains('=') => {
-                let key = arg.split('=').next().unwrap();
-                if CLAUDE_VALUE_FLAGS.contains(&key) || CLAUDE_BOOL_FLAGS.contains(&key) {
-                    passthrough_args.push(arg.clone());
-                } else {
-                    eprintln!("Unknown argument: {}", arg);
-                    eprintln!("Run 'cs --help' for cs options");
-                    eprintln!("Run 'claude --help' for Claude options");
-                    exit(1);
+            "extra_args" => {
+                let JsonValue::Array(items) = value else {
+                    return Err("'extra_args' must be an array of strings".to_string());
+                };
+                request.extra_args = items
+                    .into_iter()
+                    .map(|item| string("extra_args", item).map_err(|_| "'extra_args' must be an array of strings".to_string()))
+                    .collect::<Result<_, _>>()?;
+                if request.extra_args.iter().any(|a| a == "--session-id") {
+                    return Err("'--session-id' conflicts with cs session management".to_string());
                 }
             }
+            other => return Err(format!("unknown field '{}'", other)),
+        }
+    }
+    Ok(request)
+}
 
-            // Positional argument (prompt) - pass through to Claude
-            _ if !arg.starts_with('-') => {
-                passthrough_args.push(arg.clone());
-            }
+/// Print a `--stdin-json` error object and exit
+fn stdin_json_error(message: &str, code: i32) -> ! {
+    println!("{{\"error\":{}}}", json_string(message));
+    exit(code);
+}
 
-            // Unknown flag
-            _ => {
-                eprintln!("Unknown argument: {}", arg);
-                eprintln!("Run 'cs --help' for cs options");
-                eprintln!("Run 'claude --help' for Claude options");
-                exit(1);
-            }
+/// A `--stdin-json` request after validation, for main to apply
+struct StdinJson {
+    mode: JsonRequestMode,
+    namespace: Option<[u8; 16]>,
+    extra_args: Vec<String>,
+}
+
+/// Read and validate the `--stdin-json` request, then move into its `dir` so
+/// the session is resolved, configured, and launched there. Problems with the
+/// request itself are printed as `{"error": …}`.
+fn read_stdin_json() -> StdinJson {
+    let input = std::io::read_to_string(std::io::stdin())
+        .unwrap_or_else(|e| stdin_json_error(&format!("failed to read stdin: {}", e), EXIT_ERROR));
+    let request = parse_json_request(&input).unwrap_or_else(|e| stdin_json_error(&e, EXIT_USAGE));
+
+    let namespace = request.namespace.as_deref().map(|value| {
+        let namespace = well_known_namespace(value).or_else(|| parse_uuid_strict(value)).unwrap_or_else(|| {
+            stdin_json_error(&format!("'namespace' must be a UUID or one of dns, url, oid, x500, got '{}'", value), EXIT_USAGE)
+        });
+        if NAMESPACE_OVERRIDE.get().is_some_and(|flag| *flag != namespace) {
+            stdin_json_error("'namespace' conflicts with --namespace/--namespace-name", EXIT_USAGE);
         }
-        i += 1;
+        namespace
+    });
+    if let Some(dir) = &request.dir {
+        let path = fs::canonicalize(dir)
+            .ok()
+            .filter(|path| path.is_dir())
+            .unwrap_or_else(|| stdin_json_error(&format!("'dir' is not an accessible directory: {}", dir), EXIT_ERROR));
+        if let Err(e) = env::set_current_dir(&path) {
+            stdin_json_error(&format!("failed to enter {}: {}", path.display(), e), EXIT_ERROR);
+        }
+    }
+    StdinJson {
+        mode: request.mode,
+        namespace,
+        extra_args: request.extra_args,
+    }
+}
+
+/// Print the `--stdin-json` result object (in place of the info box)
+fn print_stdin_json(name: &str, uuid: &str, session_exists: bool, argv: &[String], launched: bool) {
+    let dir = env::current_dir().unwrap_or_default();
+    let argv: Vec<String> = argv.iter().map(|a| json_string(a)).collect();
+    println!(
+        "{{\"name\":{},\"uuid\":{},\"status\":{},\"dir\":{},\"launched\":{},\"argv\":[{}]}}",
+        json_string(name),
+        json_string(uuid),
+        json_string(if session_exists { "exists" } else { "new" }),
+        json_string(&dir.display().to_string()),
+        launched,
+        argv.join(",")
+    );
+}
+
+/// Whether Claude's session store lacks `uuid` (`recover_missing_sessions`).
+/// A store that can't be read, or holds nothing at all, proves nothing.
+fn missing_from_claude_store(uuid: &str, store: Result<Vec<ClaudeSession>, String>) -> bool {
+    store.is_ok_and(|sessions| !sessions.is_empty() && !sessions.iter().any(|s| s.uuid == uuid))
+}
+
+/// Summarize whether a session is known to cs's database and to Claude's own store
+fn session_health(identity: &SessionIdentity, in_cs: bool, in_claude: Result<bool, String>) -> String {
+    let session = format!("session {} ({})", identity.name, identity.uuid);
+    match (in_cs, in_claude) {
+        (_, Err(e)) => {
+            let tracked = if in_cs { "in" } else { "not in" };
+            format!("{} is {} cs's database; Claude's store could not be read: {}", session, tracked, e)
+        }
+        (true, Ok(true)) => format!("{} is in cs's database and Claude's store", session),
+        (true, Ok(false)) => format!(
+            "{} is in cs's database but not Claude's store (expect \"No conversation found\"; run 'cs --reset')",
+            session
+        ),
+        (false, Ok(true)) => format!(
+            "{} is in Claude's store but not cs's database (run 'cs --remote-list --adopt' to track it)",
+            session
+        ),
+        (false, Ok(false)) => format!("{} is new (in neither cs's database nor Claude's store)", session),
     }
+}
 
-    // Get folder name
-    let folder_name = match get_folder_name() {
-        Ok(name) => name,
+/// Run `claude doctor`, show its output verbatim, then add cs's view of the current session
+fn doctor_claude(identity: &SessionIdentity) -> ! {
+    if !check_claude_installed() {
+        print_claude_not_found_error();
+        exit(EXIT_CLAUDE_NOT_FOUND);
+    }
+    let output = match claude_command(&["doctor"]).output() {
+        Ok(output) => output,
         Err(e) => {
-            eprintln!("Error: {}", e);
-            exit(1);
+            eprintln!("Error running claude doctor: {}", e);
+            exit(EXIT_ERROR);
         }
     };
+    let _ = std::io::stdout().write_all(&output.stdout);
+    let _ = std::io::stderr().write_all(&output.stderr);
 
-    // Get git branch (optional - fall back to folder-only if not in a git repo)
-    let (session_name, is_git_repo) = match get_git_branch() {
-        Ok(branch_name) => (format!("{}+{}", folder_name, branch_name), true),
-        Err(_) => (folder_name.clone(), false),
-    };
-    let session_uuid = generate_uuid5(&session_name);
+    let in_cs = load_sessions().contains(&identity.uuid);
+    let in_claude = list_claude_sessions().map(|sessions| sessions.iter().any(|s| s.uuid == identity.uuid));
+    println!();
+    println!("cs: {}", session_health(identity, in_cs, in_claude));
+    exit(child_exit_code(output.status));
+}
 
-    // Handle reset mode: remove existing entry from database
-    if reset_mode {
-        remove_session(&session_uuid);
+/// Name and expected UUID `--self-test` checks the generator against (DNS namespace)
+const SELF_TEST_VECTOR: (&str, &str) = ("claude-code-resumer+main", "afe19c61-d53f-581c-985c-56e9daf4e63d");
+
+/// `--self-test`: the UUID generator reproduces a known vector
+fn self_test_uuid() -> Result<String, String> {
+    let (name, expected) = SELF_TEST_VECTOR;
+    let uuid = generate_uuid5_with(name, &DEFAULT_NAMESPACE);
+    if uuid == expected {
+        Ok(format!("{} -> {}", name, uuid))
+    } else {
+        Err(format!("{} -> {}, expected {}", name, uuid, expected))
     }
+}
 
-    // Load session database (after potential reset)
-    let sessions = load_sessions();
-    let session_exists = sessions.contains(&session_uuid);
+/// `--self-test`: the database directory accepts a scratch write, read, and delete
+fn self_test_db_dir(db_path: &Path) -> Result<String, String> {
+    let dir = db_path.parent().unwrap_or(Path::new("."));
+    let scratch = dir.join(format!(".cs-self-test.{}", std::process::id()));
+    let payload = "cs self-test\n";
+    fs::create_dir_all(dir).map_err(|e| format!("cannot create {}: {}", dir.display(), e))?;
+    fs::write(&scratch, payload).map_err(|e| format!("cannot write in {}: {}", dir.display(), e))?;
+    let read = fs::read_to_string(&scratch);
+    let removed = fs::remove_file(&scratch);
+    match (read, removed) {
+        (Ok(content), Ok(())) if content == payload => Ok(format!("{} is writable", dir.display())),
+        (Ok(_), Ok(())) => Err(format!("{}: scratch file read back differently", dir.display())),
+        (Err(e), _) => Err(format!("cannot read back {}: {}", scratch.display(), e)),
+        (_, Err(e)) => Err(format!("cannot delete {}: {}", scratch.display(), e)),
+    }
+}
 
-    // Determine effective status for display
-    let status_display = if resume_mode {
-        "resume-with-picker"
-    } else if force_create || reset_mode {
-        "force-create"
-    } else if session_exists {
-        "exists"
-    } else {
-        "new"
-    };
+/// `--self-test`: claude (or the `claude_launcher`) runs and reports a version
+fn self_test_claude() -> Result<String, String> {
+    let command = claude_launcher().join(" ");
+    match claude_command(&["--version"]).output() {
+        Ok(output) if output.status.success() => Ok(String::from_utf8_lossy(&output.stdout).trim().to_string()),
+        Ok(output) => Err(format!("'{} --version' exited with {}", command, child_exit_code(output.status))),
+        Err(e) => Err(format!("cannot run '{}': {}", command, e)),
+    }
+}
 
-    // Print info
-    println!("┌─────────────────────────────────────────────");
-    println!("│ Session: {}", session_name);
-    println!("│ UUID:    {}", session_uuid);
-    println!("│ Status:  {}", status_display);
-    if !is_git_repo {
-        println!("│ Note:    Not a git repo (folder-only mode)");
-    }
-    println!("└─────────────────────────────────────────────");
-    println!();
+/// Run `--self-test`: exercise UUID generation, the database directory, and
+/// launching claude, printing pass/FAIL per check. Exits nonzero if any check fails.
+fn run_self_test() -> ! {
+    let checks = [
+        ("UUID v5", self_test_uuid()),
+        ("Database", self_test_db_dir(&get_db_path())),
+        ("Claude", self_test_claude()),
+    ];
+    for (name, result) in &checks {
+        match result {
+            Ok(detail) => println!("pass  {:<9} {}", name, detail),
+            Err(problem) => println!("FAIL  {:<9} {}", name, problem),
+        }
+    }
+    let passed = checks.iter().filter(|(_, result)| result.is_ok()).count();
+    println!("{} of {} checks passed", passed, checks.len());
+    exit(if passed == checks.len() { 0 } else { EXIT_ERROR });
+}
 
-    // Check for dry-run
-    if dry_run {
-        if !passthrough_args.is_empty() {
-            println!("Passthrough args: {:?}", passthrough_args);
+/// Exit code to report for a finished claude process. The full code is kept
+/// (Windows NTSTATUS values such as 0xC0000005 arrive as negative i32 and
+/// `exit` hands them back unchanged). A process that ended without a code
+/// never counts as success: a Unix signal maps to 128 + signal, anything else
+/// to EXIT_ERROR.
+fn child_exit_code(status: ExitStatus) -> i32 {
+    if let Some(code) = status.code() {
+        return code;
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            return 128 + signal;
         }
-        return;
     }
+    EXIT_ERROR
+}
 
-    // Determine which arguments to use
-    let mut claude_args: Vec<String> = if resume_mode {
-        println!("Resuming session (with picker fallback)...");
-        vec!["--resume".to_string(), session_uuid.clone()]
-    } else if force_create || reset_mode || !session_exists {
-        if !session_exists {
-            save_session(&session_uuid);
-        }
-        println!("Creating session...");
-        vec!["--session-id".to_string(), session_uuid.clone()]
-    } else {
-        println!("Resuming session...");
-        vec!["-r".to_string(), session_uuid.clone()]
+/// Parse a version like "1.2.3", "v1.2" or "1.0.44 (Claude Code)" into
+/// (major, minor, patch). Pre-release/build suffixes (`-beta`, `+abc`) are ignored.
+fn parse_version(text: &str) -> Option<(u64, u64, u64)> {
+    let token = text
+        .split_whitespace()
+        .map(|t| t.trim_start_matches('v'))
+        .find(|t| t.starts_with(|c: char| c.is_ascii_digit()))?;
+    let core = token.split(['-', '+']).next()?;
+    let mut parts = core.split('.').map(|p| p.parse::<u64>());
+    let major = parts.next()?.ok()?;
+    let minor = parts.next().unwrap_or(Ok(0)).ok()?;
+    let patch = parts.next().unwrap_or(Ok(0)).ok()?;
+    Some((major, minor, patch))
+}
+
+/// Refuse to launch when the installed Claude is older than `min_claude_version`
+fn check_claude_version(config: &Config) {
+    let Some(required) = config.min_claude_version() else {
+        return;
+    };
+    let Some(minimum) = parse_version(required) else {
+        eprintln!("Error: min_claude_version '{}' is not a version number", required);
+        exit(EXIT_USAGE);
     };
+    let installed = match claude_command(&["--version"]).output() {
+        Ok(output) => String::from_utf8_lossy(&output.stdout).trim().to_string(),
+        Err(_) => {
+            print_claude_not_found_error();
+            exit(EXIT_CLAUDE_NOT_FOUND);
+        }
+    };
+    verbose_log(&format!("claude version: {} (minimum {})", installed, required));
+    match parse_version(&installed) {
+        Some(version) if version >= minimum => {}
+        Some(_) => {
+            eprintln!("Error: claude {} is older than min_claude_version {}", installed, required);
+            eprintln!("Update Claude, or pass --skip-version-check to launch anyway.");
+            exit(EXIT_CLAUDE_TOO_OLD);
+        }
+        None => {
+            eprintln!("Error: could not read the claude version from '{}'", installed);
+            eprintln!("Pass --skip-version-check to launch anyway.");
+            exit(EXIT_CLAUDE_TOO_OLD);
+        }
+    }
+}
 
-    // Append passthrough args
-    claude_args.extend(passthrough_args);
+/// How claude is started, from CS_CLAUDE_LAUNCHER or `claude_launcher`
+/// (e.g. `npx @anthropic-ai/claude-code`); unset means the `claude` binary
+static CLAUDE_LAUNCHER: OnceLock<Vec<String>> = OnceLock::new();
 
-    // Launch claude (platform-specific)
-    launch_claude_owned(claude_args);
+/// The launcher's program and leading arguments (`["claude"]` by default)
+fn claude_launcher() -> Vec<String> {
+    CLAUDE_LAUNCHER
+        .get()
+        .filter(|launcher| !launcher.is_empty())
+        .cloned()
+        .unwrap_or_else(|| vec!["claude".to_string()])
+}
+
+/// The full command line for running claude with `args`: the launcher's
+/// tokens first, then the computed arguments
+fn launcher_argv<S: AsRef<str>>(launcher: &[String], args: &[S]) -> Vec<String> {
+    launcher
+        .iter()
+        .cloned()
+        .chain(args.iter().map(|arg| arg.as_ref().to_string()))
+        .collect()
+}
+
+/// A `Command` running claude with `args` through the launcher
+fn claude_command<S: AsRef<str>>(args: &[S]) -> Command {
+    let argv = launcher_argv(&claude_launcher(), args);
+    let mut command = Command::new(&argv[0]);
+    command.args(&argv[1..]);
+    command
 }
 
-/// Check if claude CLI is installed
+/// Check if claude CLI (or the launcher that runs it) is installed
 fn check_claude_installed() -> bool {
+    let program = claude_launcher().swap_remove(0);
     #[cfg(windows)]
-    let check_cmd = Command::new("where").arg("claude").output();
+    let check_cmd = Command::new("where").arg(&program).output();
     #[cfg(not(windows))]
-    let check_cmd = Command::new("which").arg("claude").output();
+    let check_cmd = Command::new("which").arg(&program).output();
 
     match check_cmd {
         Ok(output) => output.status.success(),
@@ -682,6 +6290,22 @@ fn check_claude_installed() -> bool {
     }
 }
 
+/// Describe a failure to start claude. An argument list over the OS limit
+/// (E2BIG) gets a hint instead of the bare OS error.
+fn launch_error_message(err: &std::io::Error, args: &[String]) -> String {
+    if err.kind() != std::io::ErrorKind::ArgumentListTooLong {
+        return format!("Error launching claude: {}", err);
+    }
+    let bytes: usize = args.iter().map(|arg| arg.len() + 1).sum();
+    format!(
+        "Error launching claude: the argument list is too long for the OS ({} arguments, {} bytes).\n\
+         Pass large prompts on stdin instead (e.g. `cs -p < prompt.txt`) or reference\n\
+         files with --file rather than inlining their contents.",
+        args.len(),
+        bytes
+    )
+}
+
 /// Print error message when Claude CLI is not found
 fn print_claude_not_found_error() {
     eprintln!("Error: Claude CLI not found in PATH");
@@ -701,88 +6325,465 @@ fn launch_claude(args: &[&str]) -> ! {
     // Check if claude exists before replacing the process
     if !check_claude_installed() {
         print_claude_not_found_error();
-        exit(127);
+        exit(EXIT_CLAUDE_NOT_FOUND);
     }
 
-    let err = Command::new("claude").args(args).exec();
+    let err = claude_command(args).exec();
 
     // If we get here, the exec call failed
     if err.kind() == std::io::ErrorKind::NotFound {
         print_claude_not_found_error();
-        exit(127);
+        exit(EXIT_CLAUDE_NOT_FOUND);
     }
 
     eprintln!("Error launching claude: {}", err);
-    exit(1);
+    exit(EXIT_ERROR);
 }
 
 /// Launch claude with owned String arguments (Unix version)
-/// Uses exec() to replace the current process - args are passed as array, not shell string
+/// Uses exec() to replace the current process - args are passed as array, not shell string.
+/// `--timeout` can't be enforced once cs is replaced, so it is ignored here.
 #[cfg(unix)]
-fn launch_claude_owned(args: Vec<String>) -> ! {
+fn launch_claude_owned(args: Vec<String>, _timeout: Option<Duration>) -> ! {
     // Check if claude exists before replacing the process
     if !check_claude_installed() {
         print_claude_not_found_error();
-        exit(127);
+        exit(EXIT_CLAUDE_NOT_FOUND);
     }
 
-    let err = Command::new("claude").args(&args).exec();
+    record_claude_args(&args);
+    forget_exit_code();
+    let err = claude_command(&args).exec();
 
     // If we get here, the exec call failed
     if err.kind() == std::io::ErrorKind::NotFound {
         print_claude_not_found_error();
-        exit(127);
+        exit(EXIT_CLAUDE_NOT_FOUND);
     }
 
-    eprintln!("Error launching claude: {}", err);
-    exit(1);
+    eprintln!("{}", launch_error_message(&err, &args));
+    exit(EXIT_ERROR);
+}
+
+/// Wait for a child process, killing it if it outlives `timeout`.
+/// Returns None if the child was killed because of the timeout.
+fn wait_with_timeout(
+    child: &mut Child,
+    timeout: Option<Duration>,
+) -> std::io::Result<Option<ExitStatus>> {
+    let Some(timeout) = timeout else {
+        return child.wait().map(Some);
+    };
+
+    let deadline = Instant::now() + timeout;
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(Some(status));
+        }
+        if Instant::now() >= deadline {
+            child.kill()?;
+            child.wait()?;
+            return Ok(None);
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    }
+}
+
+/// Two Ctrl-C exits of claude this close together end `--watch`
+const WATCH_INTERRUPT_WINDOW: Duration = Duration::from_secs(2);
+
+/// Pause before a `--watch` relaunch, long enough to press Ctrl-C and stop cs.
+/// Doubles with each fast failure in a row.
+const WATCH_RELAUNCH_DELAY: Duration = Duration::from_secs(1);
+
+/// A failed claude run shorter than this counts as a fast failure for `--watch`
+const WATCH_FAST_FAILURE: Duration = Duration::from_secs(10);
+
+/// `--watch` gives up after this many fast failures in a row (e.g. a stale
+/// session or an auth error that fails on every launch)
+const WATCH_MAX_FAST_FAILURES: u32 = 5;
+
+/// Exit code of a process stopped by Ctrl-C (128 + SIGINT)
+const EXIT_INTERRUPTED: i32 = 130;
+
+/// Decide whether `--watch` should stop after claude exited with `code` at `now`.
+/// Stops on the second interrupted exit within WATCH_INTERRUPT_WINDOW;
+/// `last_interrupt` remembers when the previous one happened.
+fn watch_should_stop(code: i32, now: Instant, last_interrupt: &mut Option<Instant>) -> bool {
+    if code != EXIT_INTERRUPTED {
+        *last_interrupt = None;
+        return false;
+    }
+    if let Some(prev) = last_interrupt.replace(now) {
+        return now.duration_since(prev) <= WATCH_INTERRUPT_WINDOW;
+    }
+    false
+}
+
+/// What `--watch` does after claude exits
+#[derive(Debug, PartialEq)]
+enum WatchStep {
+    /// Stop watching, for the given reason
+    Stop(String),
+    /// Relaunch claude after this pause
+    Relaunch(Duration),
+}
+
+/// What `--watch` remembers between claude runs
+#[derive(Debug, Default)]
+struct WatchState {
+    last_interrupt: Option<Instant>,
+    fast_failures: u32,
+}
+
+/// Decide what `--watch` does after claude exited with `code` at `now`,
+/// having run for `ran_for`. A normal exit (e.g. `/exit`) or a timeout stops;
+/// failures are relaunched with a growing pause, giving up after
+/// WATCH_MAX_FAST_FAILURES fast ones in a row.
+fn watch_next_step(code: i32, ran_for: Duration, now: Instant, state: &mut WatchState) -> WatchStep {
+    if code == 0 {
+        return WatchStep::Stop("claude exited normally".to_string());
+    }
+    if code == EXIT_TIMEOUT {
+        return WatchStep::Stop("claude exceeded --timeout".to_string());
+    }
+    if watch_should_stop(code, now, &mut state.last_interrupt) {
+        return WatchStep::Stop("Interrupted twice".to_string());
+    }
+    if ran_for < WATCH_FAST_FAILURE {
+        state.fast_failures += 1;
+    } else {
+        state.fast_failures = 0;
+    }
+    if state.fast_failures >= WATCH_MAX_FAST_FAILURES {
+        return WatchStep::Stop(format!("claude failed within {}s {} times in a row", WATCH_FAST_FAILURE.as_secs(), state.fast_failures));
+    }
+    WatchStep::Relaunch(WATCH_RELAUNCH_DELAY * 2u32.pow(state.fast_failures))
+}
+
+/// Run claude as a child process and wait for it, returning its exit code
+/// (for the modes that keep cs around instead of exec-ing claude).
+/// A run longer than `timeout` is stopped and reported as EXIT_TIMEOUT.
+fn spawn_claude_and_wait(args: &[String], timeout: Option<Duration>) -> i32 {
+    run_claude_child(args, timeout, false).0
+}
+
+/// Run claude like `spawn_claude_and_wait`, also returning the start of its
+/// stderr (still shown as it arrives) so a failure can be recognized
+fn spawn_claude_capturing_stderr(args: &[String], timeout: Option<Duration>) -> (i32, String) {
+    run_claude_child(args, timeout, true)
+}
+
+/// How much of claude's stderr is kept for `missing_session_stderr` matching
+const STDERR_CAPTURE_LIMIT: usize = 64 * 1024;
+
+fn run_claude_child(args: &[String], timeout: Option<Duration>, capture_stderr: bool) -> (i32, String) {
+    verbose_log(&format!("spawn: claude {}", args.join(" ")));
+    record_claude_args(args);
+    let mut command = claude_command(args);
+    if capture_stderr {
+        command.stderr(std::process::Stdio::piped());
+    }
+    let result = command.spawn().and_then(|mut child| {
+        let tee = child.stderr.take().map(|stderr| std::thread::spawn(move || tee_stderr(stderr)));
+        let status = wait_with_timeout(&mut child, timeout)?;
+        let captured = tee.and_then(|tee| tee.join().ok()).unwrap_or_default();
+        Ok((status, captured))
+    });
+    match result {
+        Ok((Some(status), captured)) => {
+            let code = child_exit_code(status);
+            record_exit_code(code);
+            (code, captured)
+        }
+        Ok((None, captured)) => {
+            eprintln!("Error: claude exceeded the {}s timeout and was stopped", timeout.unwrap_or_default().as_secs());
+            record_exit_code(EXIT_TIMEOUT);
+            (EXIT_TIMEOUT, captured)
+        }
+        Err(e) => {
+            if e.kind() == std::io::ErrorKind::NotFound {
+                print_claude_not_found_error();
+                exit(EXIT_CLAUDE_NOT_FOUND);
+            }
+            eprintln!("{}", launch_error_message(&e, args));
+            exit(EXIT_ERROR);
+        }
+    }
+}
+
+/// Copy a child's stderr to ours as it arrives, returning the first
+/// STDERR_CAPTURE_LIMIT bytes of it
+fn tee_stderr(mut stderr: impl std::io::Read) -> String {
+    let mut captured = Vec::new();
+    let mut buf = [0u8; 4096];
+    while let Ok(n) = stderr.read(&mut buf) {
+        if n == 0 {
+            break;
+        }
+        let _ = std::io::stderr().write_all(&buf[..n]);
+        if captured.len() < STDERR_CAPTURE_LIMIT {
+            captured.extend_from_slice(&buf[..n]);
+        }
+    }
+    String::from_utf8_lossy(&captured).into_owned()
+}
+
+/// How to recognize Claude failing to resume a conversation it doesn't have
+/// (`missing_session_stderr` / `missing_session_exit_code` config)
+struct MissingSessionRule {
+    stderr: String,
+    exit_code: Option<i32>,
+}
+
+impl MissingSessionRule {
+    fn from_config(config: &Config) -> Self {
+        MissingSessionRule {
+            stderr: config.missing_session_stderr().to_string(),
+            exit_code: config.missing_session_exit_code(),
+        }
+    }
+
+    /// Check a failed run against every configured criterion
+    fn matches(&self, code: i32, stderr: &str) -> bool {
+        if code == 0 || code == EXIT_TIMEOUT || (self.stderr.is_empty() && self.exit_code.is_none()) {
+            return false;
+        }
+        let code_matches = self.exit_code.is_none_or(|expected| code == expected);
+        let stderr_matches = self.stderr.is_empty() || stderr.contains(&self.stderr);
+        code_matches && stderr_matches
+    }
+}
+
+/// A session cs runs as a child process (Windows, `--watch`, `--resume-all`),
+/// recreated if Claude turns out not to have its conversation
+struct SessionRecovery {
+    uuid: String,
+    name: Option<String>,
+    rule: MissingSessionRule,
+}
+
+impl SessionRecovery {
+    fn new(uuid: &str, name: &str, config: &Config) -> Self {
+        SessionRecovery {
+            uuid: uuid.to_string(),
+            name: Some(name.to_string()).filter(|n| !n.is_empty()),
+            rule: MissingSessionRule::from_config(config),
+        }
+    }
+
+    /// Run claude with `args` and wait for it. If they resume the session and
+    /// the run fails as `rule` describes, drop the stale database entry and
+    /// start the conversation with `--session-id` instead. Returns the exit
+    /// code of the last run.
+    fn run(&self, args: &[String], timeout: Option<Duration>) -> i32 {
+        let resume_args = LaunchMode::Resume.session_args(&self.uuid);
+        if !args.starts_with(&resume_args) {
+            return spawn_claude_and_wait(args, timeout);
+        }
+        let (code, stderr) = spawn_claude_capturing_stderr(args, timeout);
+        if !self.rule.matches(code, &stderr) {
+            return code;
+        }
+        eprintln!("cs: Claude has no conversation {}; starting it as a new session", self.uuid);
+        remove_session(&self.uuid);
+        save_record(&SessionRecord::new(&self.uuid, self.name.as_deref()).stamped(unix_now()));
+        let mut create_args = LaunchMode::Create.session_args(&self.uuid);
+        create_args.extend(args[resume_args.len()..].iter().cloned());
+        spawn_claude_and_wait(&create_args, timeout)
+    }
+}
+
+/// What to do with the next session in `--resume-all`
+#[derive(Debug, PartialEq)]
+enum ResumeAllChoice {
+    Open,
+    Skip,
+    Quit,
+}
+
+/// Read a `--resume-all` answer: empty or `o` opens, `s` skips, `q` quits
+fn parse_resume_all_choice(answer: &str) -> Option<ResumeAllChoice> {
+    match answer.trim().to_ascii_lowercase().as_str() {
+        "" | "o" | "open" => Some(ResumeAllChoice::Open),
+        "s" | "skip" => Some(ResumeAllChoice::Skip),
+        "q" | "quit" => Some(ResumeAllChoice::Quit),
+        _ => None,
+    }
+}
+
+/// Ask what to do with the next `--resume-all` session, asking again on an
+/// unrecognized answer. Without a terminal on stdin every session is opened.
+fn ask_resume_all_choice(position: usize, total: usize, name: &str) -> ResumeAllChoice {
+    use std::io::IsTerminal;
+    if !std::io::stdin().is_terminal() {
+        return ResumeAllChoice::Open;
+    }
+    loop {
+        eprint!("Next ({}/{}): {}. [Enter] open, [s]kip, [q]uit: ", position, total, name);
+        let _ = std::io::stderr().flush();
+        let mut input = String::new();
+        if std::io::stdin().read_line(&mut input).unwrap_or(0) == 0 {
+            return ResumeAllChoice::Quit;
+        }
+        if let Some(choice) = parse_resume_all_choice(&input) {
+            return choice;
+        }
+    }
+}
+
+/// Resume every tracked session of the current repository in turn, most
+/// recently used first (`--resume-all`). Claude looks conversations up by
+/// project directory, so other repositories' sessions can't be resumed from
+/// here. Each runs as a child process with the usual default args and
+/// session lock. Claude handles Ctrl-C itself and exits normally, so its exit
+/// code can't tell a quit from an interrupt; `q` or Ctrl-C at the prompt
+/// between sessions ends the sequence instead.
+fn resume_all(config: &Config, passthrough_args: &[String]) {
+    let folders = repo_folders(&env::current_dir().unwrap_or_default());
+    let mut records: Vec<SessionRecord> = load_records()
+        .into_iter()
+        .filter(|r| record_in_project(r, &folders, config.separator()))
+        .collect();
+    if records.is_empty() {
+        eprintln!("No sessions for {} in the database.", folders.join(" or "));
+        return;
+    }
+    sort_by_recency(&mut records);
+    if is_dry_run() {
+        for record in &records {
+            eprintln!("Would resume {} ({})", record.name.as_deref().unwrap_or_default(), record.uuid);
+        }
+        return;
+    }
+    if !check_claude_installed() {
+        print_claude_not_found_error();
+        exit(EXIT_CLAUDE_NOT_FOUND);
+    }
+
+    let total = records.len();
+    for (index, record) in records.iter().enumerate() {
+        let name = record.name.as_deref().unwrap_or("(unnamed)");
+        match ask_resume_all_choice(index + 1, total, name) {
+            ResumeAllChoice::Open => {}
+            ResumeAllChoice::Skip => continue,
+            ResumeAllChoice::Quit => return,
+        }
+        if config.session_lock() {
+            if let Err(e) = acquire_session_lock(&record.uuid) {
+                eprintln!("Skipping {}: {}", name, e);
+                continue;
+            }
+        }
+        eprintln!("Resuming {} ({})...", name, record.uuid);
+        touch_session(&record.uuid);
+        let mut args = LaunchMode::Resume.session_args(&record.uuid);
+        args.extend(passthrough_args.iter().cloned());
+        let recovery = SessionRecovery {
+            uuid: record.uuid.clone(),
+            name: record.name.clone(),
+            rule: MissingSessionRule::from_config(config),
+        };
+        recovery.run(&args, None);
+        release_session_lock(&record.uuid);
+    }
+}
+
+/// Run claude with `first_args`, then keep relaunching it with `relaunch_args`
+/// every time it fails (`--watch`); see `watch_next_step`. Uses spawn-and-wait
+/// on every platform, so `timeout` applies to each run and a resume of a
+/// conversation Claude lacks is recovered. Exits with claude's last exit code
+/// once the loop stops (128 + signal if it was killed by one, as with exec()).
+fn watch_claude(
+    first_args: Vec<String>,
+    relaunch_args: Vec<String>,
+    timeout: Option<Duration>,
+    recovery: &SessionRecovery,
+) -> ! {
+    if !check_claude_installed() {
+        print_claude_not_found_error();
+        exit(EXIT_CLAUDE_NOT_FOUND);
+    }
+
+    let mut args = first_args;
+    let mut state = WatchState::default();
+    loop {
+        let started = Instant::now();
+        let code = recovery.run(&args, timeout);
+        match watch_next_step(code, started.elapsed(), Instant::now(), &mut state) {
+            WatchStep::Stop(reason) => {
+                eprintln!("{}, stopping watch.", reason);
+                exit(code);
+            }
+            // Claude has exited, so Ctrl-C here just ends cs
+            WatchStep::Relaunch(delay) => {
+                eprintln!("claude exited ({}), relaunching in {}s... (Ctrl-C to stop)", code, delay.as_secs());
+                std::thread::sleep(delay);
+            }
+        }
+        args = relaunch_args.clone();
+    }
 }
 
 /// Launch claude with the given arguments (Windows version - spawns child process)
 #[cfg(windows)]
 #[allow(dead_code)]
 fn launch_claude(args: &[&str]) -> ! {
-    match Command::new("claude").args(args).spawn() {
+    match claude_command(args).spawn() {
         Ok(mut child) => {
             match child.wait() {
-                Ok(status) => exit(status.code().unwrap_or(0)),
+                Ok(status) => exit(child_exit_code(status)),
                 Err(e) => {
                     eprintln!("Error waiting for claude: {}", e);
-                    exit(1);
+                    exit(EXIT_ERROR);
                 }
             }
         }
         Err(e) => {
             if e.kind() == std::io::ErrorKind::NotFound {
                 print_claude_not_found_error();
-                exit(127);
+                exit(EXIT_CLAUDE_NOT_FOUND);
             }
             eprintln!("Error launching claude: {}", e);
-            exit(1);
+            exit(EXIT_ERROR);
         }
     }
 }
 
 /// Launch claude with owned String arguments (Windows version)
+/// Kills claude and exits with EXIT_TIMEOUT if it runs longer than `timeout`
 #[cfg(windows)]
-fn launch_claude_owned(args: Vec<String>) -> ! {
-    match Command::new("claude").args(&args).spawn() {
+fn launch_claude_owned(args: Vec<String>, timeout: Option<Duration>) -> ! {
+    record_claude_args(&args);
+    match claude_command(&args).spawn() {
         Ok(mut child) => {
-            match child.wait() {
-                Ok(status) => exit(status.code().unwrap_or(0)),
+            match wait_with_timeout(&mut child, timeout) {
+                Ok(Some(status)) => {
+                    let code = child_exit_code(status);
+                    record_exit_code(code);
+                    exit(code);
+                }
+                Ok(None) => {
+                    eprintln!(
+                        "Error: claude exceeded the {}s timeout and was stopped",
+                        timeout.unwrap_or_default().as_secs()
+                    );
+                    record_exit_code(EXIT_TIMEOUT);
+                    exit(EXIT_TIMEOUT);
+                }
                 Err(e) => {
                     eprintln!("Error waiting for claude: {}", e);
-                    exit(1);
+                    exit(EXIT_ERROR);
                 }
             }
         }
         Err(e) => {
             if e.kind() == std::io::ErrorKind::NotFound {
                 print_claude_not_found_error();
-                exit(127);
+                exit(EXIT_CLAUDE_NOT_FOUND);
             }
-            eprintln!("Error launching claude: {}", e);
-            exit(1);
+            eprintln!("{}", launch_error_message(&e, &args));
+            exit(EXIT_ERROR);
         }
     }
 }
@@ -67,6 +67,42 @@ fn test_uuid5_known_value() {
     assert_eq!(uuid, "afe19c61-d53f-581c-985c-56e9daf4e63d");
 }
 
+#[test]
+fn test_uuid5_with_explicit_namespace() {
+    let name = "claude-code-resumer+main";
+    assert_eq!(generate_uuid5_with(name, &DEFAULT_NAMESPACE), "afe19c61-d53f-581c-985c-56e9daf4e63d");
+
+    let url = well_known_namespace("url").unwrap();
+    let in_url = generate_uuid5_with(name, &url);
+    assert_ne!(in_url, generate_uuid5_with(name, &DEFAULT_NAMESPACE));
+    assert_eq!(in_url, generate_uuid5_with(name, &url), "deterministic per namespace");
+    assert_eq!(in_url.chars().nth(14), Some('5'));
+}
+
+#[test]
+#[serial]
+fn test_generate_uuid5_uses_configured_namespace() {
+    let custom = "12345678-1234-1234-1234-123456789012";
+    std::env::set_var("CS_NAMESPACE", custom);
+    let uuid = generate_uuid5("app+main");
+    std::env::remove_var("CS_NAMESPACE");
+    assert_eq!(uuid, generate_uuid5_with("app+main", &parse_uuid(custom).unwrap()));
+}
+
+#[test]
+fn test_self_test_checks() {
+    assert_eq!(self_test_uuid(), Ok(format!("{} -> {}", SELF_TEST_VECTOR.0, SELF_TEST_VECTOR.1)));
+
+    let dir = TempDir::new().unwrap();
+    let db_path = dir.path().join("nested/sessions");
+    assert!(self_test_db_dir(&db_path).is_ok());
+    assert_eq!(std::fs::read_dir(dir.path().join("nested")).unwrap().count(), 0, "scratch file is removed");
+
+    let blocker = dir.path().join("file");
+    std::fs::write(&blocker, "").unwrap();
+    assert!(self_test_db_dir(&blocker.join("sessions")).unwrap_err().starts_with("cannot create"));
+}
+
 #[test]
 fn test_uuid5_special_characters() {
     let uuid1 = generate_uuid5("project+feature/auth");
@@ -92,6 +128,148 @@ fn test_uuid5_empty_components() {
 // UUID parsing tests (no env var dependencies)
 // ============================================================================
 
+#[test]
+fn test_derive_namespace_stable() {
+    // Matches Python's uuid.uuid5(uuid.UUID("b835a6ba-aa0e-5de4-9a78-e6506e427d0d"), "acme-team")
+    assert_eq!(format_uuid(&derive_namespace("acme-team")), "6af6e36e-524a-5a14-b65a-c63931e1f941");
+    assert_eq!(derive_namespace("acme-team"), derive_namespace("acme-team"));
+    assert_ne!(derive_namespace("acme-team"), derive_namespace("other-team"));
+}
+
+#[test]
+fn test_namespace_from_args() {
+    let args = |list: &[&str]| list.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+    let explicit = "11111111-1111-1111-1111-111111111111";
+
+    assert_eq!(namespace_from_args(&args(&["cs", "--model", "opus"])), Ok(None));
+    assert_eq!(
+        namespace_from_args(&args(&["cs", "--namespace-name", "acme-team"])),
+        Ok(Some(derive_namespace("acme-team")))
+    );
+    assert_eq!(
+        namespace_from_args(&args(&["cs", "--namespace-name", "acme-team", "--namespace", explicit])),
+        Ok(parse_uuid(explicit)),
+        "explicit UUID wins"
+    );
+    assert!(namespace_from_args(&args(&["cs", "--namespace", "not-a-uuid"])).is_err());
+    assert!(namespace_from_args(&args(&["cs", "--namespace-name"])).is_err());
+}
+
+#[test]
+fn test_well_known_namespaces_match_rfc4122() {
+    let cases = [
+        ("dns", "6ba7b810-9dad-11d1-80b4-00c04fd430c8"),
+        ("url", "6ba7b811-9dad-11d1-80b4-00c04fd430c8"),
+        ("oid", "6ba7b812-9dad-11d1-80b4-00c04fd430c8"),
+        ("x500", "6ba7b814-9dad-11d1-80b4-00c04fd430c8"),
+    ];
+    for (name, uuid) in cases {
+        assert_eq!(well_known_namespace(name), parse_uuid(uuid), "{}", name);
+        assert_eq!(
+            namespace_from_args(&[String::from("cs"), String::from("--namespace"), name.to_uppercase()]),
+            Ok(parse_uuid(uuid)),
+            "{}",
+            name
+        );
+    }
+    assert_eq!(well_known_namespace("dns"), Some(DEFAULT_NAMESPACE));
+    assert_eq!(well_known_namespace("x.500"), None);
+}
+
+#[test]
+fn test_version_line() {
+    let version = env!("CS_VERSION");
+    assert_eq!(version_line(true), version);
+    assert_eq!(version_line(false), format!("cs {}", version));
+}
+
+#[test]
+fn test_help_lists_every_cs_flag_and_subcommand() {
+    let help = render_help(&help_sections(), false);
+    // Whole tokens, so e.g. --no-git doesn't count as --no
+    let words: Vec<&str> = help.split(|c: char| !(c.is_ascii_alphanumeric() || c == '-' || c == '_')).collect();
+    for name in CS_FLAGS.iter().chain(CS_SUBCOMMANDS).chain(CLAUDE_SUBCOMMANDS) {
+        assert!(words.contains(name), "{} missing from --help", name);
+    }
+    for (name, _) in CS_ENV_VARS {
+        assert!(words.contains(name), "{} missing from --help", name);
+    }
+    assert!(!help.contains('\x1b'), "no color when piped");
+    assert!(render_help(&help_sections(), true).contains("\x1b[1;33mSESSIONS:\x1b[0m"));
+}
+
+#[test]
+fn test_full_help_lists_every_claude_flag_and_subcommand() {
+    let help = render_help(&full_help_sections(), false);
+    let words: Vec<&str> = help.split(|c: char| c.is_whitespace() || c == ',').collect();
+    let constants = CLAUDE_BOOL_FLAGS
+        .iter()
+        .chain(CLAUDE_VALUE_FLAGS)
+        .chain(CLAUDE_SUBCOMMANDS)
+        .chain(CS_SUBCOMMANDS);
+    for name in constants {
+        assert!(words.contains(name), "{} missing from --help --full", name);
+    }
+    assert!(help.contains("-c, --continue"));
+    assert!(help.contains("one of: opus, sonnet, haiku"));
+}
+
+#[test]
+fn test_render_help_aligns_columns() {
+    let sections = [HelpSection {
+        title: "DEMO",
+        rows: vec![
+            help_row("-a", "First"),
+            help_row("--longer <x>", "Second\ncontinued"),
+            help_row("", "Prose"),
+        ],
+    }];
+    assert_eq!(
+        render_help(&sections, false),
+        "cs - Claude Code Session Manager\n\n\
+         DEMO:\n\
+         \x20   -a            First\n\
+         \x20   --longer <x>  Second\n\
+         \x20                 continued\n\
+         \x20   Prose\n\n"
+    );
+}
+
+#[test]
+fn test_color_choice() {
+    let args = |list: &[&str]| list.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+    assert_eq!(color_from_args(&args(&["cs", "--help"])), Ok(None));
+    assert_eq!(color_from_args(&args(&["cs", "--color", "never", "--help"])), Ok(Some(ColorChoice::Never)));
+    assert!(color_from_args(&args(&["cs", "--color", "sometimes"])).is_err());
+    assert!(color_from_args(&args(&["cs", "--color"])).is_err());
+    assert_eq!(color_from_args(&args(&["cs", "--color=always", "--help"])), Ok(Some(ColorChoice::Always)));
+    assert!(color_from_args(&args(&["cs", "--color="])).is_err());
+    assert_eq!(color_from_args(&args(&["cs", "--colorful"])), Ok(None));
+
+    // (choice, terminal, NO_COLOR, TERM=dumb) -> color
+    assert!(should_color(ColorChoice::Auto, true, false, false));
+    assert!(!should_color(ColorChoice::Auto, false, false, false), "auto: not when piped");
+    assert!(!should_color(ColorChoice::Auto, true, true, false), "auto: NO_COLOR turns it off");
+    assert!(!should_color(ColorChoice::Auto, true, false, true), "auto: not on a dumb terminal");
+    assert!(should_color(ColorChoice::Always, false, true, true), "always beats piping and NO_COLOR");
+    assert!(!should_color(ColorChoice::Never, true, false, false));
+}
+
+#[test]
+fn test_batch_uuids() {
+    let content = "# migration list\napp+main\n\n  lib+dev  \n";
+    assert_eq!(
+        batch_uuids(content, &DEFAULT_NAMESPACE),
+        vec![
+            ("app+main".to_string(), generate_uuid5_with("app+main", &DEFAULT_NAMESPACE)),
+            ("lib+dev".to_string(), generate_uuid5_with("lib+dev", &DEFAULT_NAMESPACE)),
+        ]
+    );
+    let url = well_known_namespace("url").unwrap();
+    assert_eq!(batch_uuids("app+main", &url), vec![("app+main".to_string(), generate_uuid5_with("app+main", &url))]);
+    assert!(batch_uuids("\n# only comments\n", &DEFAULT_NAMESPACE).is_empty());
+}
+
 #[test]
 fn test_parse_uuid_valid() {
     let result = parse_uuid("6ba7b810-9dad-11d1-80b4-00c04fd430c8");
@@ -113,13 +291,34 @@ fn test_parse_uuid_invalid() {
     assert!(parse_uuid("zzzzzzzz-zzzz-zzzz-zzzz-zzzzzzzzzzzz").is_none());
 }
 
+#[test]
+fn test_parse_uuid_strict() {
+    let canonical = "6ba7b810-9dad-11d1-80b4-00c04fd430c8";
+    assert_eq!(parse_uuid_strict(canonical), parse_uuid(canonical));
+    assert_eq!(
+        parse_uuid_strict("6ba7b8109dad11d180b400c04fd430c8"),
+        parse_uuid(canonical)
+    );
+
+    // Lenient parsing accepts any layout with 32 hex digits; strict does not
+    let malformed = "6ba7-b810..9dad11d1-80b4-00c04fd430c8";
+    assert!(parse_uuid(malformed).is_some());
+    assert!(parse_uuid_strict(malformed).is_none());
+    assert!(parse_uuid_strict("6ba7b8109-dad-11d1-80b4-00c04fd430c8").is_none());
+    assert!(parse_uuid_strict("{6ba7b810-9dad-11d1-80b4-00c04fd430c8}").is_none());
+    assert!(parse_uuid_strict("6ba7b810-9dad-11d1-80b4-00c04fd430cg").is_none());
+
+    let args = |list: &[&str]| list.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+    assert!(namespace_from_args(&args(&["cs", "--namespace", malformed])).is_err());
+}
+
 // ============================================================================
 // Folder name test (no env var dependencies)
 // ============================================================================
 
 #[test]
 fn test_get_folder_name() {
-    let result = get_folder_name();
+    let result = get_folder_name_in(&std::env::current_dir().unwrap());
     assert!(result.is_ok());
     assert!(!result.unwrap().is_empty());
 }
@@ -165,6 +364,15 @@ fn test_get_namespace_custom() {
     std::env::remove_var("CS_NAMESPACE");
 }
 
+#[test]
+#[serial]
+fn test_get_namespace_well_known_name() {
+    std::env::set_var("CS_NAMESPACE", "url");
+    let ns = get_namespace();
+    assert_eq!(format_uuid(&ns), "6ba7b811-9dad-11d1-80b4-00c04fd430c8");
+    std::env::remove_var("CS_NAMESPACE");
+}
+
 // ============================================================================
 // Session database tests (use isolated temp dirs, must run serially)
 // ============================================================================
@@ -250,3 +458,2396 @@ fn test_session_remove_nonexistent() {
     let sessions = load_sessions();
     assert!(sessions.is_empty());
 }
+
+// ============================================================================
+// Shell integration tests (no env var dependencies)
+// ============================================================================
+
+#[test]
+fn test_completion_script_supported_shells() {
+    for shell in SUPPORTED_SHELLS {
+        let script = completion_script(shell).expect("supported shell should have a script");
+        assert!(script.contains("force"), "{} completions should offer cs flags", shell);
+        assert!(script.contains("model"), "{} completions should offer Claude flags", shell);
+        assert!(script.contains("upgrade"), "{} completions should offer subcommands", shell);
+    }
+}
+
+#[test]
+fn test_completion_script_unknown_shell() {
+    assert!(completion_script("powershell").is_none());
+    assert!(completion_script("").is_none());
+}
+
+#[test]
+fn test_init_script_sources_completions() {
+    for shell in SUPPORTED_SHELLS {
+        let script = init_script(shell).expect("supported shell should have an init block");
+        assert!(script.contains(&format!("cs --completions {}", shell)));
+        for (name, _) in CS_ENV_VARS {
+            assert!(script.contains(name), "{} init should document {}", shell, name);
+        }
+    }
+    assert!(init_script("tcsh").is_none());
+}
+
+// ============================================================================
+// Config parsing tests (no env var dependencies)
+// ============================================================================
+
+#[test]
+fn test_parse_config_values() {
+    let content = r#"
+# cs config
+separator = "::"   # trailing comment
+enabled = true
+retries = 3
+dirs = ["a", 'b#c']
+
+[prompts]
+review = "Review this \"diff\""
+"#;
+    let config = config::parse_config(content).expect("config should parse");
+    assert_eq!(config.separator(), "::");
+    assert_eq!(config.values.get("enabled"), Some(&config::ConfigValue::Bool(true)));
+    assert_eq!(config.values.get("retries"), Some(&config::ConfigValue::Integer(3)));
+    assert_eq!(
+        config.values.get("dirs"),
+        Some(&config::ConfigValue::Array(vec!["a".to_string(), "b#c".to_string()]))
+    );
+    assert_eq!(config.get_str("prompts.review"), Some("Review this \"diff\""));
+}
+
+#[test]
+fn test_parse_config_errors() {
+    assert!(config::parse_config("separator").is_err());
+    assert!(config::parse_config("separator = \"unterminated").is_err());
+    assert!(config::parse_config("separator = bare").is_err());
+}
+
+#[test]
+#[serial]
+fn test_load_user_config_malformed() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("config.toml");
+    std::fs::write(&path, "separator = \"+\"\nbroken line\n").unwrap();
+
+    std::env::remove_var("CS_STRICT_CONFIG");
+    assert_eq!(load_user_config(&path), Ok(Config::default()));
+
+    std::env::set_var("CS_STRICT_CONFIG", "1");
+    let err = load_user_config(&path).unwrap_err();
+    std::env::remove_var("CS_STRICT_CONFIG");
+    assert!(err.contains("config.toml"), "error should name the file: {}", err);
+    assert!(err.contains("line 2"), "error should give the cause: {}", err);
+}
+
+#[test]
+#[serial]
+fn test_load_user_config_unreadable() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("config.toml");
+    std::fs::write(&path, "separator = \"-\"\n").unwrap();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o000)).unwrap();
+    }
+    if std::fs::read_to_string(&path).is_ok() {
+        // Permissions aren't enforced (e.g. running as root); use a directory instead
+        std::fs::remove_file(&path).unwrap();
+        std::fs::create_dir(&path).unwrap();
+    }
+
+    std::env::remove_var("CS_STRICT_CONFIG");
+    assert_eq!(load_user_config(&path), Ok(Config::default()));
+
+    std::env::set_var("CS_STRICT_CONFIG", "1");
+    assert!(load_user_config(&path).is_err());
+    std::env::remove_var("CS_STRICT_CONFIG");
+}
+
+#[test]
+fn test_load_config_rejects_empty_separator() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("config.toml");
+    std::fs::write(&path, "separator = \"\"\n").unwrap();
+    assert_eq!(load_config(&path, false).unwrap_err(), "separator: must not be empty");
+
+    std::fs::write(&path, "separator = \"::\"\n").unwrap();
+    assert_eq!(load_config(&path, false).unwrap().separator(), "::");
+}
+
+#[test]
+#[serial]
+fn test_config_path_override() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("custom.toml");
+    std::fs::write(&path, "separator = \"::\"\nbackup_retention = 3\n").unwrap();
+
+    std::env::set_var("CS_CONFIG_PATH", &path);
+    assert_eq!(get_config_path(), path);
+    let config = load_user_config(&get_config_path()).unwrap();
+    std::env::remove_var("CS_CONFIG_PATH");
+
+    assert_eq!(config.separator(), "::");
+    assert_eq!(config.backup_retention(), 3);
+}
+
+#[test]
+#[serial]
+fn test_config_path_xdg() {
+    let temp_dir = TempDir::new().unwrap();
+    std::env::remove_var("CS_CONFIG_PATH");
+    std::env::set_var("XDG_CONFIG_HOME", temp_dir.path());
+
+    // Only used when the file exists
+    assert!(get_config_path().ends_with(".cs/config.toml"));
+
+    let xdg_path = temp_dir.path().join("cs").join("config.toml");
+    std::fs::create_dir_all(xdg_path.parent().unwrap()).unwrap();
+    std::fs::write(&xdg_path, "separator = \"-\"\n").unwrap();
+    assert_eq!(get_config_path(), xdg_path);
+
+    std::env::remove_var("XDG_CONFIG_HOME");
+}
+
+#[test]
+#[serial]
+fn test_cs_home_precedence() {
+    let temp_dir = TempDir::new().unwrap();
+    let cs_home = temp_dir.path().join("cs-home");
+    std::env::remove_var("CS_DB_PATH");
+    std::env::remove_var("CS_CONFIG_PATH");
+    std::env::remove_var("CS_HOME");
+
+    // System HOME
+    assert!(get_db_path().ends_with(".cs/sessions"));
+
+    // CS_HOME beats HOME, for both database and config
+    std::env::set_var("CS_HOME", &cs_home);
+    assert_eq!(get_db_path(), cs_home.join("sessions"));
+    assert_eq!(get_config_path(), cs_home.join("config.toml"));
+
+    // Specific overrides beat CS_HOME
+    std::env::set_var("CS_DB_PATH", temp_dir.path().join("db"));
+    std::env::set_var("CS_CONFIG_PATH", temp_dir.path().join("config"));
+    assert_eq!(get_db_path(), temp_dir.path().join("db"));
+    assert_eq!(get_config_path(), temp_dir.path().join("config"));
+
+    std::env::remove_var("CS_DB_PATH");
+    std::env::remove_var("CS_CONFIG_PATH");
+    std::env::remove_var("CS_HOME");
+}
+
+#[test]
+#[serial]
+fn test_profile_paths() {
+    let temp_dir = TempDir::new().unwrap();
+    let cs_home = temp_dir.path().join("cs-home");
+    std::env::remove_var("CS_DB_PATH");
+    std::env::remove_var("CS_CONFIG_PATH");
+    std::env::set_var("CS_HOME", &cs_home);
+
+    // "default" and empty mean no profile
+    std::env::set_var("CS_PROFILE", "default");
+    assert_eq!(get_db_path(), cs_home.join("sessions"));
+    std::env::set_var("CS_PROFILE", "");
+    assert_eq!(get_db_path(), cs_home.join("sessions"));
+
+    // A profile gets its own database; its config is used only if present
+    let work = cs_home.join("profiles").join("work");
+    std::env::set_var("CS_PROFILE", "work");
+    assert_eq!(get_db_path(), work.join("sessions"));
+    assert_eq!(get_config_path(), cs_home.join("config.toml"));
+    std::fs::create_dir_all(&work).unwrap();
+    std::fs::write(work.join("config.toml"), "").unwrap();
+    assert_eq!(get_config_path(), work.join("config.toml"));
+
+    // Profiles are created lazily on first save
+    save_session("11111111-1111-1111-1111-111111111111");
+    assert!(work.join("sessions").is_file());
+
+    // CS_DB_PATH still wins
+    std::env::set_var("CS_DB_PATH", temp_dir.path().join("db"));
+    assert_eq!(get_db_path(), temp_dir.path().join("db"));
+
+    // An invalid CS_PROFILE is an error, not a silent fallback
+    assert_eq!(validate_profile_env(), Ok(()));
+    std::env::set_var("CS_PROFILE", "../x");
+    assert!(validate_profile_env().unwrap_err().starts_with("CS_PROFILE: invalid profile name '../x'"));
+    std::env::set_var("CS_PROFILE", "default");
+    assert_eq!(validate_profile_env(), Ok(()));
+
+    std::env::remove_var("CS_DB_PATH");
+    std::env::remove_var("CS_PROFILE");
+    std::env::remove_var("CS_HOME");
+}
+
+#[test]
+fn test_profile_from_args() {
+    let args = |list: &[&str]| list.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+
+    assert_eq!(profile_from_args(&args(&["cs", "--list"])), Ok(None));
+    assert_eq!(
+        profile_from_args(&args(&["cs", "--profile", "work", "--list"])),
+        Ok(Some("work".to_string()))
+    );
+    assert!(profile_from_args(&args(&["cs", "--profile"])).is_err());
+    assert!(profile_from_args(&args(&["cs", "--profile", "../etc"])).is_err());
+    assert!(profile_from_args(&args(&["cs", "--profile", ".hidden"])).is_err());
+}
+
+#[test]
+fn test_check_require_git() {
+    let identity = |is_git_repo| SessionIdentity {
+        name: "scratch".to_string(),
+        uuid: generate_uuid5("scratch"),
+        is_git_repo,
+    };
+    assert!(check_require_git(&identity(true)).is_ok());
+    assert!(check_require_git(&identity(false)).unwrap_err().contains("'scratch'"));
+
+    assert!(config::parse_config("require_git = true").unwrap().require_git());
+    assert!(!Config::default().require_git());
+}
+
+#[test]
+fn test_allow_positional_config() {
+    assert!(Config::default().allow_positional());
+    assert!(!config::parse_config("allow_positional = false").unwrap().allow_positional());
+}
+
+#[test]
+fn test_is_yes() {
+    assert!(is_yes("y\n"));
+    assert!(is_yes(" YES "));
+    assert!(!is_yes("\n"));
+    assert!(!is_yes("n"));
+    assert!(!is_yes("yeah"));
+    assert!(config::parse_config("confirm_new = true").unwrap().confirm_new());
+}
+
+#[test]
+fn test_launch_error_message() {
+    let args = vec!["-p".to_string(), "x".repeat(10)];
+    let too_long = std::io::Error::from(std::io::ErrorKind::ArgumentListTooLong);
+    let message = launch_error_message(&too_long, &args);
+    assert!(message.contains("too long"));
+    assert!(message.contains("2 arguments, 14 bytes"));
+    assert!(message.contains("stdin"));
+
+    let denied = std::io::Error::from(std::io::ErrorKind::PermissionDenied);
+    assert!(launch_error_message(&denied, &args).starts_with("Error launching claude: "));
+    assert!(!launch_error_message(&denied, &args).contains("stdin"));
+}
+
+#[test]
+fn test_watch_should_stop_on_quick_double_interrupt() {
+    let start = Instant::now();
+    let mut last = None;
+    assert!(!watch_should_stop(EXIT_INTERRUPTED, start, &mut last));
+    assert!(watch_should_stop(EXIT_INTERRUPTED, start + Duration::from_secs(1), &mut last));
+
+    // Interrupts far apart, or separated by a normal exit, keep watching
+    let mut last = None;
+    assert!(!watch_should_stop(EXIT_INTERRUPTED, start, &mut last));
+    assert!(!watch_should_stop(EXIT_INTERRUPTED, start + Duration::from_secs(10), &mut last));
+    assert!(!watch_should_stop(0, start + Duration::from_secs(11), &mut last));
+    assert!(!watch_should_stop(EXIT_INTERRUPTED, start + Duration::from_secs(12), &mut last));
+    assert!(!watch_should_stop(1, start, &mut None));
+}
+
+#[test]
+fn test_watch_next_step() {
+    let now = Instant::now();
+    let long = WATCH_FAST_FAILURE * 2;
+    let quick = Duration::from_secs(1);
+
+    // A normal exit (e.g. /exit) or a timeout ends the watch
+    assert!(matches!(watch_next_step(0, long, now, &mut WatchState::default()), WatchStep::Stop(_)));
+    assert!(matches!(watch_next_step(EXIT_TIMEOUT, long, now, &mut WatchState::default()), WatchStep::Stop(_)));
+
+    // A crash after a long run relaunches promptly
+    let mut state = WatchState::default();
+    assert_eq!(watch_next_step(1, long, now, &mut state), WatchStep::Relaunch(WATCH_RELAUNCH_DELAY));
+
+    // Fast failures back off, then give up
+    let mut delays = Vec::new();
+    let step = loop {
+        match watch_next_step(1, quick, now, &mut state) {
+            WatchStep::Relaunch(delay) => delays.push(delay.as_secs()),
+            stop => break stop,
+        }
+    };
+    assert_eq!(delays, vec![2, 4, 8, 16]);
+    assert!(matches!(step, WatchStep::Stop(reason) if reason.contains("5 times")));
+
+    // A long run in between resets the count
+    let mut state = WatchState { fast_failures: WATCH_MAX_FAST_FAILURES - 1, ..Default::default() };
+    assert_eq!(watch_next_step(1, long, now, &mut state), WatchStep::Relaunch(WATCH_RELAUNCH_DELAY));
+}
+
+#[test]
+fn test_json_schema() {
+    let schema = json_schema(&JsonOutput {
+        name: "status",
+        command: "cs --status --json",
+        array: false,
+        fields: &[("status", "string"), ("uuid", "string|null")],
+    });
+    assert_eq!(
+        schema,
+        "{\"$schema\":\"https://json-schema.org/draft/2020-12/schema\",\"title\":\"cs status\",\
+\"description\":\"Output of `cs --status --json`\",\"type\":\"object\",\
+\"properties\":{\"status\":{\"type\":\"string\"},\"uuid\":{\"type\":[\"string\",\"null\"]}},\
+\"required\":[\"status\",\"uuid\"],\"additionalProperties\":false}"
+    );
+
+    let batch = json_schema(&JsonOutput { name: "batch", command: "cs --batch", array: true, fields: &[("name", "string")] });
+    assert!(batch.contains("\"type\":\"array\",\"items\":{\"type\":\"object\""), "{}", batch);
+}
+
+#[test]
+fn test_prompt_template() {
+    let config = config::parse_config("separator = \"+\"\n[prompts]\nreview = \"Review this diff\"\nfix = \"Fix the tests\"\n").unwrap();
+    assert_eq!(config.prompt_names(), vec!["fix", "review"]);
+    assert_eq!(prompt_template(&config, "review").unwrap(), "Review this diff");
+    assert_eq!(
+        prompt_template(&config, "plan").unwrap_err(),
+        "unknown prompt template 'plan' (defined: fix, review)"
+    );
+    assert!(prompt_template(&Config::default(), "plan").unwrap_err().contains("no templates defined"));
+}
+
+#[test]
+fn test_renamed_branch_candidate() {
+    let record = |name: &str, used: u64| SessionRecord {
+        last_used: Some(used),
+        ..SessionRecord::new(&generate_uuid5(name), Some(name))
+    };
+    let records = vec![
+        record("app+old-name", 100),
+        record("app+main", 300),
+        record("app+older", 50),
+        record("other+gone", 400),
+        record("app+feature", 200),
+    ];
+    let branch_exists = |branch: &str| branch == "main" || branch == "feature";
+
+    let candidate = renamed_branch_candidate(&records, "app+", "app+new-name", &branch_exists);
+    assert_eq!(candidate.and_then(|r| r.name).as_deref(), Some("app+old-name"));
+
+    let all_exist = |_: &str| true;
+    assert!(renamed_branch_candidate(&records, "app+", "app+new-name", &all_exist).is_none());
+}
+
+#[test]
+fn test_write_uuid_file() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("uuid.txt");
+    let uuid = generate_uuid5("app+main");
+
+    write_uuid_file(&path, &uuid).unwrap();
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), format!("{}\n", uuid));
+
+    let err = write_uuid_file(&dir.path().join("missing/uuid.txt"), &uuid).unwrap_err();
+    assert!(err.starts_with("failed to write UUID to"), "{}", err);
+}
+
+#[test]
+fn test_exit_code_file() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("state/last-exit");
+
+    assert_eq!(read_exit_code(&path), None, "never recorded");
+    write_exit_code(&path, 3).unwrap();
+    assert_eq!(read_exit_code(&path), Some(3));
+    write_exit_code(&path, -1073741819).unwrap();
+    assert_eq!(read_exit_code(&path), Some(-1073741819));
+    std::fs::write(&path, "garbage").unwrap();
+    assert_eq!(read_exit_code(&path), None);
+}
+
+#[test]
+fn test_write_claude_args() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("last-command");
+    let args = |list: &[&str]| list.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+
+    write_claude_args(&path, &args(&["--session-id", "abc", "fix the bug"])).unwrap();
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "--session-id\nabc\nfix the bug\n");
+    write_claude_args(&path, &args(&["-r", "abc"])).unwrap();
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "-r\nabc\n", "the previous launch is replaced");
+
+    let err = write_claude_args(&dir.path().join("missing/last-command"), &args(&["-r"])).unwrap_err();
+    assert!(err.starts_with("failed to write claude args to"), "{}", err);
+}
+
+#[test]
+fn test_name_in_project() {
+    assert!(name_in_project("app", "app", "+"));
+    assert!(name_in_project("app+main", "app", "+"));
+    assert!(name_in_project("app+feature#2", "app", "+"));
+    assert!(name_in_project("app@1a2b3c4d", "app", "+"));
+    assert!(!name_in_project("application+main", "app", "+"));
+    assert!(!name_in_project("other+app", "app", "+"));
+    assert!(name_in_project("app::main", "app", "::"));
+}
+
+#[test]
+fn test_group_records() {
+    let record = |name: Option<&str>, tags: &[&str]| SessionRecord {
+        tags: tags.iter().map(|t| t.to_string()).collect(),
+        ..SessionRecord::new(&generate_uuid5(name.unwrap_or("x")), name)
+    };
+    let records = [
+        record(Some("app+main"), &["wip"]),
+        record(Some("api+main@1a2b3c4d"), &[]),
+        record(Some("app+feature#2"), &["wip", "client-a"]),
+        record(Some("scratch"), &[]),
+        record(None, &["wip"]),
+    ];
+    let summary = |group_by: GroupBy| -> Vec<(String, usize)> {
+        group_records(&records, group_by, "+").into_iter().map(|(key, members)| (key, members.len())).collect()
+    };
+    let expected = |pairs: &[(&str, usize)]| -> Vec<(String, usize)> {
+        pairs.iter().map(|(key, n)| (key.to_string(), *n)).collect()
+    };
+
+    assert_eq!(
+        summary(GroupBy::Folder),
+        expected(&[("app", 2), ("api", 1), ("scratch", 1), ("(unnamed)", 1)])
+    );
+    assert_eq!(
+        summary(GroupBy::Branch),
+        expected(&[("main", 2), ("feature", 1), ("(no branch)", 1), ("(unnamed)", 1)])
+    );
+    assert_eq!(
+        summary(GroupBy::Tag),
+        expected(&[("wip", 3), ("(untagged)", 2), ("client-a", 1)])
+    );
+    assert_eq!(GroupBy::parse("branch"), Ok(GroupBy::Branch));
+    assert!(GroupBy::parse("regex").is_err());
+    assert_eq!(config::parse_config("group_by = \"tag\"").unwrap().group_by(), Some("tag"));
+}
+
+#[test]
+fn test_record_json() {
+    let record = SessionRecord {
+        tags: vec!["wip".to_string()],
+        last_used: Some(0),
+        ..SessionRecord::new("uuid-1", Some("app \"x\""))
+    };
+    assert_eq!(
+        record_json(&record),
+        "{\"uuid\":\"uuid-1\",\"name\":\"app \\\"x\\\"\",\"tags\":[\"wip\"],\"created\":null,\"used\":\"1970-01-01T00:00:00Z\"}"
+    );
+    assert!(record_json(&SessionRecord::new("uuid-2", None)).contains("\"name\":null,\"tags\":[]"));
+}
+
+#[test]
+fn test_describe_session() {
+    let uuid = generate_uuid5("app+main");
+    let record = SessionRecord {
+        tags: vec!["wip".to_string()],
+        created: Some(0),
+        ..SessionRecord::new(&uuid, Some("app+main"))
+    };
+    let current = SessionIdentity { name: "app+main".to_string(), uuid: uuid.clone(), is_git_repo: true };
+
+    assert_eq!(
+        describe_session(&uuid, &[record], Some(&current)),
+        vec![
+            format!("UUID:      {}", uuid),
+            "Database:  tracked".to_string(),
+            "Name:      app+main".to_string(),
+            "Tags:      wip".to_string(),
+            "Created:   1970-01-01T00:00:00Z".to_string(),
+            "Last used: unknown".to_string(),
+            "Directory: matches this directory's session (app+main)".to_string(),
+        ]
+    );
+
+    let other = generate_uuid5("other+main");
+    let lines = describe_session(&other, &[], Some(&current));
+    assert_eq!(lines[1], "Database:  not tracked");
+    assert_eq!(lines[2], format!("Directory: this directory's session is app+main ({})", uuid));
+    assert_eq!(describe_session(&other, &[], None).len(), 2);
+}
+
+#[test]
+fn test_format_age() {
+    assert_eq!(format_age(100, 100), "just now");
+    assert_eq!(format_age(200, 100), "just now", "clock skew never goes negative");
+    assert_eq!(format_age(0, 60), "1 minute ago");
+    assert_eq!(format_age(0, 2 * 3_600 + 59), "2 hours ago");
+    assert_eq!(format_age(0, 3 * 86_400 + 5), "3 days ago");
+}
+
+#[test]
+fn test_session_age() {
+    let uuid = generate_uuid5("app+main");
+    let records = [SessionRecord { last_used: Some(0), ..SessionRecord::new(&uuid, Some("app+main")) }];
+    let now = 86_400;
+
+    assert_eq!(session_age(&uuid, &records, now), Ok("1 day ago (1970-01-01T00:00:00Z)".to_string()));
+    assert_eq!(session_age("app+main", &records, now), Ok("1 day ago (1970-01-01T00:00:00Z)".to_string()));
+    assert_eq!(session_age("app+main", &[SessionRecord::new(&uuid, None)], now), Ok("unknown".to_string()));
+    assert!(session_age("other+main", &[], now).unwrap_err().contains("not tracked"));
+}
+
+#[test]
+fn test_download_with_retries() {
+    use std::cell::{Cell, RefCell};
+    let transient = || DownloadError { message: "Download failed: timeout".to_string(), transient: true };
+
+    // Succeeds on the third attempt, backing off 1s then 2s
+    let calls = Cell::new(0);
+    let sleeps = RefCell::new(Vec::new());
+    let download = || {
+        calls.set(calls.get() + 1);
+        if calls.get() < 3 { Err(transient()) } else { Ok(()) }
+    };
+    assert!(download_with_retries(&download, 3, &|d| sleeps.borrow_mut().push(d)).is_ok());
+    assert_eq!(calls.get(), 3);
+    assert_eq!(*sleeps.borrow(), vec![Duration::from_secs(1), Duration::from_secs(2)]);
+
+    // Gives up after the last attempt
+    calls.set(0);
+    let always_failing = || {
+        calls.set(calls.get() + 1);
+        Err(transient())
+    };
+    assert!(download_with_retries(&always_failing, 2, &|_| {}).is_err());
+    assert_eq!(calls.get(), 2);
+
+    // Permanent errors (e.g. HTTP 404) are not retried
+    calls.set(0);
+    let not_found = || {
+        calls.set(calls.get() + 1);
+        Err(DownloadError { message: "404".to_string(), transient: false })
+    };
+    assert_eq!(download_with_retries(&not_found, 3, &|_| {}).unwrap_err(), "404");
+    assert_eq!(calls.get(), 1);
+}
+
+#[test]
+#[cfg(not(windows))]
+fn test_curl_error_is_transient() {
+    assert!(curl_error_is_transient(Some(6), "Could not resolve host"));
+    assert!(curl_error_is_transient(Some(28), "Operation timed out"));
+    assert!(curl_error_is_transient(Some(22), "curl: (22) The requested URL returned error: 503"));
+    assert!(!curl_error_is_transient(Some(22), "curl: (22) The requested URL returned error: 404"));
+    assert!(!curl_error_is_transient(Some(23), "Failure writing output"));
+    assert!(!curl_error_is_transient(None, ""));
+}
+
+#[test]
+fn test_expand_short_cluster() {
+    assert!(is_short_cluster("-fn"));
+    assert!(!is_short_cluster("-f"));
+    assert!(!is_short_cluster("--force"));
+    assert!(!is_short_cluster("-1x"));
+
+    assert_eq!(expand_short_cluster("-fn").unwrap(), vec!["-f", "-n"]);
+    assert_eq!(expand_short_cluster("-nf").unwrap(), vec!["-n", "-f"]);
+    assert_eq!(expand_short_cluster("-nRy").unwrap(), vec!["-n", "-R", "-y"]);
+
+    let err = expand_short_cluster("-fd").unwrap_err();
+    assert!(err.contains("'-d' in '-fd' takes a value"), "{}", err);
+    let err = expand_short_cluster("-fx").unwrap_err();
+    assert!(err.contains("'-x' in '-fx' is not a cs short flag"), "{}", err);
+}
+
+#[test]
+fn test_parse_resume_all_choice() {
+    assert_eq!(parse_resume_all_choice("\n"), Some(ResumeAllChoice::Open));
+    assert_eq!(parse_resume_all_choice("o"), Some(ResumeAllChoice::Open));
+    assert_eq!(parse_resume_all_choice(" S \n"), Some(ResumeAllChoice::Skip));
+    assert_eq!(parse_resume_all_choice("quit"), Some(ResumeAllChoice::Quit));
+    assert_eq!(parse_resume_all_choice("maybe"), None);
+}
+
+#[test]
+fn test_parse_version() {
+    assert_eq!(parse_version("1.2.3"), Some((1, 2, 3)));
+    assert_eq!(parse_version("1.0.44 (Claude Code)"), Some((1, 0, 44)));
+    assert_eq!(parse_version("claude v2.1"), Some((2, 1, 0)));
+    assert_eq!(parse_version("1.2.3-beta.1+build.7"), Some((1, 2, 3)));
+    assert_eq!(parse_version("1.2.3+20240101"), Some((1, 2, 3)));
+    assert_eq!(parse_version("Claude Code"), None);
+    assert_eq!(parse_version("1.x"), None);
+    assert!(parse_version("1.10.0") > parse_version("1.9.9"));
+
+    let config = config::parse_config("min_claude_version = \"1.2.0\"").unwrap();
+    assert_eq!(config.min_claude_version(), Some("1.2.0"));
+    assert_eq!(Config::default().min_claude_version(), None);
+}
+
+#[test]
+#[serial]
+fn test_claude_config_locations() {
+    std::env::set_var("CLAUDE_CONFIG_DIR", "/opt/claude");
+    let locations = claude_config_locations();
+    assert_eq!(locations[0], ("Config dir", PathBuf::from("/opt/claude")));
+    assert!(locations.contains(&("Sessions", PathBuf::from("/opt/claude/projects"))));
+    std::env::remove_var("CLAUDE_CONFIG_DIR");
+}
+
+#[test]
+fn test_expand_env_vars() {
+    let lookup = |name: &str| match name {
+        "HOME" => Some("/home/me".to_string()),
+        "PROJECT" => Some("app".to_string()),
+        _ => None,
+    };
+    let expand = |value: &str| config::expand_env_vars(value, &lookup, false).unwrap();
+
+    assert_eq!(expand("${HOME}/mcp.json"), "/home/me/mcp.json");
+    assert_eq!(expand("$HOME/$PROJECT-dev"), "/home/me/app-dev");
+    assert_eq!(expand("cost: 5$ or $1"), "cost: 5$ or $1");
+
+    // Missing variables stay as written, or fail in strict mode
+    assert_eq!(expand("$MISSING/${ALSO_MISSING}"), "$MISSING/${ALSO_MISSING}");
+    assert!(config::expand_env_vars("$MISSING", &lookup, true).unwrap_err().contains("'MISSING'"));
+
+    // Escaped dollars are literal
+    assert_eq!(expand("\\$HOME and C:\\dir"), "$HOME and C:\\dir");
+}
+
+#[test]
+fn test_config_expands_env_in_strings_and_arrays() {
+    let content = "name = \"$PROJECT\"\ndefault_args = [\"--mcp-config\", \"${HOME}/mcp.json\", \"\\$HOME\"]\nn = 3";
+    let lookup = |name: &str| match name {
+        "HOME" => Some("/home/me".to_string()),
+        _ => None,
+    };
+
+    let expanded = config::parse_config_with_env(content, &lookup, false).unwrap();
+    assert_eq!(expanded.get_str("name"), Some("$PROJECT"));
+    assert_eq!(expanded.default_args(), ["--mcp-config", "/home/me/mcp.json", "$HOME"]);
+    assert_eq!(expanded.get_int("n"), Some(3));
+
+    let err = config::parse_config_with_env(content, &lookup, true).unwrap_err();
+    assert_eq!(err, "name: unknown environment variable 'PROJECT'");
+
+    // Without expansion, references are kept as written and escapes resolved
+    let literal = config::parse_config(content).unwrap();
+    assert_eq!(literal.default_args(), ["--mcp-config", "${HOME}/mcp.json", "$HOME"]);
+}
+
+#[test]
+fn test_config_backslash_before_dollar() {
+    let lookup = |name: &str| (name == "HOME").then(|| "/home/me".to_string());
+    let parse = |content: &str| config::parse_config_with_env(content, &lookup, true).unwrap();
+
+    // `\\` is a literal backslash, so `\\\$` is a backslash then a literal `$`
+    assert_eq!(parse("a = \"C:\\\\\\$HOME\"").get_str("a"), Some("C:\\$HOME"));
+    assert_eq!(parse("a = \"C:\\\\$HOME\"").get_str("a"), Some("C:\\/home/me"));
+    assert_eq!(parse("a = \"C:\\\\dir\"").get_str("a"), Some("C:\\dir"));
+    assert_eq!(parse("a = 'C:\\dir\\$HOME'").get_str("a"), Some("C:\\dir\\/home/me"));
+    assert_eq!(config::parse_config("a = \"C:\\\\\\$HOME\"").unwrap().get_str("a"), Some("C:\\$HOME"));
+}
+
+#[test]
+fn test_load_config_missing_file_uses_defaults() {
+    let temp_dir = TempDir::new().unwrap();
+    let config = config::load_config(&temp_dir.path().join("missing.toml"), false).unwrap();
+    assert_eq!(config, Config::default());
+}
+
+#[test]
+fn test_config_problems() {
+    let dir = TempDir::new().unwrap();
+    std::fs::write(dir.path().join("mcp.json"), "{}").unwrap();
+    let lookup = |name: &str| (name == "HOME_MCP").then(|| "mcp.json".to_string());
+
+    let valid = "default_args = [\"--mcp-config\", \"$HOME_MCP\"]\n[prompts]\nreview = \"Review\"\n";
+    assert!(config_problems(valid, dir.path(), &lookup).is_empty());
+
+    let invalid = "\
+separator = \"\"
+require_git = \"yes\"
+backup_retention = -1
+min_claude_version = \"latest\"
+group_by = \"client\"
+default_args = [\"--settings\", \"missing.json\"]
+managed_paths = [\"/no/such/dir\", \"/tmp/**\"]
+namespace = \"abc\"
+";
+    assert_eq!(
+        config_problems(invalid, dir.path(), &lookup),
+        vec![
+            "namespace: unknown key",
+            "require_git: expected boolean, found string",
+            "separator: must not be empty",
+            "backup_retention: must not be negative",
+            "group_by: must be folder, branch, or tag, got 'client'",
+            "min_claude_version: 'latest' is not a version like 1.2.3",
+            "default_args: file not found: missing.json for --settings",
+            "managed_paths: /no/such/dir does not exist",
+        ]
+    );
+
+    assert_eq!(config_problems("oops", dir.path(), &lookup), vec!["line 1: expected 'key = value'"]);
+    assert_eq!(
+        config_problems("base_ref = \"$NOPE\"", dir.path(), &lookup),
+        vec!["base_ref: unknown environment variable 'NOPE'"]
+    );
+}
+
+#[test]
+fn test_glob_match() {
+    assert!(glob_match("/tmp/*", "/tmp/scratch"));
+    assert!(!glob_match("/tmp/*", "/tmp/scratch/deeper"));
+    assert!(glob_match("/tmp/**", "/tmp/scratch/deeper"));
+    assert!(glob_match("/home/*/scratch-?", "/home/me/scratch-1"));
+    assert!(!glob_match("/home/*/scratch-?", "/home/me/scratch-10"));
+}
+
+#[test]
+fn test_is_managed_dir() {
+    let config = config::parse_config(
+        "managed_paths = [\"/work\", \"/src/*-app\"]\nunmanaged_paths = [\"/work/tmp\", \"/**/scratch\"]",
+    )
+    .unwrap();
+
+    assert!(is_managed_dir(&config, Path::new("/work")));
+    assert!(is_managed_dir(&config, Path::new("/work/project")));
+    assert!(is_managed_dir(&config, Path::new("/src/web-app/sub")));
+    assert!(!is_managed_dir(&config, Path::new("/workshop")), "prefix match is per path component");
+    assert!(!is_managed_dir(&config, Path::new("/src/lib")));
+    assert!(!is_managed_dir(&config, Path::new("/work/tmp/x")), "unmanaged wins over managed");
+    assert!(!is_managed_dir(&config, Path::new("/work/a/scratch/b")));
+
+    // Default: manage everywhere
+    assert!(is_managed_dir(&Config::default(), Path::new("/anywhere")));
+}
+
+#[test]
+fn test_config_default_separator() {
+    assert_eq!(Config::default().separator(), "+");
+}
+
+#[test]
+fn test_build_session_name() {
+    assert_eq!(build_session_name("my-project", Some("main"), "+"), "my-project+main");
+    assert_eq!(build_session_name("my-project", Some("feature"), "::"), "my-project::feature");
+    assert_eq!(build_session_name("my-folder", None, "::"), "my-folder");
+}
+
+#[test]
+#[serial]
+fn test_separator_change_is_recorded() {
+    let _env = TestEnv::new();
+    save_session("uuid-1111-1111-1111-111111111111");
+
+    check_separator_change("+");
+    assert!(!get_separator_marker_path().exists(), "default separator needs no marker");
+
+    check_separator_change("::");
+    let recorded = std::fs::read_to_string(get_separator_marker_path()).unwrap();
+    assert_eq!(recorded.trim(), "::");
+}
+
+#[test]
+#[serial]
+fn test_case_insensitive_change_is_recorded() {
+    let _env = TestEnv::new();
+    save_session("uuid-1111-1111-1111-111111111111");
+
+    check_case_insensitive_change(false);
+    assert!(!get_case_marker_path().exists(), "the default needs no marker");
+
+    check_case_insensitive_change(true);
+    assert_eq!(std::fs::read_to_string(get_case_marker_path()).unwrap().trim(), "true");
+    check_case_insensitive_change(false);
+    assert_eq!(std::fs::read_to_string(get_case_marker_path()).unwrap().trim(), "false");
+}
+
+// ============================================================================
+// Backup tests (use isolated temp dirs, must run serially)
+// ============================================================================
+
+#[test]
+fn test_unix_to_utc() {
+    assert_eq!(unix_to_utc(0), (1970, 1, 1, 0, 0, 0));
+    assert_eq!(unix_to_utc(951_782_400), (2000, 2, 29, 0, 0, 0));
+    assert_eq!(unix_to_utc(1_769_212_800 + 3_661), (2026, 1, 24, 1, 1, 1));
+}
+
+#[test]
+#[serial]
+fn test_backup_db_creates_copy() {
+    let _env = TestEnv::new();
+    assert!(backup_db(10).unwrap().is_none(), "No database means no backup");
+
+    save_session("uuid-backup-1111-2222-333344445555");
+    let backup = backup_db(10).unwrap().expect("backup should be created");
+
+    assert!(backup.starts_with(get_backup_dir()));
+    assert_eq!(
+        std::fs::read_to_string(&backup).unwrap(),
+        std::fs::read_to_string(get_db_path()).unwrap()
+    );
+}
+
+#[test]
+#[serial]
+fn test_backup_db_retention() {
+    let _env = TestEnv::new();
+    save_session("uuid-backup-1111-2222-333344445555");
+
+    let backups: Vec<_> = (0..5).map(|_| backup_db(3).unwrap().unwrap()).collect();
+
+    let remaining = std::fs::read_dir(get_backup_dir()).unwrap().count();
+    assert_eq!(remaining, 3);
+    assert!(!backups[0].exists(), "Oldest backup should be removed");
+    assert!(!backups[1].exists(), "Oldest backup should be removed");
+    assert!(backups[4].exists(), "Newest backup should be kept");
+}
+
+#[test]
+fn test_backup_retention_keeps_at_least_one() {
+    let config = config::parse_config("backup_retention = 0").unwrap();
+    assert_eq!(config.backup_retention(), 1);
+}
+
+#[test]
+fn test_completion_values_for_model() {
+    assert_eq!(completion_values("--model"), CLAUDE_MODEL_VALUES);
+    assert!(completion_values("--add-dir").is_empty());
+
+    for shell in SUPPORTED_SHELLS {
+        let script = completion_script(shell).unwrap();
+        for model in CLAUDE_MODEL_VALUES {
+            assert!(script.contains(model), "{} completions should offer model {}", shell, model);
+        }
+    }
+}
+
+// ============================================================================
+// Claude session store tests (use isolated temp dirs, must run serially)
+// ============================================================================
+
+#[test]
+#[serial]
+fn test_list_claude_sessions() {
+    let claude_dir = TempDir::new().unwrap();
+    let project = claude_dir.path().join("projects").join("-home-me-my-app");
+    std::fs::create_dir_all(&project).unwrap();
+    std::fs::write(project.join("afe19c61-d53f-581c-985c-56e9daf4e63d.jsonl"), "").unwrap();
+    std::fs::write(project.join("not-a-session.jsonl"), "").unwrap();
+    std::fs::write(project.join("afe19c61-d53f-581c-985c-56e9daf4e63e.txt"), "").unwrap();
+
+    std::env::set_var("CLAUDE_CONFIG_DIR", claude_dir.path());
+    let sessions = list_claude_sessions().unwrap();
+    std::env::remove_var("CLAUDE_CONFIG_DIR");
+
+    assert_eq!(sessions.len(), 1);
+    assert_eq!(sessions[0].uuid, "afe19c61-d53f-581c-985c-56e9daf4e63d");
+    assert_eq!(sessions[0].project, "-home-me-my-app");
+}
+
+#[test]
+#[serial]
+fn test_list_claude_sessions_missing_store() {
+    let claude_dir = TempDir::new().unwrap();
+    std::env::set_var("CLAUDE_CONFIG_DIR", claude_dir.path().join("missing"));
+    let result = list_claude_sessions();
+    std::env::remove_var("CLAUDE_CONFIG_DIR");
+
+    assert!(result.is_err());
+}
+
+// ============================================================================
+// Info box rendering tests (no env var dependencies)
+// ============================================================================
+
+#[test]
+fn test_render_info_box_unicode() {
+    let rendered = render_info_box(&[("Session", "my-app+main"), ("UUID", "abc")], &UNICODE_BOX, None);
+    let lines: Vec<&str> = rendered.lines().collect();
+    assert_eq!(lines.len(), 4);
+    assert_eq!(lines[0], format!("┌{}", "─".repeat(BOX_WIDTH)));
+    assert_eq!(lines[1], "│ Session: my-app+main");
+    assert_eq!(lines[2], "│ UUID:    abc");
+    assert_eq!(lines[3], format!("└{}", "─".repeat(BOX_WIDTH)));
+}
+
+#[test]
+fn test_render_info_box_ascii() {
+    let rendered = render_info_box(&[("Status", "new")], &ASCII_BOX, None);
+    assert_eq!(
+        rendered,
+        format!("+{0}\n| Status:  new\n+{0}\n", "-".repeat(BOX_WIDTH))
+    );
+    assert!(rendered.is_ascii());
+}
+
+#[test]
+fn test_uuid5_digest_matches_uuid() {
+    let digest = uuid5_digest(&DEFAULT_NAMESPACE, "claude-code-resumer+main");
+    let uuid_hex = "afe19c61-d53f-581c-985c-56e9daf4e63d".replace('-', "");
+    let digest_hex = to_hex(&digest[..16]);
+
+    // Only the version nibble (byte 6) and variant bits (byte 8) differ
+    assert_eq!(digest_hex[..12], uuid_hex[..12]);
+    assert_eq!(digest_hex[14..16], uuid_hex[14..16]);
+    assert_eq!(digest_hex[18..], uuid_hex[18..]);
+    assert_eq!(format_uuid(&DEFAULT_NAMESPACE), "6ba7b810-9dad-11d1-80b4-00c04fd430c8");
+}
+
+// ============================================================================
+// Argument handling tests (no env var dependencies)
+// ============================================================================
+
+#[test]
+fn test_expand_value_flag_repeatable() {
+    assert_eq!(
+        expand_value_flag("--add-dir", "a,b,c").unwrap(),
+        vec!["--add-dir", "a", "--add-dir", "b", "--add-dir", "c"]
+    );
+    assert_eq!(expand_value_flag("--add-dir", "a,,b").unwrap(), vec!["--add-dir", "a", "--add-dir", "b"]);
+    assert_eq!(expand_value_flag("--add-dir", "single").unwrap(), vec!["--add-dir", "single"]);
+    // Only separators: an error, not a silently dropped flag
+    assert!(expand_value_flag("--add-dir", ",").is_err());
+    assert!(expand_value_flag("--plugin-dir", ",,").is_err());
+}
+
+#[test]
+fn test_expand_value_flag_not_repeatable() {
+    assert_eq!(
+        expand_value_flag("--allowed-tools", "Bash,Edit").unwrap(),
+        vec!["--allowed-tools", "Bash,Edit"]
+    );
+}
+
+#[test]
+fn test_key_value_args_strict_mode() {
+    assert_eq!(key_value_args("--model=opus", false), Some(Ok(vec!["--model=opus".to_string()])));
+    assert_eq!(key_value_args("--add-dir=a,b", false), Some(expand_value_flag("--add-dir", "a,b")));
+    assert!(matches!(key_value_args("--add-dir=,", false), Some(Err(_))));
+    assert_eq!(key_value_args("--foo=bar", false), None, "unknown keys are rejected by default");
+}
+
+#[test]
+fn test_key_value_args_loose_mode() {
+    assert_eq!(key_value_args("--foo=bar", true), Some(Ok(vec!["--foo=bar".to_string()])));
+    assert_eq!(key_value_args("--model=opus", true), Some(Ok(vec!["--model=opus".to_string()])));
+    assert_eq!(key_value_args("foo=bar", true), None, "only flags are forwarded");
+    assert!(config::parse_config("loose_args = true").unwrap().loose_args());
+    assert!(!Config::default().loose_args());
+}
+
+// ============================================================================
+// Process wait tests (spawn real child processes)
+// ============================================================================
+
+/// A command that runs for several seconds on every platform
+fn long_running_command() -> std::process::Command {
+    #[cfg(windows)]
+    {
+        let mut cmd = std::process::Command::new("powershell");
+        cmd.args(["-NoProfile", "-Command", "Start-Sleep -Seconds 10"]);
+        cmd
+    }
+    #[cfg(not(windows))]
+    {
+        let mut cmd = std::process::Command::new("sleep");
+        cmd.arg("10");
+        cmd
+    }
+}
+
+#[test]
+#[cfg(unix)]
+fn test_child_exit_code() {
+    use std::os::unix::process::ExitStatusExt;
+    assert_eq!(child_exit_code(ExitStatus::from_raw(0)), 0);
+    assert_eq!(child_exit_code(ExitStatus::from_raw(3 << 8)), 3);
+    // Killed by SIGKILL: no exit code, reported like a shell would
+    assert_eq!(child_exit_code(ExitStatus::from_raw(9)), 137);
+}
+
+#[test]
+#[cfg(unix)]
+fn test_child_exit_code_for_signalled_child() {
+    // A spawned child that dies from a signal reports 128 + signum, as exec() would
+    let status = Command::new("sh").args(["-c", "kill -TERM $$"]).status().unwrap();
+    assert_eq!(child_exit_code(status), 128 + 15);
+
+    let mut child = Command::new("sleep").arg("10").spawn().unwrap();
+    child.kill().unwrap();
+    assert_eq!(child_exit_code(child.wait().unwrap()), 128 + 9);
+}
+
+#[test]
+#[cfg(windows)]
+fn test_child_exit_code() {
+    use std::os::windows::process::ExitStatusExt;
+    assert_eq!(child_exit_code(ExitStatus::from_raw(0)), 0);
+    assert_eq!(child_exit_code(ExitStatus::from_raw(0xC000_0005)), 0xC000_0005_u32 as i32);
+}
+
+#[test]
+fn test_missing_session_rule() {
+    let default = MissingSessionRule::from_config(&Config::default());
+    let message = "No conversation found with session ID: 4b513bfa";
+    assert!(default.matches(1, message));
+    assert!(!default.matches(0, message), "success is never a missing session");
+    assert!(!default.matches(EXIT_TIMEOUT, message), "a timeout is never a missing session");
+    assert!(!default.matches(1, "network error"));
+
+    let by_code = MissingSessionRule { stderr: String::new(), exit_code: Some(3) };
+    assert!(by_code.matches(3, ""));
+    assert!(!by_code.matches(1, message));
+
+    let both = MissingSessionRule { stderr: "not found".to_string(), exit_code: Some(3) };
+    assert!(both.matches(3, "session not found"));
+    assert!(!both.matches(3, "other failure"));
+
+    let disabled = MissingSessionRule { stderr: String::new(), exit_code: None };
+    assert!(!disabled.matches(1, message));
+}
+
+#[test]
+fn test_missing_from_claude_store() {
+    let uuid = "11111111-1111-5111-8111-111111111111";
+    let store = |uuids: &[&str]| -> Result<Vec<ClaudeSession>, String> {
+        Ok(uuids.iter().map(|u| ClaudeSession { uuid: u.to_string(), project: "-home-me-app".to_string() }).collect())
+    };
+
+    assert!(missing_from_claude_store(uuid, store(&["22222222-2222-5222-8222-222222222222"])));
+    assert!(!missing_from_claude_store(uuid, store(&[uuid])));
+    // An empty or unreadable store proves nothing
+    assert!(!missing_from_claude_store(uuid, store(&[])));
+    assert!(!missing_from_claude_store(uuid, Err("Claude session store not found".to_string())));
+}
+
+#[test]
+fn test_wait_with_timeout_kills_long_running_child() {
+    let mut child = long_running_command().spawn().expect("spawn long-running command");
+    let started = std::time::Instant::now();
+
+    let result = wait_with_timeout(&mut child, Some(std::time::Duration::from_millis(200))).unwrap();
+
+    assert!(result.is_none(), "Child should be killed on timeout");
+    assert!(started.elapsed() < std::time::Duration::from_secs(5));
+}
+
+#[test]
+fn test_wait_with_timeout_returns_status() {
+    let mut child = long_running_command().spawn().expect("spawn long-running command");
+    child.kill().unwrap();
+
+    let result = wait_with_timeout(&mut child, Some(std::time::Duration::from_secs(5))).unwrap();
+    assert!(result.is_some(), "Exited child should report its status");
+}
+
+#[test]
+#[serial]
+fn test_clone_session_registers_destination() {
+    let _env = TestEnv::new();
+    save_session(&generate_uuid5("app+branch-a"));
+
+    clone_session("app+branch-a", "app+branch-b");
+
+    let sessions = load_sessions();
+    assert!(sessions.contains(&generate_uuid5("app+branch-a")));
+    assert!(sessions.contains(&generate_uuid5("app+branch-b")));
+    assert_eq!(sessions.len(), 2);
+}
+
+#[test]
+#[serial]
+fn test_clear_sessions_dry_run_keeps_database() {
+    let _env = TestEnv::new();
+    save_session("uuid-1111-1111-1111-111111111111");
+
+    clear_sessions(10, true, false);
+    assert_eq!(load_sessions().len(), 1, "Dry run must not delete the database");
+    assert!(!get_backup_dir().exists(), "Dry run must not take a backup");
+
+    clear_sessions(10, false, true);
+    assert!(load_sessions().is_empty());
+}
+
+#[test]
+fn test_clear_target_names_profile() {
+    let path = Path::new("/home/me/.cs/profiles/work/sessions");
+    assert_eq!(clear_target(Some("work"), path), "profile 'work' (/home/me/.cs/profiles/work/sessions)");
+    assert_eq!(clear_target(None, Path::new("/home/me/.cs/sessions")), "the default profile (/home/me/.cs/sessions)");
+}
+
+#[test]
+fn test_env_registry_covers_cs_variables() {
+    let source = include_str!("main.rs");
+    for chunk in source.split("env::var(\"").skip(1) {
+        let name = chunk.split('"').next().unwrap();
+        if name.starts_with("CS_") || name.starts_with("CLAUDE_") {
+            assert!(
+                CS_ENV_VARS.iter().any(|(var, _)| *var == name),
+                "{} is read but missing from CS_ENV_VARS",
+                name
+            );
+        }
+    }
+}
+
+#[test]
+#[serial]
+fn test_readonly_mode_skips_mutations() {
+    let _env = TestEnv::new();
+    save_session("uuid-keep-1111-2222-333344445555");
+
+    std::env::set_var("CS_READONLY", "1");
+    assert!(is_db_readonly());
+    save_session("uuid-new-aaaa-bbbb-ccccddddeeee");
+    remove_session("uuid-keep-1111-2222-333344445555");
+    clear_sessions(10, false, true);
+    std::env::remove_var("CS_READONLY");
+
+    let sessions = load_sessions();
+    assert_eq!(sessions.len(), 1);
+    assert!(sessions.contains("uuid-keep-1111-2222-333344445555"));
+}
+
+#[test]
+#[serial]
+fn test_readonly_directory_is_detected() {
+    let _env = TestEnv::new();
+    let temp_dir = TempDir::new().unwrap();
+
+    // A database under a path that can't hold directories can't be written
+    let blocker = temp_dir.path().join("not-a-dir");
+    fs::write(&blocker, "").unwrap();
+    std::env::set_var("CS_DB_PATH", blocker.join("cs").join("sessions"));
+    assert!(is_db_readonly());
+
+    // A missing database in a writable (or not yet created) directory is fine
+    std::env::set_var("CS_DB_PATH", temp_dir.path().join("new").join("sessions"));
+    assert!(!is_db_readonly());
+    assert!(fs::read_dir(temp_dir.path()).unwrap().all(|e| !e.unwrap().file_name().to_string_lossy().starts_with(".cs-")));
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let locked = temp_dir.path().join("locked");
+        fs::create_dir(&locked).unwrap();
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o555)).unwrap();
+        std::env::set_var("CS_DB_PATH", locked.join("sessions"));
+        // root ignores the permission bits, so only check where they apply
+        if fs::write(locked.join("probe"), "").is_err() {
+            assert!(is_db_readonly());
+        }
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o755)).unwrap();
+    }
+}
+
+#[test]
+#[serial]
+fn test_dry_run_leaves_database_untouched() {
+    let _env = TestEnv::new();
+    let uuid = generate_uuid5("app+main");
+    save_session(&uuid);
+    let db_path = get_db_path();
+    let before = fs::read(&db_path).unwrap();
+    let modified = fs::metadata(&db_path).unwrap().modified().unwrap();
+
+    DRY_RUN.store(true, Ordering::Relaxed);
+    // What `--reset --dry-run`, a dry-run launch, and the database commands would do
+    remove_session(&uuid);
+    save_record(&SessionRecord::new(&generate_uuid5("app+other"), Some("app+other")).stamped(unix_now()));
+    touch_session(&uuid);
+    let tagged = tag_session(&uuid, "app+main", &["wip".to_string()]);
+    let repaired = repair_db();
+    clear_sessions(10, false, true);
+    DRY_RUN.store(false, Ordering::Relaxed);
+
+    assert_eq!(tagged, Ok(vec!["wip".to_string()]));
+    assert!(repaired.is_ok());
+    assert_eq!(fs::read(&db_path).unwrap(), before);
+    assert_eq!(fs::metadata(&db_path).unwrap().modified().unwrap(), modified);
+}
+
+#[test]
+fn test_dry_run_from_args() {
+    let args = |list: &[&str]| list.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+
+    assert!(dry_run_from_args(&args(&["cs", "--rm", "app+main", "--dry-run"])));
+    assert!(dry_run_from_args(&args(&["cs", "--reset", "-n"])));
+    assert!(dry_run_from_args(&args(&["cs", "-fn"])));
+    assert!(!dry_run_from_args(&args(&["cs", "-f", "--no-git"])));
+    assert!(!dry_run_from_args(&args(&["cs", "-fx"])));
+    assert!(!dry_run_from_args(&args(&["cs", "-p", "--", "-n"])));
+}
+
+#[test]
+fn test_cs_args_skip_claude_values_and_stop_at_double_dash() {
+    let args = |list: &[&str]| list.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+
+    assert_eq!(
+        cs_args(&args(&["cs", "--system-prompt", "--profile", "--list", "--", "--namespace", "x"])),
+        vec!["--list"]
+    );
+    assert_eq!(cs_args(&args(&["cs", "-p", "--color", "never"])), vec!["-p", "--color", "never"]);
+
+    // Values that look like cs flags stay claude's
+    assert_eq!(profile_from_args(&args(&["cs", "--append-system-prompt", "--profile"])), Ok(None));
+    assert_eq!(namespace_from_args(&args(&["cs", "-p", "--", "--namespace"])), Ok(None));
+    assert_eq!(color_from_args(&args(&["cs", "--model", "--color=bad"])), Ok(None));
+}
+
+// ============================================================================
+// Directory-based session resolution tests (use temp dirs)
+// ============================================================================
+
+#[test]
+#[serial]
+fn test_resolve_session_non_git_dir() {
+    let parent = TempDir::new().unwrap();
+    let dir = parent.path().join("plain-folder");
+    std::fs::create_dir(&dir).unwrap();
+
+    let identity = resolve_session(&dir, "+", true).unwrap();
+    assert_eq!(identity.name, "plain-folder");
+    assert_eq!(identity.uuid, generate_uuid5("plain-folder"));
+    assert!(!identity.is_git_repo);
+}
+
+#[test]
+fn test_fold_folder_name() {
+    assert_eq!(fold_folder_name("MyProject"), "myproject");
+    assert_eq!(fold_folder_name("Caf\u{e9}"), "caf\u{e9}");
+    assert_eq!(fold_folder_name("Cafe\u{301}"), "caf\u{e9}", "decomposed (NFD) e + acute is composed");
+    assert_eq!(fold_folder_name("CAFE\u{301}"), "caf\u{e9}");
+    assert_eq!(fold_folder_name("x\u{301}"), "x\u{301}", "marks without a composition are kept");
+    // Full NFC, not just common Western letters
+    assert_eq!(fold_folder_name("Z\u{307}ubr"), "\u{17c}ubr", "z + dot above");
+    assert_eq!(fold_folder_name("e\u{328}"), "\u{119}", "e + ogonek");
+    assert_eq!(fold_folder_name("o\u{30b}"), "\u{151}", "o + double acute");
+    assert_eq!(fold_folder_name("g\u{306}"), "\u{11f}", "g + breve");
+    assert_eq!(fold_folder_name("Vie\u{302}\u{323}t"), "vi\u{1ec7}t", "Vietnamese stacked marks");
+    assert_eq!(fold_folder_name("\u{3b1}\u{301}"), "\u{3ac}", "Greek tonos");
+}
+
+#[test]
+#[serial]
+fn test_case_insensitive_folders_share_a_session() {
+    let parent = TempDir::new().unwrap();
+    let upper = parent.path().join("MyProject");
+    let lower = parent.path().join("myproject");
+    std::fs::create_dir(&upper).unwrap();
+    if std::fs::create_dir(&lower).is_err() {
+        return; // case-insensitive filesystem: both spellings are one directory
+    }
+
+    assert_ne!(resolve_session(&upper, "+", false).unwrap().uuid, resolve_session(&lower, "+", false).unwrap().uuid);
+
+    CASE_INSENSITIVE.store(true, Ordering::Relaxed);
+    let folded = (resolve_session(&upper, "+", false), resolve_session(&lower, "+", false));
+    CASE_INSENSITIVE.store(false, Ordering::Relaxed);
+    let (upper_id, lower_id) = (folded.0.unwrap(), folded.1.unwrap());
+    assert_eq!(upper_id.name, "myproject");
+    assert_eq!(upper_id.uuid, lower_id.uuid);
+    assert_eq!(upper_id.uuid, generate_uuid5("myproject"));
+    assert!(config::parse_config("case_insensitive = true").unwrap().case_insensitive());
+}
+
+#[test]
+#[serial]
+fn test_resolve_session_git_dir() {
+    let parent = TempDir::new().unwrap();
+    let dir = parent.path().join("repo");
+    std::fs::create_dir(&dir).unwrap();
+    let git = |args: &[&str]| {
+        std::process::Command::new("git")
+            .args(args)
+            .current_dir(&dir)
+            .output()
+            .expect("git should be installed")
+    };
+    git(&["init", "-q"]);
+    git(&["checkout", "-q", "-b", "feature/x"]);
+    git(&["-c", "user.name=cs", "-c", "user.email=cs@example.com", "commit", "-q", "--allow-empty", "-m", "init"]);
+
+    let identity = resolve_session(&dir, "+", true).unwrap();
+    assert_eq!(identity.name, "repo+feature/x");
+    assert_eq!(identity.uuid, generate_uuid5("repo+feature/x"));
+    assert!(identity.is_git_repo);
+}
+
+#[test]
+#[serial]
+fn test_apply_base_ref() {
+    let parent = TempDir::new().unwrap();
+    let dir = parent.path().join("repo");
+    std::fs::create_dir(&dir).unwrap();
+    let git = |args: &[&str]| {
+        std::process::Command::new("git")
+            .args(args)
+            .current_dir(&dir)
+            .output()
+            .expect("git should be installed")
+    };
+    let commit = |message: &str| {
+        git(&["-c", "user.name=cs", "-c", "user.email=cs@example.com", "commit", "-q", "--allow-empty", "-m", message])
+    };
+    git(&["init", "-q"]);
+    git(&["checkout", "-q", "-b", "main"]);
+    commit("base");
+    let base = String::from_utf8_lossy(&git(&["rev-parse", "HEAD"]).stdout).trim()[..BASE_COMMIT_LEN].to_string();
+
+    // The base branch keeps its plain name
+    let identity = apply_base_ref(resolve_session(&dir, "+", true).unwrap(), &dir, "main");
+    assert_eq!(identity.name, "repo+main");
+
+    git(&["checkout", "-q", "-b", "feature"]);
+    commit("work");
+    let identity = apply_base_ref(resolve_session(&dir, "+", true).unwrap(), &dir, "main");
+    assert_eq!(identity.name, format!("repo+feature@{}", base));
+    assert_eq!(identity.uuid, generate_uuid5(&identity.name));
+
+    // Unknown refs fall back to branch naming
+    let identity = apply_base_ref(resolve_session(&dir, "+", true).unwrap(), &dir, "no-such-ref");
+    assert_eq!(identity.name, "repo+feature");
+}
+
+// ============================================================================
+// Self-update temp file tests (use temp dirs)
+// ============================================================================
+
+#[test]
+fn test_unsupported_platform_error_names_target() {
+    let message = unsupported_platform_error();
+    assert!(message.starts_with("Unsupported platform for auto-update: target "));
+    assert!(message.contains(env!("CS_TARGET")));
+    assert!(message.contains(std::env::consts::ARCH));
+}
+
+#[test]
+fn test_temp_file_guard_removes_file() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("cs.new.1");
+    std::fs::write(&path, "partial").unwrap();
+    {
+        let _guard = TempFileGuard(path.clone());
+    }
+    assert!(!path.exists());
+}
+
+#[test]
+fn test_remove_stale_downloads() {
+    let dir = TempDir::new().unwrap();
+    let exe = dir.path().join("cs");
+    std::fs::write(&exe, "binary").unwrap();
+    std::fs::write(temp_download_path(&exe, 111), "partial").unwrap();
+    std::fs::write(dir.path().join("cs.new"), "legacy partial").unwrap();
+    std::fs::write(dir.path().join("other.new.1"), "unrelated").unwrap();
+
+    assert_eq!(remove_stale_downloads(&exe), 2);
+    assert!(exe.exists());
+    assert!(dir.path().join("other.new.1").exists());
+}
+
+#[test]
+#[serial]
+fn test_repair_db_cleans_messy_file() {
+    let _env = TestEnv::new();
+    let messy = "\
+BBBBBBBB-1111-5111-8111-111111111111
+aaaaaaaa-1111-5111-8111-111111111111
+
+  bbbbbbbb-1111-5111-8111-111111111111
+aaaaaaaa-1111-5111-8111-111111111111\t
+\t
+";
+    std::fs::write(get_db_path(), messy).unwrap();
+
+    let report = repair_db().unwrap();
+
+    assert_eq!(
+        report,
+        RepairReport { kept: 2, duplicates: 2, normalized: 3, blank: 2 }
+    );
+    assert_eq!(
+        std::fs::read_to_string(get_db_path()).unwrap(),
+        "aaaaaaaa-1111-5111-8111-111111111111\nbbbbbbbb-1111-5111-8111-111111111111\n"
+    );
+}
+
+#[test]
+#[serial]
+fn test_repair_db_merges_duplicate_records() {
+    let _env = TestEnv::new();
+    let messy = "\
+aaaaaaaa-1111-5111-8111-111111111111\ttags=wip
+AAAAAAAA-1111-5111-8111-111111111111\tname=app+main\ttags=review,wip
+";
+    std::fs::write(get_db_path(), messy).unwrap();
+
+    let report = repair_db().unwrap();
+
+    assert_eq!(report.kept, 1);
+    assert_eq!(report.duplicates, 1);
+    assert_eq!(
+        std::fs::read_to_string(get_db_path()).unwrap(),
+        "aaaaaaaa-1111-5111-8111-111111111111\tname=app+main\ttags=wip,review\n"
+    );
+}
+
+#[test]
+fn test_parse_legacy_sessions() {
+    let legacy = "\
+# sessions from cs.sh
+app+main=AAAAAAAA-1111-5111-8111-111111111111
+
+app+feature=x=bbbbbbbb111151118111111111111111
+";
+    let records = parse_legacy_sessions(legacy).unwrap();
+    assert_eq!(
+        records,
+        vec![
+            SessionRecord::new("aaaaaaaa-1111-5111-8111-111111111111", Some("app+main")),
+            SessionRecord::new("bbbbbbbb-1111-5111-8111-111111111111", Some("app+feature=x")),
+        ]
+    );
+
+    // The current database format is not mistaken for legacy lines
+    let err = parse_legacy_sessions("aaaaaaaa-1111-5111-8111-111111111111\tname=app+main\n").unwrap_err();
+    assert!(err.starts_with("line 1:"), "{}", err);
+    assert!(parse_legacy_sessions("=aaaaaaaa-1111-5111-8111-111111111111\n").is_err());
+    assert!(parse_legacy_sessions("# only a comment\n").is_err());
+}
+
+#[test]
+#[serial]
+fn test_import_legacy_merges_into_database() {
+    let env = TestEnv::new();
+    let existing = "aaaaaaaa-1111-5111-8111-111111111111\tname=app+main\tused=100\n";
+    std::fs::write(get_db_path(), existing).unwrap();
+    let legacy_path = env._temp_dir.path().join("legacy.txt");
+    std::fs::write(
+        &legacy_path,
+        "app+main=aaaaaaaa-1111-5111-8111-111111111111\napp+dev=cccccccc-1111-5111-8111-111111111111\n",
+    )
+    .unwrap();
+
+    assert_eq!(import_legacy(&legacy_path).unwrap(), (1, 1));
+    assert_eq!(
+        std::fs::read_to_string(get_db_path()).unwrap(),
+        format!("{}cccccccc-1111-5111-8111-111111111111\tname=app+dev\n", existing)
+    );
+
+    // Importing again adds nothing
+    assert_eq!(import_legacy(&legacy_path).unwrap(), (0, 2));
+}
+
+#[test]
+fn test_find_name_merges_keeps_most_recent() {
+    let record = |uuid: &str, name: &str, used: Option<u64>, tags: &[&str]| SessionRecord {
+        last_used: used,
+        tags: tags.iter().map(|t| t.to_string()).collect(),
+        ..SessionRecord::new(uuid, Some(name))
+    };
+    let records = vec![
+        record("uuid-a", "app+main", Some(100), &["wip"]),
+        record("uuid-b", "app+main@1a2b3c4d", Some(300), &[]),
+        record("uuid-c", "app+main#2", Some(50), &[]),
+        record("uuid-d", "other+main", None, &[]),
+        record("uuid-a", "app+main", Some(100), &[]),
+        SessionRecord::new("uuid-e", None),
+    ];
+
+    let merges = find_name_merges(&records);
+
+    assert_eq!(dedupe_key("app+main@1a2b3c4d"), "app+main");
+    // '@' is valid in a branch name; only a base commit qualifier is stripped
+    assert_eq!(dedupe_key("app+user@x"), "app+user@x");
+    assert_eq!(dedupe_key("app+user@home@1a2b3c4d"), "app+user@home");
+    assert_eq!(find_name_merges(&[record("uuid-x", "app+user@x", None, &[]), record("uuid-y", "app+user@y", None, &[])]), vec![]);
+    assert_eq!(
+        merges,
+        vec![NameMerge {
+            keep: record("uuid-b", "app+main@1a2b3c4d", Some(300), &["wip"]),
+            drop: vec![record("uuid-a", "app+main", Some(100), &["wip"])],
+        }]
+    );
+}
+
+#[test]
+#[serial]
+fn test_apply_name_merges_rewrites_database() {
+    let _env = TestEnv::new();
+    std::fs::write(
+        get_db_path(),
+        "uuid-a\tname=app+main\ttags=wip\tused=100\nuuid-d\tname=other+main\nuuid-b\tname=app+main@1a2b3c4d\tused=300\n",
+    )
+    .unwrap();
+
+    let merges = find_name_merges(&load_records());
+    assert_eq!(apply_name_merges(&merges).unwrap(), 1);
+    assert_eq!(
+        std::fs::read_to_string(get_db_path()).unwrap(),
+        "uuid-d\tname=other+main\nuuid-b\tname=app+main@1a2b3c4d\ttags=wip\tused=300\n"
+    );
+}
+
+#[test]
+fn test_gc_records() {
+    let record = |uuid: &str, used: Option<u64>| SessionRecord {
+        last_used: used,
+        ..SessionRecord::new(uuid, None)
+    };
+    let records = vec![
+        record("uuid-fresh", Some(500)),
+        record("uuid-stale", Some(50)),
+        record("uuid-legacy", None),
+        record("uuid-orphan", Some(600)),
+    ];
+    let claude: HashSet<String> = ["uuid-fresh", "uuid-stale", "uuid-legacy"].iter().map(|s| s.to_string()).collect();
+
+    let (kept, pruned, orphaned) = gc_records(records.clone(), Some(100), Some(&claude), 1000);
+    let kept: Vec<&str> = kept.iter().map(|r| r.uuid.as_str()).collect();
+    assert_eq!(kept, vec!["uuid-fresh", "uuid-legacy"]);
+    assert_eq!((pruned, orphaned), (1, 1));
+
+    // Recently used sessions may not have a conversation file yet
+    let (kept, _, orphaned) = gc_records(records.clone(), Some(100), Some(&claude), 600);
+    assert_eq!((kept.len(), orphaned), (3, 0));
+
+    let (kept, pruned, orphaned) = gc_records(records, None, None, 1000);
+    assert_eq!((kept.len(), pruned, orphaned), (4, 0, 0));
+}
+
+#[test]
+fn test_orphans_need_confirmation() {
+    assert!(!orphans_need_confirmation(0, 0));
+    assert!(!orphans_need_confirmation(1, 4));
+    assert!(!orphans_need_confirmation(2, 4));
+    assert!(orphans_need_confirmation(3, 4));
+    assert!(orphans_need_confirmation(1, 1));
+}
+
+#[test]
+#[serial]
+fn test_gc_skips_orphans_with_empty_claude_store() {
+    let _env = TestEnv::new();
+    let claude_dir = TempDir::new().unwrap();
+    std::fs::create_dir(claude_dir.path().join("projects")).unwrap();
+    std::env::set_var("CLAUDE_CONFIG_DIR", claude_dir.path());
+    let old = SessionRecord::new("11111111-1111-5111-8111-111111111111", Some("app+main")).stamped(100);
+    save_record(&old);
+    save_record(&SessionRecord::new("22222222-2222-5222-8222-222222222222", Some("app+dev")).stamped(100));
+
+    let options = GcOptions { repair: false, prune: false, orphans: true };
+    run_gc(&Config::default(), &options, true);
+    assert_eq!(load_records().len(), 2);
+
+    // With conversations in the store, sessions missing from it are removed
+    let project = claude_dir.path().join("projects").join("-app");
+    std::fs::create_dir(&project).unwrap();
+    std::fs::write(project.join(format!("{}.jsonl", old.uuid)), "").unwrap();
+    run_gc(&Config::default(), &options, true);
+    let uuids: Vec<String> = load_records().into_iter().map(|r| r.uuid).collect();
+    assert_eq!(uuids, vec![old.uuid]);
+
+    std::env::remove_var("CLAUDE_CONFIG_DIR");
+}
+
+// ============================================================================
+// Tag tests
+// ============================================================================
+
+#[test]
+fn test_session_record_round_trip() {
+    let legacy = SessionRecord::parse("  4b513bfa-1111-5111-8111-111111111111 ").unwrap();
+    assert_eq!(legacy, SessionRecord::new("4b513bfa-1111-5111-8111-111111111111", None));
+    assert_eq!(legacy.to_line(), "4b513bfa-1111-5111-8111-111111111111");
+
+    let line = "4b513bfa-1111-5111-8111-111111111111\tname=app+main\ttags=review,wip";
+    let record = SessionRecord::parse(line).unwrap();
+    assert_eq!(record.name.as_deref(), Some("app+main"));
+    assert_eq!(record.tags, vec!["review", "wip"]);
+    assert_eq!(record.to_line(), line);
+
+    assert!(SessionRecord::parse(" \t ").is_none());
+
+    let stamped = SessionRecord::new("4b513bfa-1111-5111-8111-111111111111", None).stamped(1700000000);
+    assert_eq!(stamped.to_line(), "4b513bfa-1111-5111-8111-111111111111\tcreated=1700000000\tused=1700000000");
+    assert_eq!(SessionRecord::parse(&stamped.to_line()), Some(stamped));
+}
+
+#[test]
+#[serial]
+fn test_nth_recent_session() {
+    let _env = TestEnv::new();
+    std::fs::write(
+        get_db_path(),
+        "uuid-legacy\nuuid-old\tname=app+old\tused=100\nuuid-new\tname=app+new\tused=300\nuuid-mid\tused=200\n",
+    )
+    .unwrap();
+
+    assert_eq!(nth_recent_session(1).unwrap().uuid, "uuid-new");
+    assert_eq!(nth_recent_session(2).unwrap().uuid, "uuid-mid");
+    assert_eq!(nth_recent_session(4).unwrap().uuid, "uuid-legacy", "legacy entries sort last");
+    assert_eq!(
+        nth_recent_session(5).unwrap_err(),
+        "no session #5; the database has 4 session(s)"
+    );
+    assert!(nth_recent_session(0).is_err());
+}
+
+#[test]
+fn test_picker_lines() {
+    let mut records: Vec<SessionRecord> = (0..10)
+        .map(|i| SessionRecord::new(&format!("{:08x}-1111-5111-8111-111111111111", i), None))
+        .collect();
+    records[0].name = Some("app+main".to_string());
+
+    let lines = picker_lines(&records);
+    assert_eq!(lines[0], " 1) app+main  00000000");
+    assert_eq!(lines[9], "10) (unnamed)  00000009");
+}
+
+#[test]
+fn test_mru_lines() {
+    let record = |uuid: &str, name: Option<&str>, used: Option<u64>| SessionRecord {
+        last_used: used,
+        ..SessionRecord::new(uuid, name)
+    };
+    let records = vec![
+        record("uuid-old", Some("app+old"), Some(10)),
+        record("uuid-legacy", None, None),
+        record("uuid-new", Some("app+main"), Some(30)),
+    ];
+
+    assert_eq!(mru_lines(records.clone(), 10), vec!["1) app+main", "2) app+old", "3) uuid-legacy"]);
+    assert_eq!(mru_lines(records, 1), vec!["1) app+main"]);
+    assert!(mru_lines(Vec::new(), DEFAULT_MRU_COUNT).is_empty());
+}
+
+#[test]
+fn test_record_in_project() {
+    let folders = vec!["app".to_string(), "repo".to_string()];
+    let record = |name: Option<&str>| SessionRecord::new("uuid", name);
+
+    assert!(record_in_project(&record(Some("app+main")), &folders, "+"));
+    assert!(record_in_project(&record(Some("repo#2")), &folders, "+"));
+    assert!(!record_in_project(&record(Some("application+main")), &folders, "+"));
+    assert!(!record_in_project(&record(None), &folders, "+"));
+}
+
+#[test]
+#[serial]
+fn test_release_session_lock() {
+    let _env = TestEnv::new();
+    let uuid = "11111111-1111-5111-8111-111111111111";
+    let lock_path = get_lock_dir().join(format!("{}.lock", uuid));
+
+    acquire_session_lock(uuid).unwrap();
+    assert!(lock_path.exists());
+    release_session_lock(uuid);
+    assert!(!lock_path.exists());
+
+    // Another process's lock is left alone
+    std::fs::write(&lock_path, "1").unwrap();
+    release_session_lock(uuid);
+    assert!(lock_path.exists());
+}
+
+#[test]
+#[serial]
+fn test_lock_db() {
+    let _env = TestEnv::new();
+    let lock_path = get_lock_dir().join("db.lock");
+
+    let outer = lock_db();
+    let other = std::fs::File::open(&lock_path).unwrap();
+    assert!(other.try_lock().is_err(), "the lock is held");
+    // Nested updates share the lock instead of waiting on it
+    drop(lock_db());
+    assert!(other.try_lock().is_err(), "the outer guard still holds the lock");
+    drop(outer);
+    assert!(other.try_lock().is_ok(), "dropping the guard releases the lock");
+}
+
+#[test]
+fn test_touch_project_records() {
+    let record = |uuid: &str, name: Option<&str>| SessionRecord {
+        last_used: Some(10),
+        ..SessionRecord::new(uuid, name)
+    };
+    let mut records = vec![
+        record("uuid-app", Some("app+main")),
+        record("uuid-root", Some("repo")),
+        record("uuid-other", Some("application+main")),
+        record("uuid-legacy", None),
+    ];
+    let folders = vec!["app".to_string(), "repo".to_string()];
+
+    assert_eq!(touch_project_records(&mut records, &folders, "+", 99), 2);
+    let used: Vec<Option<u64>> = records.iter().map(|r| r.last_used).collect();
+    assert_eq!(used, vec![Some(99), Some(99), Some(10), Some(10)]);
+    assert_eq!(touch_project_records(&mut records, &[], "+", 100), 0);
+}
+
+#[test]
+fn test_parse_picker_choice() {
+    assert_eq!(parse_picker_choice("2\n", 3), Ok(Some(2)));
+    assert_eq!(parse_picker_choice("\n", 3), Ok(None));
+    assert_eq!(parse_picker_choice(" Q ", 3), Ok(None));
+    assert!(parse_picker_choice("0", 3).is_err());
+    assert!(parse_picker_choice("4", 3).is_err());
+    assert!(parse_picker_choice("two", 3).is_err());
+}
+
+#[test]
+fn test_session_stats() {
+    let day = 24 * 60 * 60;
+    let now = 100 * day;
+    let record = |name: Option<&str>, created: Option<u64>, used: Option<u64>| SessionRecord {
+        created,
+        last_used: used,
+        ..SessionRecord::new("uuid", name)
+    };
+    let records = vec![
+        record(Some("app+main"), Some(10 * day), Some(now - day)),
+        record(Some("app+feature"), Some(20 * day), Some(now - 10 * day)),
+        record(Some("app#2"), None, None),
+        record(Some("lib"), Some(5 * day), Some(now - 40 * day)),
+        record(None, None, None),
+    ];
+
+    let stats = session_stats(&records, now, "+");
+    assert_eq!(
+        stats,
+        SessionStats {
+            total: 5,
+            used_7d: 1,
+            used_30d: 2,
+            repos: 2,
+            oldest: Some(5 * day),
+            newest: Some(now - day),
+        }
+    );
+    assert_eq!(session_stats(&[], now, "+").oldest, None);
+}
+
+#[test]
+fn test_format_utc() {
+    assert_eq!(format_utc(0), "1970-01-01T00:00:00Z");
+    assert_eq!(format_utc(1706691900), "2024-01-31T09:05:00Z");
+}
+
+#[test]
+#[serial]
+fn test_check_session_assertion() {
+    let _env = TestEnv::new();
+    let identity = SessionIdentity {
+        name: "app+main".to_string(),
+        uuid: generate_uuid5("app+main"),
+        is_git_repo: true,
+    };
+
+    assert!(check_session_assertion(false, &identity).is_ok());
+    assert!(check_session_assertion(true, &identity).unwrap_err().contains("does not exist"));
+
+    save_session(&identity.uuid);
+    assert!(check_session_assertion(true, &identity).is_ok());
+    assert!(check_session_assertion(false, &identity).unwrap_err().contains("already exists"));
+}
+
+#[test]
+#[serial]
+fn test_remove_sessions_by_name_and_uuid() {
+    let _env = TestEnv::new();
+    let renamed = "11111111-1111-1111-1111-111111111111";
+    save_record(&SessionRecord::new(renamed, Some("custom-name")));
+    save_session(&generate_uuid5("app+main"));
+    save_session(&generate_uuid5("app+dev"));
+
+    let records = load_records();
+    assert_eq!(resolve_session_ref("custom-name", &records), renamed);
+    assert_eq!(resolve_session_ref("app+main", &records), generate_uuid5("app+main"));
+    assert_eq!(resolve_session_ref("11111111111111111111111111111111", &records), renamed);
+
+    let targets = vec!["custom-name".to_string(), generate_uuid5("app+main")];
+    assert!(remove_sessions(&targets));
+    let remaining = load_sessions();
+    assert_eq!(remaining.len(), 1);
+    assert!(remaining.contains(&generate_uuid5("app+dev")));
+
+    // Removing again reports the miss
+    assert!(!remove_sessions(&targets));
+    assert!(!remove_session(renamed));
+}
+
+#[test]
+fn test_sort_by_recency_breaks_ties_by_uuid() {
+    let record = |uuid: &str, used: Option<u64>| SessionRecord {
+        last_used: used,
+        ..SessionRecord::new(uuid, None)
+    };
+    let mut records = vec![
+        record("cccc", None),
+        record("bbbb", Some(100)),
+        record("dddd", Some(200)),
+        record("aaaa", None),
+        record("abab", Some(100)),
+    ];
+
+    sort_by_recency(&mut records);
+    let order: Vec<&str> = records.iter().map(|r| r.uuid.as_str()).collect();
+    assert_eq!(order, vec!["dddd", "abab", "bbbb", "aaaa", "cccc"]);
+}
+
+#[test]
+#[serial]
+fn test_adopt_session_stores_name() {
+    let _env = TestEnv::new();
+    let identity = SessionIdentity {
+        name: "app+main".to_string(),
+        uuid: generate_uuid5("app+main"),
+        is_git_repo: true,
+    };
+
+    adopt_session(&identity);
+    adopt_session(&identity);
+    let records = load_records();
+    assert_eq!(records.len(), 1, "adopting twice keeps one entry");
+    assert_eq!(records[0].uuid, identity.uuid);
+    assert_eq!(records[0].name.as_deref(), Some("app+main"));
+    assert!(records[0].created.is_some());
+}
+
+#[cfg(unix)]
+#[test]
+#[serial]
+fn test_symlinked_db_survives_writes() {
+    let temp_dir = TempDir::new().unwrap();
+    let synced = temp_dir.path().join("synced");
+    std::fs::create_dir(&synced).unwrap();
+    let target = synced.join("sessions");
+    let link = temp_dir.path().join("sessions");
+    std::os::unix::fs::symlink("synced/sessions", &link).unwrap();
+    std::env::set_var("CS_DB_PATH", &link);
+
+    save_session("11111111-1111-1111-1111-111111111111");
+    save_session("22222222-2222-2222-2222-222222222222");
+    assert!(remove_session("11111111-1111-1111-1111-111111111111"));
+    touch_session("22222222-2222-2222-2222-222222222222");
+
+    assert!(std::fs::symlink_metadata(&link).unwrap().file_type().is_symlink());
+    let content = std::fs::read_to_string(&target).unwrap();
+    assert!(content.starts_with("22222222-2222-2222-2222-222222222222"));
+    assert!(!content.contains("11111111"));
+
+    clear_sessions(0, false, true);
+    assert!(std::fs::symlink_metadata(&link).unwrap().file_type().is_symlink());
+    assert_eq!(std::fs::read_to_string(&target).unwrap(), "");
+
+    std::env::remove_var("CS_DB_PATH");
+}
+
+#[test]
+fn test_find_session_by_prefix() {
+    let records = vec![
+        SessionRecord::new("4b513bfa-8c71-512b-9a3e-000000000001", Some("app+main")),
+        SessionRecord::new("4b51aaaa-0000-5000-8000-000000000002", Some("app+dev")),
+        SessionRecord::new("9f00cafe-0000-5000-8000-000000000003", None),
+    ];
+
+    assert_eq!(find_session_by_prefix("4b513", &records).unwrap().name.as_deref(), Some("app+main"));
+    assert_eq!(find_session_by_prefix("9F00", &records).unwrap().uuid, records[2].uuid);
+
+    let err = find_session_by_prefix("4b51", &records).unwrap_err();
+    assert!(err.contains("ambiguous"));
+    assert!(err.contains("app+main") && err.contains("app+dev"));
+    assert!(find_session_by_prefix("4b5", &records).unwrap_err().contains("too short"));
+    assert!(find_session_by_prefix("dead", &records).unwrap_err().contains("no tracked session"));
+}
+
+#[test]
+#[serial]
+fn test_save_session_twice_writes_one_line() {
+    let _env = TestEnv::new();
+    let uuid = generate_uuid5("app+main");
+
+    save_session(&uuid);
+    save_session(&uuid);
+    save_record(&SessionRecord::new(&uuid, Some("app+main")));
+
+    let content = std::fs::read_to_string(get_db_path()).unwrap();
+    assert_eq!(content.lines().count(), 1);
+}
+
+#[test]
+#[serial]
+fn test_save_record_fills_in_legacy_entry() {
+    let _env = TestEnv::new();
+    let uuid = generate_uuid5("app+main");
+
+    // A legacy entry: just the UUID
+    save_session(&uuid);
+    save_record(&SessionRecord::new(&uuid, Some("app+main")).stamped(100));
+    let records = load_records();
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0].name.as_deref(), Some("app+main"));
+    assert_eq!((records[0].created, records[0].last_used), (Some(100), Some(100)));
+
+    // What is already stored is kept
+    save_record(&SessionRecord::new(&uuid, Some("app+main")).stamped(200));
+    assert_eq!(load_records()[0].created, Some(100));
+}
+
+#[test]
+#[serial]
+#[cfg(unix)]
+fn test_acquire_session_lock() {
+    let _env = TestEnv::new();
+    let uuid = generate_uuid5("app+main");
+    let lock_path = get_lock_dir().join(format!("{}.lock", uuid));
+
+    acquire_session_lock(&uuid).unwrap();
+    assert_eq!(std::fs::read_to_string(&lock_path).unwrap(), std::process::id().to_string());
+
+    // Held by a live process (this one)
+    let err = acquire_session_lock(&uuid).unwrap_err();
+    assert!(err.contains("already active"), "{}", err);
+
+    // Left behind by a process that has exited
+    let mut child = Command::new("true").spawn().unwrap();
+    let dead_pid = child.id();
+    child.wait().unwrap();
+    std::fs::write(&lock_path, dead_pid.to_string()).unwrap();
+    acquire_session_lock(&uuid).unwrap();
+    assert_eq!(std::fs::read_to_string(&lock_path).unwrap(), std::process::id().to_string());
+}
+
+#[test]
+#[serial]
+fn test_touch_session_updates_last_used() {
+    let _env = TestEnv::new();
+    save_record(&SessionRecord::new("uuid-a", Some("app+a")).stamped(100));
+    save_session("uuid-b");
+
+    touch_session("uuid-a");
+    touch_session("uuid-missing");
+
+    let records = load_records();
+    assert_eq!(records.len(), 2);
+    assert_eq!(records[0].created, Some(100));
+    assert!(records[0].last_used.unwrap() > 100);
+    assert_eq!(records[1].last_used, None);
+}
+
+#[test]
+fn test_conflicting_name() {
+    let records = vec![
+        SessionRecord::new("uuid-a", Some("app+main")),
+        SessionRecord::new("uuid-b", None),
+    ];
+    assert_eq!(conflicting_name(&records, &SessionRecord::new("uuid-a", Some("app-main"))), Some("app+main"));
+    assert_eq!(conflicting_name(&records, &SessionRecord::new("uuid-a", Some("app+main"))), None);
+    assert_eq!(conflicting_name(&records, &SessionRecord::new("uuid-b", Some("lib+main"))), None);
+    assert_eq!(conflicting_name(&records, &SessionRecord::new("uuid-a", None)), None);
+}
+
+#[test]
+fn test_validate_tags() {
+    assert_eq!(validate_tags("review, wip,,").unwrap(), vec!["review", "wip"]);
+    assert!(validate_tags("").is_err());
+    assert!(validate_tags(",").is_err());
+    assert!(validate_tags("two words").is_err());
+    assert!(validate_tags("a=b").is_err());
+}
+
+#[test]
+#[serial]
+fn test_tag_session_adds_and_merges_tags() {
+    let _env = TestEnv::new();
+    let tracked = generate_uuid5("app+main");
+    let other = "uuid-other-1111-2222-333344445555";
+    save_session(&tracked);
+    save_session(other);
+
+    let tags = tag_session(&tracked, "app+main", &["review".to_string()]).unwrap();
+    assert_eq!(tags, vec!["review"]);
+    let tags = tag_session(&tracked, "app+main", &["wip".to_string(), "review".to_string()]).unwrap();
+    assert_eq!(tags, vec!["review", "wip"]);
+
+    let records = load_records();
+    assert_eq!(records.len(), 2, "Tagging must not duplicate entries");
+    assert_eq!(records[0].name.as_deref(), Some("app+main"));
+    assert!(records[0].has_tags(&["wip".to_string()]));
+    assert!(!records[1].has_tags(&["wip".to_string()]));
+    assert!(records[1].has_tags(&[]));
+
+    // Untracked sessions are refused rather than given an entry a later `cs` would resume
+    let new_uuid = generate_uuid5("app+spike");
+    assert!(tag_session(&new_uuid, "app+spike", &["spike".to_string()]).is_err());
+    assert!(!load_sessions().contains(&new_uuid));
+}
+
+#[test]
+fn test_session_identity_with_suffix() {
+    let identity = SessionIdentity {
+        name: "app+main".to_string(),
+        uuid: generate_uuid5("app+main"),
+        is_git_repo: true,
+    };
+    let parallel = identity.with_suffix("2");
+    assert_eq!(parallel.name, "app+main#2");
+    assert_eq!(parallel.uuid, generate_uuid5("app+main#2"));
+    assert!(parallel.is_git_repo);
+}
+
+#[test]
+fn test_next_free_suffix() {
+    let mut sessions = HashSet::new();
+    assert_eq!(next_free_suffix("app+main", &sessions), 1);
+
+    sessions.insert(generate_uuid5("app+main#1"));
+    sessions.insert(generate_uuid5("app+main#3"));
+    assert_eq!(next_free_suffix("app+main", &sessions), 2);
+
+    sessions.insert(generate_uuid5("app+main#2"));
+    assert_eq!(next_free_suffix("app+main", &sessions), 4);
+}
+
+#[test]
+fn test_check_path_args() {
+    let temp_dir = TempDir::new().unwrap();
+    let mcp = temp_dir.path().join("mcp.json");
+    std::fs::write(&mcp, "{}").unwrap();
+    let mcp = mcp.to_string_lossy().to_string();
+    let args = |list: &[&str]| list.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+    let base = temp_dir.path();
+
+    assert!(check_path_args(&args(&["--mcp-config", &mcp, "--model", "opus"]), base).is_ok());
+    assert!(check_path_args(&args(&["--settings", "{\"model\": \"opus\"}"]), base).is_ok());
+    assert!(check_path_args(&args(&["--model", "missing.json", "prompt"]), base).is_ok());
+
+    let err = check_path_args(&args(&["--verbose", "--file", "missing.txt"]), base).unwrap_err();
+    assert_eq!(err, "file not found: missing.txt for --file");
+    let err = check_path_args(&args(&["--json-schema=nope.json"]), base).unwrap_err();
+    assert_eq!(err, "file not found: nope.json for --json-schema");
+
+    // Relative paths resolve against the directory claude starts in
+    assert!(check_path_args(&args(&["--mcp-config", "mcp.json"]), base).is_ok());
+}
+
+#[test]
+fn test_wants_machine_output() {
+    let args = |list: &[&str]| list.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+
+    assert!(wants_machine_output(&args(&["-p", "hi", "--output-format", "json"])));
+    assert!(wants_machine_output(&args(&["--output-format=stream-json"])));
+    assert!(!wants_machine_output(&args(&["--output-format", "text"])));
+    assert!(!wants_machine_output(&args(&["-p", "hi"])));
+    assert!(!wants_machine_output(&args(&["--output-format"])));
+}
+
+#[test]
+fn test_resolve_launch_dir() {
+    let temp_dir = TempDir::new().unwrap();
+    let file = temp_dir.path().join("file.txt");
+    std::fs::write(&file, "").unwrap();
+
+    assert_eq!(
+        resolve_launch_dir(&temp_dir.path().to_string_lossy()),
+        Ok(temp_dir.path().canonicalize().unwrap())
+    );
+    assert!(resolve_launch_dir(&file.to_string_lossy()).is_err());
+    assert!(resolve_launch_dir("/definitely/not/here").is_err());
+}
+
+#[test]
+fn test_session_health_summary() {
+    let identity = SessionIdentity {
+        name: "app+main".to_string(),
+        uuid: "4b513bfa-1111-5111-8111-111111111111".to_string(),
+        is_git_repo: true,
+    };
+    let both = session_health(&identity, true, Ok(true));
+    assert_eq!(both, "session app+main (4b513bfa-1111-5111-8111-111111111111) is in cs's database and Claude's store");
+    assert!(session_health(&identity, true, Ok(false)).contains("cs --reset"));
+    assert!(session_health(&identity, false, Ok(true)).contains("--adopt"));
+    assert!(session_health(&identity, false, Ok(false)).contains("is new"));
+    let unreadable = session_health(&identity, false, Err("missing".to_string()));
+    assert!(unreadable.contains("is not in cs's database; Claude's store could not be read: missing"));
+}
+
+#[test]
+fn test_merge_default_args_precedence() {
+    let args = |list: &[&str]| list.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+    let cli = args(&["--model", "haiku", "fix the bug"]);
+    let project = args(&["--mcp-config", "./mcp.json", "--model", "sonnet", "--add-dir", "docs"]);
+    let user = args(&["--model=opus", "--verbose", "--mcp-config", "~/mcp.json", "--add-dir", "notes"]);
+
+    let merged = merge_default_args(cli, &[&project, &user]);
+    assert_eq!(
+        merged,
+        args(&[
+            "--mcp-config", "./mcp.json", "--add-dir", "docs",
+            "--verbose", "--add-dir", "notes",
+            "--model", "haiku", "fix the bug",
+        ])
+    );
+
+    assert_eq!(merge_default_args(args(&["-c"]), &[&[], &[]]), args(&["-c"]));
+}
+
+#[test]
+fn test_split_args() {
+    assert_eq!(split_args("  --model opus  --verbose ").unwrap(), vec!["--model", "opus", "--verbose"]);
+    assert_eq!(
+        split_args(r#"--append-system-prompt "be brief" --add-dir 'my docs' a\ b"#).unwrap(),
+        vec!["--append-system-prompt", "be brief", "--add-dir", "my docs", "a b"]
+    );
+    assert_eq!(split_args(r#"--x "" 'it\s'"#).unwrap(), vec!["--x", "", "it\\s"]);
+    assert!(split_args("").unwrap().is_empty());
+    assert!(split_args("--model \"opus").is_err());
+}
+
+#[test]
+fn test_set_top_level_array() {
+    let items = vec!["--mcp-config".to_string(), "./my \"mcp\".json".to_string()];
+    assert_eq!(
+        config::set_top_level_array("", "default_args", &items),
+        "default_args = [\"--mcp-config\", \"./my \\\"mcp\\\".json\"]\n"
+    );
+
+    let content = "# project defaults\ndefault_args = [\"-c\"]  # old\n\n[other]\ndefault_args = 1\n";
+    let updated = config::set_top_level_array(content, "default_args", &items[..1]);
+    assert_eq!(updated, "# project defaults\ndefault_args = [\"--mcp-config\"]\n\n[other]\ndefault_args = 1\n");
+
+    let with_section = config::set_top_level_array("[other]\nx = 1", "default_args", &items[..1]);
+    assert_eq!(with_section, "default_args = [\"--mcp-config\"]\n[other]\nx = 1\n");
+}
+
+#[test]
+#[serial]
+fn test_append_project_arg() {
+    let temp_dir = TempDir::new().unwrap();
+    std::env::set_var("CS_HOME", temp_dir.path().join("cs-home"));
+    let path = append_project_arg(temp_dir.path(), "--mcp-config ./mcp.json").unwrap();
+    assert_eq!(path, get_project_root(temp_dir.path()).join(PROJECT_CONFIG_FILE));
+
+    append_project_arg(temp_dir.path(), "--verbose").unwrap();
+    let config = config::load_config(&path, false).unwrap();
+    assert_eq!(config.default_args(), ["--mcp-config", "./mcp.json", "--verbose"]);
+    // Written by the user, so it stays trusted
+    assert_eq!(load_project_config(temp_dir.path()).unwrap().default_args(), config.default_args());
+
+    assert!(append_project_arg(temp_dir.path(), "not-a-flag").is_err());
+    assert!(append_project_arg(temp_dir.path(), "  ").is_err());
+    std::env::remove_var("CS_HOME");
+}
+
+#[test]
+#[serial]
+fn test_project_config_requires_trust() {
+    let temp_dir = TempDir::new().unwrap();
+    std::env::set_var("CS_HOME", temp_dir.path().join("cs-home"));
+    let path = get_project_root(temp_dir.path()).join(PROJECT_CONFIG_FILE);
+    std::fs::write(&path, "default_args = [\"--dangerously-skip-permissions\"]\n").unwrap();
+
+    // A checked-out file is ignored until trusted
+    assert!(load_project_config(temp_dir.path()).unwrap().default_args().is_empty());
+    trust_project_config(&path).unwrap();
+    assert_eq!(load_project_config(temp_dir.path()).unwrap().default_args(), ["--dangerously-skip-permissions"]);
+
+    // Environment variables are never expanded in a project file
+    std::env::set_var("CS_TEST_SECRET", "hunter2");
+    std::fs::write(&path, "default_args = [\"--append-system-prompt\", \"$CS_TEST_SECRET\"]\n").unwrap();
+    trust_project_config(&path).unwrap();
+    assert_eq!(load_project_config(temp_dir.path()).unwrap().default_args(), ["--append-system-prompt", "$CS_TEST_SECRET"]);
+    std::env::remove_var("CS_TEST_SECRET");
+
+    // Any edit needs a new trust, which replaces the old record
+    std::fs::write(&path, "default_args = [\"--verbose\"]\n").unwrap();
+    assert!(load_project_config(temp_dir.path()).unwrap().default_args().is_empty());
+    assert!(append_project_arg(temp_dir.path(), "--chrome").is_ok());
+    assert!(load_project_config(temp_dir.path()).unwrap().default_args().is_empty());
+    trust_project_config(&path).unwrap();
+    assert_eq!(load_project_config(temp_dir.path()).unwrap().default_args(), ["--verbose", "--chrome"]);
+    assert_eq!(std::fs::read_to_string(get_trust_path()).unwrap().lines().count(), 1);
+
+    std::env::remove_var("CS_HOME");
+}
+
+// ============================================================================
+// Launch decision tests
+// ============================================================================
+
+#[test]
+fn test_launch_mode_decide() {
+    assert_eq!(LaunchMode::decide(false, false, true), LaunchMode::Resume);
+    assert_eq!(LaunchMode::decide(false, false, false), LaunchMode::Create);
+    assert_eq!(LaunchMode::decide(false, true, true), LaunchMode::Create);
+    assert_eq!(LaunchMode::decide(true, true, false), LaunchMode::ResumePicker);
+}
+
+#[test]
+fn test_launch_mode_session_args() {
+    let uuid = "4b513bfa-1111-5111-8111-111111111111";
+    assert_eq!(LaunchMode::Resume.session_args(uuid), vec!["-r", uuid]);
+    assert_eq!(LaunchMode::Create.session_args(uuid), vec!["--session-id", uuid]);
+    assert_eq!(LaunchMode::ResumePicker.session_args(uuid), vec!["--resume", uuid]);
+}
+
+#[test]
+fn test_shell_quote() {
+    assert_eq!(shell_quote("--model"), "--model");
+    assert_eq!(shell_quote("./mcp.json"), "./mcp.json");
+    assert_eq!(shell_quote("fix the bug"), "'fix the bug'");
+    assert_eq!(shell_quote("it's"), "'it'\\''s'");
+    assert_eq!(shell_quote(""), "''");
+    assert_eq!(shell_quote("$HOME"), "'$HOME'");
+}
+
+#[test]
+fn test_claude_command_line() {
+    let args: Vec<String> = ["-r", "4b513bfa-1111-5111-8111-111111111111", "--model", "opus", "what's new?"]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+    assert_eq!(
+        claude_command_line(&args),
+        "claude -r 4b513bfa-1111-5111-8111-111111111111 --model opus 'what'\\''s new?'"
+    );
+}
+
+#[test]
+fn test_launcher_argv() {
+    let launcher = |list: &[&str]| list.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+    let npx = launcher(&["npx", "@anthropic-ai/claude-code"]);
+
+    assert_eq!(
+        launcher_argv(&npx, &["-r", "abc", "--model", "opus"]),
+        launcher(&["npx", "@anthropic-ai/claude-code", "-r", "abc", "--model", "opus"])
+    );
+    assert_eq!(launcher_argv(&launcher(&["claude"]), &["--version"]), launcher(&["claude", "--version"]));
+    assert_eq!(launcher_argv::<&str>(&npx, &[]), npx);
+    assert_eq!(claude_launcher(), launcher(&["claude"]), "plain claude unless a launcher is configured");
+
+    let config = config::parse_config("claude_launcher = [\"npx\", \"@anthropic-ai/claude-code\"]\n").unwrap();
+    assert_eq!(config.claude_launcher(), npx.as_slice());
+    assert!(Config::default().claude_launcher().is_empty());
+}
+
+// ============================================================================
+// Status output tests
+// ============================================================================
+
+#[test]
+fn test_json_string_escaping() {
+    assert_eq!(json_string("plain"), "\"plain\"");
+    assert_eq!(json_string("a\"b\\c"), "\"a\\\"b\\\\c\"");
+    assert_eq!(json_string("line\nnext\u{1}"), "\"line\\nnext\\u0001\"");
+}
+
+#[test]
+fn test_parse_json() {
+    assert_eq!(
+        parse_json(" {\"a\": [1, true, null], \"b\": \"x\\\"y\\u0041\\n\"} "),
+        Ok(JsonValue::Object(vec![
+            (
+                "a".to_string(),
+                JsonValue::Array(vec![JsonValue::Number("1".to_string()), JsonValue::Bool(true), JsonValue::Null])
+            ),
+            ("b".to_string(), JsonValue::String("x\"yA\n".to_string())),
+        ]))
+    );
+    assert_eq!(parse_json("[]"), Ok(JsonValue::Array(Vec::new())));
+    for bad in ["", "{", "{\"a\" 1}", "[1,]", "\"open", "{} x", "tru", "{1: 2}", "\"\\u12\""] {
+        assert!(parse_json(bad).is_err(), "{:?} should not parse", bad);
+    }
+
+    // Surrogate pairs (as escaped by e.g. Python's json.dumps) decode to one character
+    assert_eq!(parse_json(r#""\uD83D\uDE00/x""#), Ok(JsonValue::String("\u{1F600}/x".to_string())));
+    for lone in [r#""\uD83D""#, r#""\uD83Dx""#, r#""\uD83D\u0041""#, r#""\uDE00""#] {
+        assert_eq!(parse_json(lone), Err("unpaired surrogate in string".to_string()), "{}", lone);
+    }
+
+    // Nesting is bounded instead of overflowing the stack
+    let nested = |depth: usize| format!("{}{}", "[".repeat(depth), "]".repeat(depth));
+    assert!(parse_json(&nested(JSON_MAX_DEPTH)).is_ok());
+    assert!(parse_json(&nested(JSON_MAX_DEPTH + 1)).unwrap_err().contains("nested deeper"));
+    assert!(parse_json(&"[".repeat(100_000)).is_err());
+}
+
+#[test]
+fn test_parse_json_request() {
+    assert_eq!(
+        parse_json_request("{}"),
+        Ok(JsonRequest { dir: None, mode: JsonRequestMode::Resume, namespace: None, extra_args: Vec::new() })
+    );
+    assert_eq!(
+        parse_json_request(r#"{"dir": "/tmp/app", "mode": "dry", "namespace": "url", "extra_args": ["--model", "opus"]}"#),
+        Ok(JsonRequest {
+            dir: Some("/tmp/app".to_string()),
+            mode: JsonRequestMode::Dry,
+            namespace: Some("url".to_string()),
+            extra_args: vec!["--model".to_string(), "opus".to_string()],
+        })
+    );
+    assert_eq!(parse_json_request(r#"{"mode": "create"}"#).unwrap().mode, JsonRequestMode::Create);
+
+    let error = |input: &str| parse_json_request(input).unwrap_err();
+    assert!(error("{").starts_with("invalid JSON"));
+    assert_eq!(error("[]"), "request must be a JSON object");
+    assert_eq!(error(r#"{"folder": "x"}"#), "unknown field 'folder'");
+    assert_eq!(error(r#"{"mode": "launch"}"#), "'mode' must be resume, create, or dry, got 'launch'");
+    assert_eq!(error(r#"{"dir": 1}"#), "'dir' must be a string");
+    assert_eq!(error(r#"{"extra_args": [1]}"#), "'extra_args' must be an array of strings");
+    assert!(error(r#"{"extra_args": ["--session-id", "x"]}"#).contains("conflicts"));
+}
+
+#[test]
+#[serial]
+fn test_session_status() {
+    let _env = TestEnv::new();
+    let identity = SessionIdentity {
+        name: "app+main".to_string(),
+        uuid: generate_uuid5("app+main"),
+        is_git_repo: true,
+    };
+
+    assert_eq!(session_status(None), "none");
+    assert_eq!(session_status(Some(&identity)), "new");
+    save_session(&identity.uuid);
+    assert_eq!(session_status(Some(&identity)), "exists");
+}
+
+#[test]
+fn test_render_info_box_truncates_to_width() {
+    let long_name = "my-project+feature/a-very-long-branch-name-that-keeps-going";
+    let rendered = render_info_box(&[("Session", long_name)], &ASCII_BOX, Some(40));
+    let lines: Vec<&str> = rendered.lines().collect();
+
+    assert!(lines.iter().all(|l| l.chars().count() <= 40), "{:?}", lines);
+    assert_eq!(lines[0].len(), 40);
+    assert!(lines[1].starts_with("| Session: my-project+feature/"));
+    assert!(lines[1].ends_with("..."));
+}
+
+#[test]
+fn test_render_info_box_expands_when_wider() {
+    let long_name = "my-project+feature/a-very-long-branch-name-that-keeps-going";
+    let rendered = render_info_box(&[("Session", long_name)], &UNICODE_BOX, Some(200));
+    let lines: Vec<&str> = rendered.lines().collect();
+
+    assert_eq!(lines[1], format!("│ Session: {}", long_name));
+    assert_eq!(lines[0].chars().count(), lines[1].chars().count());
+}
+
+#[test]
+fn test_render_info_box_short_values_keep_default_width() {
+    let rendered = render_info_box(&[("Status", "new")], &ASCII_BOX, Some(120));
+    assert_eq!(rendered.lines().next().unwrap().len(), BOX_WIDTH + 1);
+}
@@ -0,0 +1,387 @@
+//! End-to-end checks that run the `cs` binary with a real argument list
+
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output, Stdio};
+use tempfile::TempDir;
+
+/// A cs command in `dir` with its own home (database, config, and marker files under `home`)
+fn cs_command(home: &Path, dir: &Path, args: &[&str]) -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_cs"));
+    command
+        .args(args)
+        .current_dir(dir)
+        .env("CS_HOME", home)
+        .env("CS_CONFIG_PATH", home.join("config.toml"))
+        .env_remove("CS_DB_PATH")
+        .env_remove("CS_PROFILE")
+        .env_remove("CS_READONLY")
+        .env_remove("CS_CLAUDE_ARGS");
+    command
+}
+
+/// Run cs in `dir` with its own home
+fn run_cs(home: &Path, dir: &Path, args: &[&str]) -> Output {
+    cs_command(home, dir, args).output().expect("failed to run cs")
+}
+
+/// Run cs like `run_cs`, feeding `input` on stdin
+fn run_cs_with_stdin(home: &Path, dir: &Path, args: &[&str], input: &str) -> Output {
+    use std::io::Write;
+    let mut child = cs_command(home, dir, args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("failed to run cs");
+    child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+    child.wait_with_output().expect("failed to run cs")
+}
+
+/// A home with `project/` tracked in its database
+fn tracked_project() -> (TempDir, std::path::PathBuf) {
+    let temp_dir = TempDir::new().unwrap();
+    let project = temp_dir.path().join("project");
+    fs::create_dir(&project).unwrap();
+    let adopted = run_cs(temp_dir.path(), &project, &["--adopt"]);
+    assert!(adopted.status.success(), "{}", String::from_utf8_lossy(&adopted.stderr));
+    (temp_dir, project)
+}
+
+#[test]
+fn test_dry_run_flag_order_never_writes() {
+    let (temp_dir, project) = tracked_project();
+    let home = temp_dir.path();
+    let db_path = home.join("sessions");
+    let before = fs::read(&db_path).unwrap();
+    fs::write(home.join("config.toml"), "separator = \"::\"\n").unwrap();
+
+    for args in [
+        &["--rm", "project", "--dry-run"][..],
+        &["--dry-run", "--rm", "project"],
+        &["--reset", "--dry-run"],
+        &["-n", "--tag", "wip"],
+        &["--repair", "-n"],
+        &["--gc", "--no-orphans", "-n"],
+    ] {
+        let output = run_cs(home, &project, args);
+        assert!(output.status.success(), "{:?}: {}", args, String::from_utf8_lossy(&output.stderr));
+        assert_eq!(fs::read(&db_path).unwrap(), before, "{:?} changed the database", args);
+    }
+
+    let removed = run_cs(home, &project, &["--rm", "project", "--dry-run"]);
+    assert!(String::from_utf8_lossy(&removed.stdout).starts_with("Would remove project ("));
+    // Neither the separator marker nor a backup is written
+    assert!(!home.join("separator").exists());
+    assert!(!home.join("backups").exists());
+}
+
+#[test]
+fn test_rm_backs_up_first() {
+    let (temp_dir, project) = tracked_project();
+    let home = temp_dir.path();
+    let before = fs::read_to_string(home.join("sessions")).unwrap();
+
+    let output = run_cs(home, &project, &["--rm", "project", "--ascii"]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Removed project ("));
+    assert_eq!(fs::read_to_string(home.join("sessions")).unwrap(), "");
+
+    let backups: Vec<_> = fs::read_dir(home.join("backups")).unwrap().map(|e| e.unwrap().path()).collect();
+    assert_eq!(backups.len(), 1);
+    assert_eq!(fs::read_to_string(&backups[0]).unwrap(), before);
+
+    let missing = run_cs(home, &project, &["--rm", "project"]);
+    assert!(!missing.status.success());
+}
+
+#[test]
+fn test_stdin_json_uses_the_launch_pipeline() {
+    let (temp_dir, project) = tracked_project();
+    let home = temp_dir.path();
+    fs::write(home.join("config.toml"), "default_args = [\"--model\", \"opus\"]\n").unwrap();
+    let request = format!(r#"{{"dir": {:?}, "mode": "dry", "extra_args": ["--verbose"]}}"#, project.display().to_string());
+
+    // Run from elsewhere: the request's dir picks the session, default_args apply
+    let output = run_cs_with_stdin(home, home, &["--stdin-json"], &request);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.starts_with(r#"{"name":"project","#), "{}", stdout);
+    assert!(stdout.contains(r#""status":"exists""#), "{}", stdout);
+    assert!(stdout.contains(r#""launched":false"#), "{}", stdout);
+    assert!(stdout.contains(r#""--model","opus","--verbose"]"#), "{}", stdout);
+
+    // Flags after --stdin-json still count
+    let forced = run_cs_with_stdin(home, home, &["--stdin-json", "--force"], &request);
+    let stdout = String::from_utf8_lossy(&forced.stdout);
+    assert!(stdout.contains(r#""argv":["--session-id","#), "{}", stdout);
+
+    // A request namespace changes the UUID without affecting the database
+    let before = fs::read(home.join("sessions")).unwrap();
+    let namespaced = request.replace(r#""mode""#, r#""namespace": "url", "mode""#);
+    let output = run_cs_with_stdin(home, home, &["--stdin-json"], &namespaced);
+    assert!(String::from_utf8_lossy(&output.stdout).contains(r#""status":"new""#));
+    assert_eq!(fs::read(home.join("sessions")).unwrap(), before);
+
+    let invalid = run_cs_with_stdin(home, home, &["--stdin-json"], "{\"mode\": \"launch\"}");
+    assert_eq!(invalid.status.code(), Some(64));
+    assert!(String::from_utf8_lossy(&invalid.stdout).starts_with(r#"{"error":"#));
+}
+
+#[test]
+#[cfg(unix)]
+fn test_recover_missing_session_starts_it_afresh() {
+    let (temp_dir, project) = tracked_project();
+    let home = temp_dir.path();
+    let claude_dir = home.join("claude");
+    let store = claude_dir.join("projects").join("-other");
+    fs::create_dir_all(&store).unwrap();
+    fs::write(store.join("22222222-2222-5222-8222-222222222222.jsonl"), "").unwrap();
+    // `echo` stands in for claude and prints the arguments it was given
+    let launch = |config: &str| {
+        fs::write(home.join("config.toml"), config).unwrap();
+        cs_command(home, &project, &["--skip-version-check"])
+            .env("CLAUDE_CONFIG_DIR", &claude_dir)
+            .env("CS_CLAUDE_LAUNCHER", "echo")
+            .output()
+            .expect("failed to run cs")
+    };
+
+    // Off by default: the stale entry is resumed as before
+    let output = launch("");
+    assert!(String::from_utf8_lossy(&output.stdout).starts_with("-r "), "{:?}", output);
+
+    let output = launch("recover_missing_sessions = true\n");
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert!(String::from_utf8_lossy(&output.stdout).starts_with("--session-id "));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("starting it as a new session"));
+    assert!(fs::read_to_string(home.join("sessions")).unwrap().contains("name=project"));
+}
+
+#[test]
+fn test_status_reports_go_to_stderr() {
+    let (temp_dir, project) = tracked_project();
+    let home = temp_dir.path();
+
+    for (args, expected) in [
+        (&["--repair"][..], "Repaired "),
+        (&["--backup"], "Backup saved to "),
+        (&["--gc", "--no-orphans"], "Backup saved to "),
+        (&["--clone", "project", "copy"], "Destination registered"),
+        (&["--rm", "copy"], "Backup saved to "),
+        (&["--tag", "wip"], "Tagged project ("),
+        (&["--touch-all"], "Touched 1 session(s)"),
+        (&["--dedupe-names", "-y"], "No duplicate session names found."),
+        (&["--clear", "-y"], "Session database cleared."),
+        (&["--adopt"], "Adopted project ("),
+    ] {
+        let output = run_cs(home, &project, args);
+        assert!(output.status.success(), "{:?}: {}", args, String::from_utf8_lossy(&output.stderr));
+        assert!(String::from_utf8_lossy(&output.stderr).contains(expected), "{:?}: {:?}", args, output);
+        assert!(!String::from_utf8_lossy(&output.stdout).contains(expected), "{:?} reported on stdout", args);
+    }
+}
+
+#[test]
+fn test_interactive_without_terminal_launches_normally() {
+    let (temp_dir, project) = tracked_project();
+
+    // Command::output gives cs a closed stdin, so there is nobody to pick
+    let output = run_cs(temp_dir.path(), &project, &["--interactive", "--dry-run", "--ascii"]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("project") && stderr.contains("exists"), "{}", stderr);
+    assert!(!stderr.contains("Resume which session"), "{}", stderr);
+}
+
+#[test]
+fn test_prompt_after_double_dash_goes_to_claude() {
+    let (temp_dir, project) = tracked_project();
+
+    let output = run_cs(temp_dir.path(), &project, &["--print-argv", "-p", "--", "--namespace", "--list"]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let argv = String::from_utf8_lossy(&output.stdout);
+    assert!(argv.ends_with("-p\n--\n--namespace\n--list\n"), "{}", argv);
+
+    let output = run_cs(temp_dir.path(), &project, &["--print-argv", "--system-prompt", "--profile"]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert!(String::from_utf8_lossy(&output.stdout).ends_with("--system-prompt\n--profile\n"));
+}
+
+#[test]
+fn test_copy_cmd_copies_only_with_clipboard() {
+    let (temp_dir, project) = tracked_project();
+
+    let output = run_cs(temp_dir.path(), &project, &["--copy-cmd"]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert!(String::from_utf8_lossy(&output.stdout).starts_with("claude -r "));
+    assert!(String::from_utf8_lossy(&output.stderr).is_empty(), "{}", String::from_utf8_lossy(&output.stderr));
+
+    let stray = run_cs(temp_dir.path(), &project, &["--clipboard"]);
+    assert!(!stray.status.success());
+    assert!(String::from_utf8_lossy(&stray.stderr).contains("'--clipboard' requires --copy-cmd"));
+}
+
+#[test]
+fn test_open_claude_config_opens_only_with_reveal() {
+    let temp_dir = TempDir::new().unwrap();
+    let claude_dir = temp_dir.path().join("claude");
+
+    let output = cs_command(temp_dir.path(), temp_dir.path(), &["--open-claude-config"])
+        .env("CLAUDE_CONFIG_DIR", &claude_dir)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), claude_dir.display().to_string());
+    assert!(!String::from_utf8_lossy(&output.stderr).contains("folder does not exist yet"));
+
+    let stray = run_cs(temp_dir.path(), temp_dir.path(), &["--reveal"]);
+    assert!(!stray.status.success());
+    assert!(String::from_utf8_lossy(&stray.stderr).contains("'--reveal' requires --open-claude-config"));
+}
+
+#[test]
+#[cfg(unix)]
+fn test_watch_recreates_a_session_claude_cannot_resume() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let (temp_dir, project) = tracked_project();
+    let home = temp_dir.path();
+    // Stands in for claude: fails to resume like claude does, and echoes anything else
+    let claude = home.join("fake-claude");
+    fs::write(
+        &claude,
+        "#!/bin/sh\nif [ \"$1\" = -r ]; then echo \"No conversation found with session ID: $2\" >&2; exit 1; fi\necho \"$@\"\n",
+    )
+    .unwrap();
+    fs::set_permissions(&claude, fs::Permissions::from_mode(0o755)).unwrap();
+
+    let output = cs_command(home, &project, &["--watch", "--skip-version-check"])
+        .env("CS_CLAUDE_LAUNCHER", &claude)
+        .output()
+        .expect("failed to run cs");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "{}", stderr);
+    assert!(stderr.contains("No conversation found"), "claude's stderr is still shown: {}", stderr);
+    assert!(stderr.contains("starting it as a new session"), "{}", stderr);
+    assert!(String::from_utf8_lossy(&output.stdout).starts_with("--session-id "));
+    assert!(fs::read_to_string(home.join("sessions")).unwrap().contains("name=project"));
+}
+
+#[test]
+fn test_concurrent_updates_keep_every_change() {
+    let temp_dir = TempDir::new().unwrap();
+    let home = temp_dir.path();
+    let projects: Vec<_> = (0..32)
+        .map(|n| {
+            let project = home.join(format!("project-{}", n));
+            fs::create_dir(&project).unwrap();
+            assert!(run_cs(home, &project, &["--adopt"]).status.success());
+            project
+        })
+        .collect();
+
+    // Each `--tag` rewrites the whole database
+    let children: Vec<_> = projects
+        .iter()
+        .map(|project| {
+            cs_command(home, project, &["--tag", "wip"])
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .spawn()
+                .unwrap()
+        })
+        .collect();
+    for mut child in children {
+        assert!(child.wait().unwrap().success());
+    }
+
+    let db = fs::read_to_string(home.join("sessions")).unwrap();
+    assert_eq!(db.lines().filter(|line| line.contains("tags=wip")).count(), 32, "{}", db);
+}
+
+#[test]
+fn test_separator_change_is_recorded_only_on_launch() {
+    let (temp_dir, project) = tracked_project();
+    let home = temp_dir.path();
+    fs::write(home.join("config.toml"), "separator = \"::\"\n").unwrap();
+
+    // Shell-prompt queries neither warn nor write the marker
+    for args in [&["--status"][..], &["--print-name"], &["--git-info"]] {
+        let output = run_cs(home, &project, args);
+        assert!(!String::from_utf8_lossy(&output.stderr).contains("separator changed"), "{:?}", args);
+        assert!(!home.join("separator").exists(), "{:?}", args);
+    }
+
+    let output = cs_command(home, &project, &["--skip-version-check"])
+        .env("CS_CLAUDE_LAUNCHER", "echo")
+        .output()
+        .expect("failed to run cs");
+    assert!(String::from_utf8_lossy(&output.stderr).contains("separator changed from '+' to '::'"));
+    assert_eq!(fs::read_to_string(home.join("separator")).unwrap(), "::\n");
+}
+
+#[test]
+fn test_empty_separator_is_rejected() {
+    let (temp_dir, project) = tracked_project();
+    let home = temp_dir.path();
+    fs::write(home.join("config.toml"), "separator = \"\"\n").unwrap();
+
+    let output = run_cs(home, &project, &["--print-name"]);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("separator: must not be empty"));
+
+    let strict = cs_command(home, &project, &["--print-name"]).env("CS_STRICT_CONFIG", "1").output().unwrap();
+    assert!(!strict.status.success());
+    assert!(String::from_utf8_lossy(&strict.stderr).contains("separator: must not be empty"));
+}
+
+/// A home with `repo/` as a git repository on branch `feature/x`
+fn git_project() -> (TempDir, std::path::PathBuf) {
+    let temp_dir = TempDir::new().unwrap();
+    let repo = temp_dir.path().join("repo");
+    fs::create_dir(&repo).unwrap();
+    let git = |args: &[&str]| {
+        let output = Command::new("git").args(args).current_dir(&repo).output().expect("git should be installed");
+        assert!(output.status.success(), "git {:?}: {}", args, String::from_utf8_lossy(&output.stderr));
+    };
+    git(&["init", "-q"]);
+    git(&["checkout", "-q", "-b", "feature/x"]);
+    git(&["-c", "user.name=cs", "-c", "user.email=cs@example.com", "commit", "-q", "--allow-empty", "-m", "init"]);
+    (temp_dir, repo)
+}
+
+#[test]
+fn test_git_info_reports_repo_or_folder() {
+    let (temp_dir, repo) = git_project();
+    let output = run_cs(temp_dir.path(), &repo, &["--git-info"]);
+    assert_eq!(output.status.code(), Some(0), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "git repo+feature/x\n");
+
+    let folder = temp_dir.path().join("plain");
+    fs::create_dir(&folder).unwrap();
+    let output = run_cs(temp_dir.path(), &folder, &["--git-info"]);
+    assert_eq!(output.status.code(), Some(5), "not a git repository");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "folder plain\n");
+}
+
+#[test]
+fn test_print_name_shows_the_computed_name() {
+    let (temp_dir, repo) = git_project();
+    let home = temp_dir.path();
+    let print_name = |dir: &Path, args: &[&str]| {
+        let output = run_cs(home, dir, args);
+        assert!(output.status.success(), "{:?}: {}", args, String::from_utf8_lossy(&output.stderr));
+        String::from_utf8_lossy(&output.stdout).into_owned()
+    };
+
+    assert_eq!(print_name(&repo, &["--print-name"]), "repo+feature/x\n");
+    assert_eq!(print_name(&repo, &["--print-name", "--json"]), "{\"name\":\"repo+feature/x\"}\n");
+    assert_eq!(print_name(&repo, &["--print-name", "--no-git"]), "repo\n");
+    assert_eq!(print_name(&repo, &["--print-name", "--session-name", "custom"]), "custom\n");
+
+    let suffixed = cs_command(home, &repo, &["--print-name"]).env("CS_SESSION_SUFFIX", "2").output().unwrap();
+    assert_eq!(String::from_utf8_lossy(&suffixed.stdout), "repo+feature/x#2\n");
+
+    // Nothing is written to the database
+    assert!(!home.join("sessions").exists());
+}